@@ -0,0 +1,156 @@
+// Piezo buzzer output, resampled from CPU ticks to a host sample rate
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    mem,
+    path::Path,
+};
+
+use crate::{Pc1500, lh5810, pd1990ac::FREQUENCY, wav};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+// LH5810 port C pin the buzzer is wired to
+const BUZZER_BIT: u8 = 0x40;
+const AMPLITUDE: i64 = i16::MAX as i64 / 2;
+// DC blocking filter pole, as a fraction of 256
+const DC_POLE: i64 = 254;
+
+/// Turns the buzzer line into PCM samples.
+///
+/// Each sample averages the line level over its period, so tones keep their
+/// pitch and duration at any sample rate. The piezo only responds to
+/// changes, a DC blocking filter brings a line held high or low back to
+/// silence.
+#[derive(Debug)]
+pub struct Beeper {
+    sample_rate: u32,
+    level: bool,
+    // Positions in units of 1 / sample_rate ticks
+    position: u64,
+    sample_end: u64,
+    high: u64,
+    previous_input: i64,
+    previous_output: i64,
+    samples: Vec<i16>,
+}
+
+impl Beeper {
+    #[must_use]
+    pub const fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            level: false,
+            position: 0,
+            sample_end: 0,
+            high: 0,
+            previous_input: -AMPLITUDE,
+            previous_output: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Samples produced since the last call, for streaming to the host.
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        mem::take(&mut self.samples)
+    }
+
+    /// Starts sampling at `ticks` without producing sound for the time
+    /// before it.
+    fn start(&mut self, ticks: usize, level: bool) {
+        self.position = ticks as u64 * u64::from(self.sample_rate);
+        self.sample_end = self.position + FREQUENCY as u64;
+        self.high = 0;
+        self.level = level;
+        self.previous_input = if level { AMPLITUDE } else { -AMPLITUDE };
+    }
+
+    fn step(&mut self, ticks: usize, level: bool) {
+        let now = ticks as u64 * u64::from(self.sample_rate);
+        let period = FREQUENCY as u64;
+
+        // The previous level lasted until now
+        while self.sample_end <= now {
+            if self.level {
+                self.high += self.sample_end - self.position;
+            }
+            self.position = self.sample_end;
+            self.sample_end += period;
+
+            let input = (self.high.cast_signed() * 2 - period.cast_signed()) * AMPLITUDE
+                / period.cast_signed();
+            self.high = 0;
+            let output = input - self.previous_input + self.previous_output * DC_POLE / 256;
+            self.previous_input = input;
+            self.previous_output = output;
+            self.samples.push(
+                i16::try_from(output.clamp(i16::MIN.into(), i16::MAX.into())).unwrap_or_default(),
+            );
+        }
+        if self.level {
+            self.high += now - self.position;
+        }
+        self.position = now;
+        self.level = level;
+    }
+}
+
+impl Default for Beeper {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+/// Writes buzzer samples as a mono 16-bit WAV file.
+pub fn write_wav<W: io::Write>(writer: W, sample_rate: u32, samples: &[i16]) -> io::Result<()> {
+    wav::write_wav(writer, sample_rate, samples)
+}
+
+pub fn save_wav<P: AsRef<Path>>(path: P, sample_rate: u32, samples: &[i16]) -> io::Result<()> {
+    write_wav(BufWriter::new(File::create(path)?), sample_rate, samples)
+}
+
+impl Pc1500 {
+    /// Starts producing buzzer samples at `sample_rate`, to be collected
+    /// with [`Pc1500::take_audio`].
+    pub fn enable_audio(&mut self, sample_rate: u32) {
+        let mut beeper = Beeper::new(sample_rate);
+        beeper.start(self.lh5801.get_ticks(), self.buzzer());
+        self.beeper = Some(beeper);
+    }
+
+    pub const fn disable_audio(&mut self) -> Option<Beeper> {
+        self.beeper.take()
+    }
+
+    #[must_use]
+    pub fn audio_sample_rate(&self) -> Option<u32> {
+        self.beeper.as_ref().map(Beeper::sample_rate)
+    }
+
+    /// Buzzer samples produced since the last call. Empty while audio is
+    /// disabled.
+    pub fn take_audio(&mut self) -> Vec<i16> {
+        self.beeper
+            .as_mut()
+            .map(Beeper::take_samples)
+            .unwrap_or_default()
+    }
+
+    fn buzzer(&self) -> bool {
+        self.lh5810.get_reg(lh5810::Reg::OPC) & BUZZER_BIT != 0
+    }
+
+    pub(crate) fn step_audio(&mut self) {
+        let level = self.buzzer();
+        let ticks = self.lh5801.get_ticks();
+        if let Some(beeper) = &mut self.beeper {
+            beeper.step(ticks, level);
+        }
+    }
+}
@@ -0,0 +1,622 @@
+// BASIC programs in RAM, listed as plain text
+
+use core::{error::Error, fmt, str};
+use std::collections::BTreeMap;
+
+use crate::{Pc1500, memory::STANDARD_USER_MEMORY_END};
+
+// Program pointers kept by the ROM, big-endian
+pub const PROGRAM_START: u32 = 0x7865;
+pub const PROGRAM_END: u32 = 0x7867;
+
+// Line layout: number (big-endian), length of the rest, text, CR
+const LINE_HEADER_SIZE: usize = 3;
+const END_OF_LINE: u8 = 0x0D;
+// No line number has this high byte
+const END_OF_PROGRAM: u8 = 0xFF;
+const MAX_LINE_NUMBER: u16 = 0xFEFF;
+// Two-byte tokens start with a byte in this range
+const TOKEN_PREFIX: u8 = 0xE0;
+
+const QUOTE: u8 = b'"';
+const REM: u16 = 0xF1AB;
+
+/// Where a keyword comes from: the PC-1500 ROM or the ROM of an expansion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeywordSource {
+    Pc1500,
+    Ce150,
+    Ce158,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keyword {
+    pub code: u16,
+    pub name: &'static str,
+    pub source: KeywordSource,
+}
+
+const fn keyword(code: u16, name: &'static str) -> Keyword {
+    Keyword {
+        code,
+        name,
+        source: KeywordSource::Pc1500,
+    }
+}
+
+const fn ce150(code: u16, name: &'static str) -> Keyword {
+    Keyword {
+        code,
+        name,
+        source: KeywordSource::Ce150,
+    }
+}
+
+const fn ce158(code: u16, name: &'static str) -> Keyword {
+    Keyword {
+        code,
+        name,
+        source: KeywordSource::Ce158,
+    }
+}
+
+/// Every keyword the detokenizer knows, by code. The codes the bundled
+/// bathyscaph.bin uses are checked against its listing in tests/basic.rs,
+/// the CE-158 ones are unverified without its ROM.
+pub const KEYWORDS: &[Keyword] = &[
+    // Display and I/O statements
+    keyword(0xF084, "CURSOR"),
+    keyword(0xF085, "USING"),
+    keyword(0xF088, "CLS"),
+    keyword(0xF089, "CLOAD"),
+    keyword(0xF08F, "MERGE"),
+    keyword(0xF090, "LIST"),
+    keyword(0xF091, "INPUT"),
+    keyword(0xF093, "GCURSOR"),
+    keyword(0xF095, "CSAVE"),
+    keyword(0xF097, "PRINT"),
+    keyword(0xF09F, "GPRINT"),
+    keyword(0xF0B2, "CHAIN"),
+    // CE-150 printer and plotter
+    ce150(0xF0B5, "COLOR"),
+    ce150(0xF0B6, "LF"),
+    ce150(0xF0B7, "LINE"),
+    ce150(0xF0B8, "LLIST"),
+    ce150(0xF0B9, "LPRINT"),
+    ce150(0xF0BA, "RLINE"),
+    ce150(0xF0BB, "TAB"),
+    ce150(0xF0BC, "TEST"),
+    ce150(0xE680, "CSIZE"),
+    ce150(0xE681, "GRAPH"),
+    ce150(0xE682, "GLCURSOR"),
+    ce150(0xE683, "LCURSOR"),
+    ce150(0xE684, "SORGN"),
+    ce150(0xE685, "ROTATE"),
+    ce150(0xE686, "TEXT"),
+    // Operators and functions
+    keyword(0xF150, "AND"),
+    keyword(0xF151, "OR"),
+    keyword(0xF158, "MEM"),
+    keyword(0xF15B, "TIME"),
+    keyword(0xF15C, "INKEY$"),
+    keyword(0xF15D, "PI"),
+    keyword(0xF160, "ASC"),
+    keyword(0xF161, "STR$"),
+    keyword(0xF162, "VAL"),
+    keyword(0xF163, "CHR$"),
+    keyword(0xF164, "LEN"),
+    keyword(0xF165, "DEG"),
+    keyword(0xF166, "DMS"),
+    keyword(0xF167, "STATUS"),
+    keyword(0xF168, "POINT"),
+    keyword(0xF16B, "SQR"),
+    keyword(0xF16D, "NOT"),
+    keyword(0xF16E, "PEEK#"),
+    keyword(0xF16F, "PEEK"),
+    keyword(0xF170, "ABS"),
+    keyword(0xF171, "INT"),
+    keyword(0xF172, "RIGHT$"),
+    keyword(0xF173, "ASN"),
+    keyword(0xF174, "ACS"),
+    keyword(0xF175, "ATN"),
+    keyword(0xF176, "LN"),
+    keyword(0xF177, "LOG"),
+    keyword(0xF178, "EXP"),
+    keyword(0xF179, "SGN"),
+    keyword(0xF17A, "LEFT$"),
+    keyword(0xF17B, "MID$"),
+    keyword(0xF17C, "RND"),
+    keyword(0xF17D, "SIN"),
+    keyword(0xF17E, "COS"),
+    keyword(0xF17F, "TAN"),
+    // Statements
+    keyword(0xF180, "AREAD"),
+    keyword(0xF181, "ARUN"),
+    keyword(0xF182, "BEEP"),
+    keyword(0xF183, "CONT"),
+    keyword(0xF186, "GRAD"),
+    keyword(0xF187, "CLEAR"),
+    keyword(0xF18A, "CALL"),
+    keyword(0xF18B, "DIM"),
+    keyword(0xF18C, "DEGREE"),
+    keyword(0xF18D, "DATA"),
+    keyword(0xF18E, "END"),
+    keyword(0xF192, "GOTO"),
+    keyword(0xF194, "GOSUB"),
+    keyword(0xF196, "IF"),
+    keyword(0xF198, "LET"),
+    keyword(0xF199, "RETURN"),
+    keyword(0xF19A, "NEXT"),
+    keyword(0xF19B, "NEW"),
+    keyword(0xF19C, "ON"),
+    keyword(0xF19D, "OPN"),
+    keyword(0xF19E, "OFF"),
+    keyword(0xF1A0, "POKE#"),
+    keyword(0xF1A1, "POKE"),
+    keyword(0xF1A2, "PAUSE"),
+    keyword(0xF1A4, "RUN"),
+    keyword(0xF1A5, "FOR"),
+    keyword(0xF1A6, "READ"),
+    keyword(0xF1A7, "RESTORE"),
+    keyword(0xF1A8, "RANDOM"),
+    keyword(0xF1AA, "RADIAN"),
+    keyword(REM, "REM"),
+    keyword(0xF1AC, "STOP"),
+    keyword(0xF1AD, "STEP"),
+    keyword(0xF1AE, "THEN"),
+    keyword(0xF1AF, "TROFF"),
+    keyword(0xF1B0, "TRON"),
+    keyword(0xF1B1, "TO"),
+    keyword(0xF1B3, "WAIT"),
+    keyword(0xF1B4, "ERROR"),
+    keyword(0xF1B5, "LOCK"),
+    keyword(0xF1B6, "UNLOCK"),
+    // CE-158 serial and parallel interface
+    ce158(0xE880, "COM$"),
+    ce158(0xE881, "DEV$"),
+    ce158(0xE882, "DTE"),
+    ce158(0xE883, "FEED"),
+    ce158(0xE884, "INSTAT"),
+    ce158(0xE885, "OUTSTAT"),
+    ce158(0xE886, "RINKEY$"),
+    ce158(0xE887, "SETCOM"),
+    ce158(0xE888, "SETDEV"),
+    ce158(0xE889, "TERMINAL"),
+    ce158(0xE88A, "TRANSMIT"),
+    ce158(0xE88B, "ZONE"),
+    ce158(0xE88C, "CONSOLE"),
+];
+
+/// Shortest abbreviations accepted for keywords, as typed with the final
+/// period. Any longer prefix of the keyword followed by a period works too.
+pub const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("A.", "AREAD"),
+    ("ARU.", "ARUN"),
+    ("B.", "BEEP"),
+    ("C.", "CONT"),
+    ("CA.", "CALL"),
+    ("CH.", "CHR$"),
+    ("CHA.", "CHAIN"),
+    ("CL.", "CLEAR"),
+    ("CLO.", "CLOAD"),
+    ("CS.", "CSAVE"),
+    ("CU.", "CURSOR"),
+    ("D.", "DIM"),
+    ("DA.", "DATA"),
+    ("DE.", "DEGREE"),
+    ("E.", "END"),
+    ("F.", "FOR"),
+    ("G.", "GOTO"),
+    ("GCU.", "GCURSOR"),
+    ("GOS.", "GOSUB"),
+    ("GP.", "GPRINT"),
+    ("GR.", "GRAD"),
+    ("I.", "INPUT"),
+    ("INK.", "INKEY$"),
+    ("L.", "LIST"),
+    ("LE.", "LET"),
+    ("LEF.", "LEFT$"),
+    ("LOC.", "LOCK"),
+    ("M.", "MERGE"),
+    ("MI.", "MID$"),
+    ("N.", "NEXT"),
+    ("P.", "PRINT"),
+    ("PA.", "PAUSE"),
+    ("PE.", "PEEK"),
+    ("PO.", "POKE"),
+    ("POI.", "POINT"),
+    ("R.", "RUN"),
+    ("RA.", "RANDOM"),
+    ("RAD.", "RADIAN"),
+    ("RE.", "RETURN"),
+    ("REA.", "READ"),
+    ("RES.", "RESTORE"),
+    ("RI.", "RIGHT$"),
+    ("S.", "STOP"),
+    ("STA.", "STATUS"),
+    ("STE.", "STEP"),
+    ("STR.", "STR$"),
+    ("T.", "THEN"),
+    ("TI.", "TIME"),
+    ("TR.", "TRON"),
+    ("TROF.", "TROFF"),
+    ("U.", "USING"),
+    ("UN.", "UNLOCK"),
+    ("V.", "VAL"),
+    ("W.", "WAIT"),
+];
+
+#[must_use]
+pub fn keyword_by_code(code: u16) -> Option<&'static Keyword> {
+    KEYWORDS.iter().find(|keyword| keyword.code == code)
+}
+
+#[must_use]
+pub fn keyword_by_name(name: &str) -> Option<&'static Keyword> {
+    KEYWORDS.iter().find(|keyword| keyword.name == name)
+}
+
+/// The keyword abbreviated by `letters`, typed without the final period.
+#[must_use]
+pub fn keyword_by_abbreviation(letters: &str) -> Option<&'static Keyword> {
+    ABBREVIATIONS
+        .iter()
+        .filter(|(abbreviation, name)| {
+            let shortest = abbreviation.trim_end_matches('.');
+            letters.starts_with(shortest) && name.starts_with(letters)
+        })
+        .find_map(|(_, name)| keyword_by_name(name))
+}
+
+/// The character shown for a byte of the PC-1500 character set. Bytes
+/// outside the printable range have no text form.
+#[must_use]
+pub fn char_from_byte(byte: u8) -> Option<char> {
+    (0x20..0x7F).contains(&byte).then_some(char::from(byte))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramError {
+    offset: usize,
+    reason: &'static str,
+}
+
+impl ProgramError {
+    const fn new(offset: usize, reason: &'static str) -> Self {
+        Self { offset, reason }
+    }
+
+    /// Offset of the broken line from the start of the program.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.reason)
+    }
+}
+
+impl Error for ProgramError {}
+
+/// One program line, as stored in RAM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line<'a> {
+    pub number: u16,
+    /// Tokenized text, without the final CR
+    pub text: &'a [u8],
+}
+
+/// Splits a tokenized program into its lines. The program ends with its
+/// bytes or at an end marker.
+pub fn lines(program: &[u8]) -> Result<Vec<Line<'_>>, ProgramError> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < program.len() && program[offset] != END_OF_PROGRAM {
+        let Some(header) = program.get(offset..offset + LINE_HEADER_SIZE) else {
+            return Err(ProgramError::new(offset, "line header cut short"));
+        };
+        let number = u16::from(header[0]) << 8 | u16::from(header[1]);
+        let end = offset + LINE_HEADER_SIZE + usize::from(header[2]);
+        let Some(text) = program.get(offset + LINE_HEADER_SIZE..end) else {
+            return Err(ProgramError::new(offset, "line cut short"));
+        };
+        let Some((&END_OF_LINE, text)) = text.split_last() else {
+            return Err(ProgramError::new(offset, "line does not end with CR"));
+        };
+
+        lines.push(Line { number, text });
+        offset = end;
+    }
+
+    Ok(lines)
+}
+
+pub(crate) fn push_char(output: &mut String, byte: u8) {
+    match char_from_byte(byte) {
+        Some(c) => output.push(c),
+        None => push_code(output, byte.into(), 2),
+    }
+}
+
+// Writes `code` as `digits` hexadecimal digits in brackets
+fn push_code(output: &mut String, code: u16, digits: u16) {
+    output.push('[');
+    for digit in (0..digits).rev() {
+        let nibble = (code >> (digit * 4)) & 0x0F;
+        output.extend(char::from_digit(nibble.into(), 16).map(|c| c.to_ascii_uppercase()));
+    }
+    output.push(']');
+}
+
+/// Turns the tokenized text of a line into plain text. Bytes and tokens
+/// without a text form are written as `[XX]` and `[XXXX]` in hexadecimal.
+#[must_use]
+pub fn detokenize(text: &[u8]) -> String {
+    let mut output = String::new();
+    let mut quoted = false;
+    let mut bytes = text.iter().copied().peekable();
+
+    while let Some(byte) = bytes.next() {
+        if quoted || byte < TOKEN_PREFIX {
+            quoted ^= byte == QUOTE;
+            push_char(&mut output, byte);
+            continue;
+        }
+
+        let Some(low) = bytes.next() else {
+            push_char(&mut output, byte);
+            break;
+        };
+        let code = u16::from(byte) << 8 | u16::from(low);
+        let Some(keyword) = keyword_by_code(code) else {
+            push_code(&mut output, code, 4);
+            continue;
+        };
+
+        // Keywords are set apart from names and numbers around them
+        if output
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '"' | ')' | '$'))
+        {
+            output.push(' ');
+        }
+        output.push_str(keyword.name);
+        if code != REM
+            && bytes
+                .peek()
+                .is_some_and(|&next| !b":,;()=<>+*/^".contains(&next))
+        {
+            output.push(' ');
+        }
+
+        // The rest of a remark is kept as typed
+        if code == REM {
+            for remark in bytes.by_ref() {
+                push_char(&mut output, remark);
+            }
+        }
+    }
+
+    output
+}
+
+/// Lists a tokenized program as plain text, one numbered line per line.
+pub fn list(program: &[u8]) -> Result<String, ProgramError> {
+    let mut listing = String::new();
+    for line in lines(program)? {
+        listing.push_str(&line.number.to_string());
+        listing.push(' ');
+        listing.push_str(&detokenize(line.text));
+        listing.push('\n');
+    }
+    Ok(listing)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseProgramError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl ParseProgramError {
+    const fn new(line: usize, reason: &'static str) -> Self {
+        Self { line, reason }
+    }
+
+    /// Line of the source text, counted from 1.
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for ParseProgramError {}
+
+// Reads a `[XX]` or `[XXXX]` code written by the detokenizer, returning its
+// bytes and the length of its text
+fn parse_code(text: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let digits = text
+        .iter()
+        .skip(1)
+        .take_while(|byte| byte.is_ascii_hexdigit())
+        .count();
+    if text.first() != Some(&b'[') || text.get(digits + 1) != Some(&b']') {
+        return None;
+    }
+
+    let hex = str::from_utf8(&text[1..=digits]).ok()?;
+    let bytes = match digits {
+        2 => vec![u8::from_str_radix(hex, 16).ok()?],
+        4 => {
+            let [low, high] = u16::from_str_radix(hex, 16).ok()?.to_le_bytes();
+            vec![high, low]
+        }
+        _ => return None,
+    };
+    Some((bytes, digits + 2))
+}
+
+// The keyword at the start of `text`, either abbreviated or the longest one
+// spelled out, with the length of its text
+fn parse_keyword(text: &[u8]) -> Option<(&'static Keyword, usize)> {
+    let letters = text
+        .iter()
+        .take_while(|byte| byte.is_ascii_uppercase())
+        .count();
+    if letters > 0 && text.get(letters) == Some(&b'.') {
+        let abbreviated = str::from_utf8(&text[..letters])
+            .ok()
+            .and_then(keyword_by_abbreviation);
+        if let Some(keyword) = abbreviated {
+            return Some((keyword, letters + 1));
+        }
+    }
+
+    KEYWORDS
+        .iter()
+        .filter(|keyword| text.starts_with(keyword.name.as_bytes()))
+        .max_by_key(|keyword| keyword.name.len())
+        .map(|keyword| (keyword, keyword.name.len()))
+}
+
+// Turns the plain text of a line into its tokenized form, the inverse of
+// `detokenize`. Spaces outside strings and remarks are dropped, as the ROM
+// does when a line is entered.
+fn tokenize(text: &str) -> Result<Vec<u8>, &'static str> {
+    let text = text.as_bytes();
+    let mut output = Vec::new();
+    let mut quoted = false;
+    let mut remark = false;
+    let mut offset = 0;
+
+    while let Some(&byte) = text.get(offset) {
+        if let Some((bytes, length)) = parse_code(&text[offset..]) {
+            output.extend(bytes);
+            offset += length;
+            continue;
+        }
+        if char_from_byte(byte).is_none() {
+            return Err("character not in the PC-1500 character set");
+        }
+
+        if !quoted && !remark {
+            if byte == b' ' {
+                offset += 1;
+                continue;
+            }
+            if let Some((keyword, length)) = parse_keyword(&text[offset..]) {
+                let [low, high] = keyword.code.to_le_bytes();
+                output.extend([high, low]);
+                remark = keyword.code == REM;
+                offset += length;
+                continue;
+            }
+        }
+
+        quoted ^= byte == QUOTE && !remark;
+        output.push(byte);
+        offset += 1;
+    }
+
+    Ok(output)
+}
+
+// Tokenized lines by number, with the source line each comes from. As on
+// the keyboard, a later line replaces an earlier one with the same number
+// and a number alone deletes the line.
+fn parse_lines(source: &str) -> Result<BTreeMap<u16, (usize, Vec<u8>)>, ParseProgramError> {
+    let mut lines = BTreeMap::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let source_line = index + 1;
+        let line = line.trim_start();
+        if line.trim_end().is_empty() {
+            continue;
+        }
+
+        let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+        let (number, text) = line.split_at(digits);
+        let number = number
+            .parse()
+            .ok()
+            .filter(|number| (1..=MAX_LINE_NUMBER).contains(number))
+            .ok_or_else(|| ParseProgramError::new(source_line, "invalid line number"))?;
+        let text = tokenize(text).map_err(|reason| ParseProgramError::new(source_line, reason))?;
+        if text.is_empty() {
+            lines.remove(&number);
+            continue;
+        }
+
+        let length = u8::try_from(text.len() + 1)
+            .ok()
+            .ok_or_else(|| ParseProgramError::new(source_line, "line too long"))?;
+        let [low, high] = number.to_le_bytes();
+        let mut bytes = vec![high, low, length];
+        bytes.extend(text);
+        bytes.push(END_OF_LINE);
+        lines.insert(number, (source_line, bytes));
+    }
+
+    Ok(lines)
+}
+
+/// Tokenizes a program written as plain text, one numbered line per line,
+/// into the format the ROM keeps in RAM. Lines are sorted by number.
+pub fn parse_program(source: &str) -> Result<Vec<u8>, ParseProgramError> {
+    Ok(parse_lines(source)?
+        .into_values()
+        .flat_map(|(_, bytes)| bytes)
+        .collect())
+}
+
+impl Pc1500 {
+    /// The tokenized program between the ROM program pointers.
+    #[must_use]
+    pub fn program_bytes(&self) -> Vec<u8> {
+        self.read_bytes(self.read_word(PROGRAM_START), self.read_word(PROGRAM_END))
+    }
+
+    /// Lists the BASIC program in RAM.
+    pub fn list_program(&self) -> Result<String, ProgramError> {
+        list(&self.program_bytes())
+    }
+
+    /// Replaces the BASIC program in RAM with one written as plain text. The
+    /// program is stored from the current start pointer and the end pointer
+    /// is moved past it, as if each line had been typed in.
+    pub fn load_program(&mut self, source: &str) -> Result<(), ParseProgramError> {
+        let mut addr = self.read_word(PROGRAM_START);
+
+        for (source_line, bytes) in parse_lines(source)?.into_values() {
+            // Leaves room for the end marker
+            let end = u16::try_from(bytes.len())
+                .ok()
+                .and_then(|size| addr.checked_add(size))
+                .filter(|&end| u32::from(end) <= STANDARD_USER_MEMORY_END)
+                .ok_or_else(|| {
+                    ParseProgramError::new(source_line, "program does not fit in memory")
+                })?;
+            for (byte_addr, byte) in (addr..end).zip(bytes) {
+                self.write_byte(u32::from(byte_addr), byte);
+            }
+            addr = end;
+        }
+
+        self.write_byte(u32::from(addr), END_OF_PROGRAM);
+        let [low, high] = addr.to_le_bytes();
+        self.write_byte(PROGRAM_END, high);
+        self.write_byte(PROGRAM_END + 1, low);
+        Ok(())
+    }
+}
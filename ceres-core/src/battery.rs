@@ -0,0 +1,162 @@
+// RAM and real-time clock contents kept by the batteries between sessions
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta};
+
+use crate::{
+    ClockTime, Pc1500,
+    memory::{STANDARD_USER_MEMORY_SIZE, STANDARD_USER_SYSTEM_MEMORY_SIZE},
+};
+
+pub const MAGIC: &[u8; 8] = b"PC15BATT";
+const VERSION: u8 = 1;
+
+// Clock offset and weekday shift
+const CLOCK_SIZE: usize = 8 + 1;
+
+/// What a PC-1500 keeps while switched off: the user RAM, including the
+/// BASIC program and variables, and its own notion of time.
+///
+/// The clock is stored as an offset from the host clock, so it keeps
+/// running while the emulator is not. For fixed and manual clock sources
+/// the offset is taken from their start time instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatteryBackup {
+    user_memory: Vec<u8>,
+    system_memory: Vec<u8>,
+    clock_offset: i64,
+    // The weekday counter is set independently of the date
+    weekday_shift: u8,
+}
+
+impl BatteryBackup {
+    /// Seconds the emulated clock is ahead of the host clock.
+    #[must_use]
+    pub const fn clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(self.clock_offset.to_le_bytes());
+        bytes.push(self.weekday_shift);
+        bytes.extend(&self.user_memory);
+        bytes.extend(&self.system_memory);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let rest = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| invalid_data("not a PC-1500 battery backup"))?;
+        let Some((&VERSION, rest)) = rest.split_first() else {
+            return Err(invalid_data("unsupported battery backup version"));
+        };
+
+        if rest.len() != CLOCK_SIZE + STANDARD_USER_MEMORY_SIZE + STANDARD_USER_SYSTEM_MEMORY_SIZE {
+            return Err(invalid_data("battery backup has the wrong size"));
+        }
+        let (clock, memory) = rest.split_at(CLOCK_SIZE);
+        let (user_memory, system_memory) = memory.split_at(STANDARD_USER_MEMORY_SIZE);
+
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&clock[..8]);
+        Ok(Self {
+            user_memory: user_memory.to_vec(),
+            system_memory: system_memory.to_vec(),
+            clock_offset: i64::from_le_bytes(offset),
+            weekday_shift: clock[8] % 7,
+        })
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// The date and time shown by `time`, in the year that puts it closest to
+/// `reference` since the chip has no year counter. `None` if the counters
+/// hold no valid date.
+fn clock_datetime(time: ClockTime, reference: NaiveDateTime) -> Option<NaiveDateTime> {
+    let year = reference.year();
+    (year - 1..=year + 1)
+        .filter_map(|year| {
+            NaiveDate::from_ymd_opt(year, time.month.into(), time.day.into())?.and_hms_opt(
+                time.hour.into(),
+                time.minute.into(),
+                time.second.into(),
+            )
+        })
+        .min_by_key(|datetime| (*datetime - reference).abs())
+}
+
+fn weekday(datetime: NaiveDateTime) -> u8 {
+    u8::try_from(datetime.weekday().num_days_from_sunday()).unwrap_or_default()
+}
+
+impl Pc1500 {
+    /// Takes the battery-backed state, to be restored in a later session
+    /// with [`Pc1500::restore_battery_backup`].
+    #[must_use]
+    pub fn battery_backup(&self) -> BatteryBackup {
+        let time = self.clock_time();
+        let reference = self.clock_source.start_time();
+        let (clock_offset, weekday_shift) =
+            clock_datetime(time, reference).map_or((0, 0), |datetime| {
+                (
+                    (datetime - reference).num_seconds(),
+                    (time.weekday + 7 - weekday(datetime)) % 7,
+                )
+            });
+
+        BatteryBackup {
+            user_memory: self.memory.standard_user_memory.to_vec(),
+            system_memory: self.memory.standard_user_system_memory.to_vec(),
+            clock_offset,
+            weekday_shift,
+        }
+    }
+
+    /// Restores RAM and moves the real-time clock by the saved offset from
+    /// the clock source.
+    pub fn restore_battery_backup(&mut self, backup: &BatteryBackup) {
+        self.memory
+            .standard_user_memory
+            .copy_from_slice(&backup.user_memory);
+        self.memory
+            .standard_user_system_memory
+            .copy_from_slice(&backup.system_memory);
+
+        let datetime = self.clock_source.start_time()
+            + TimeDelta::try_seconds(backup.clock_offset).unwrap_or_default();
+        let mut time = ClockTime::from(datetime);
+        time.weekday = (time.weekday + backup.weekday_shift) % 7;
+        self.set_clock_time(time);
+    }
+}
@@ -0,0 +1,222 @@
+// Numbers as stored by the ROM arithmetic routines: a binary exponent, a
+// sign and twelve BCD mantissa digits
+
+use core::ops::RangeInclusive;
+
+use crate::Pc1500;
+
+// Arithmetic registers of the ROM
+pub const ARX: u32 = 0x7A00;
+pub const ARY: u32 = 0x7A10;
+
+/// Size of a number in memory.
+pub const NUMBER_SIZE: usize = 8;
+
+// Byte layout
+const EXPONENT: usize = 0;
+const SIGN: usize = 1;
+const MANTISSA: usize = 2;
+
+const NEGATIVE: u8 = 0x80;
+const DIGITS: usize = 12;
+// Numbers range from 1E-99 to 9.99999999999E99, smaller ones become zero
+const MAX_EXPONENT: i32 = 99;
+const MIN_EXPONENT: i32 = -99;
+// Exponents written without one in decimal strings
+const PLAIN_EXPONENTS: RangeInclusive<i32> = -10..=11;
+// Enough digits to write any f64 exactly
+const F64_DIGITS: usize = 767;
+
+/// Zero has a single encoding, without sign.
+pub const ZERO: [u8; NUMBER_SIZE] = [0; NUMBER_SIZE];
+
+// A decoded number, the value being `d.ddddddddddd` times ten to the power
+// of the exponent
+struct Decimal {
+    negative: bool,
+    digits: [u8; DIGITS],
+    exponent: i32,
+}
+
+impl Decimal {
+    fn decode(bytes: [u8; NUMBER_SIZE]) -> Option<Self> {
+        let mut digits = [0; DIGITS];
+        for (pair, &byte) in digits.chunks_exact_mut(2).zip(&bytes[MANTISSA..]) {
+            pair.copy_from_slice(&[byte >> 4, byte & 0x0F]);
+        }
+        if digits.iter().any(|&digit| digit > 9) {
+            return None;
+        }
+
+        Some(Self {
+            negative: bytes[SIGN] & NEGATIVE != 0,
+            digits,
+            exponent: bytes[EXPONENT].cast_signed().into(),
+        })
+    }
+}
+
+// Encodes `0.ddd…` times ten to the power of `point`, rounding to twelve
+// significant digits half away from zero
+fn encode(negative: bool, digits: &[u8], point: i32) -> Option<[u8; NUMBER_SIZE]> {
+    let Some(first) = digits.iter().position(|&digit| digit != 0) else {
+        return Some(ZERO);
+    };
+    let mut exponent = point.checked_sub(1 + i32::try_from(first).ok()?)?;
+    let significant = &digits[first..];
+
+    let mut rounded = [0; DIGITS];
+    for (target, &digit) in rounded.iter_mut().zip(significant) {
+        *target = digit;
+    }
+    if significant.get(DIGITS).is_some_and(|&digit| digit >= 5) {
+        for digit in rounded.iter_mut().rev() {
+            *digit = (*digit + 1) % 10;
+            if *digit != 0 {
+                break;
+            }
+        }
+        // Every digit carried over
+        if rounded[0] == 0 {
+            rounded[0] = 1;
+            exponent += 1;
+        }
+    }
+
+    if exponent < MIN_EXPONENT {
+        return Some(ZERO);
+    }
+    if exponent > MAX_EXPONENT {
+        return None;
+    }
+
+    let mut bytes = ZERO;
+    bytes[EXPONENT] = i8::try_from(exponent).ok()?.cast_unsigned();
+    if negative {
+        bytes[SIGN] = NEGATIVE;
+    }
+    for (byte, pair) in bytes[MANTISSA..].iter_mut().zip(rounded.chunks_exact(2)) {
+        *byte = pair[0] << 4 | pair[1];
+    }
+    Some(bytes)
+}
+
+/// Decodes a number. Returns `None` when a mantissa digit is not decimal.
+#[must_use]
+pub fn to_f64(bytes: [u8; NUMBER_SIZE]) -> Option<f64> {
+    let decimal = Decimal::decode(bytes)?;
+    let mut text = String::new();
+    if decimal.negative {
+        text.push('-');
+    }
+    for digit in decimal.digits {
+        text.push(char::from(b'0' + digit));
+    }
+    text.push('e');
+    text.push_str(&(decimal.exponent - 11).to_string());
+    text.parse().ok()
+}
+
+/// Encodes `value` rounded to twelve significant digits. Returns `None` for
+/// NaN, infinities and numbers too large for the exponent.
+#[must_use]
+pub fn from_f64(value: f64) -> Option<[u8; NUMBER_SIZE]> {
+    if !value.is_finite() {
+        return None;
+    }
+    from_decimal(&format!("{value:.F64_DIGITS$e}"))
+}
+
+/// Writes a number in decimal with every significant digit, in scientific
+/// notation when its exponent is large. Returns `None` when a mantissa
+/// digit is not decimal.
+#[must_use]
+pub fn to_decimal(bytes: [u8; NUMBER_SIZE]) -> Option<String> {
+    let decimal = Decimal::decode(bytes)?;
+    let length = decimal
+        .digits
+        .iter()
+        .rposition(|&digit| digit != 0)
+        .map_or(1, |last| last + 1);
+    let digits: String = decimal.digits[..length]
+        .iter()
+        .map(|&digit| char::from(b'0' + digit))
+        .collect();
+
+    let mut text = String::new();
+    if decimal.negative && digits != "0" {
+        text.push('-');
+    }
+
+    if !PLAIN_EXPONENTS.contains(&decimal.exponent) {
+        let (first, rest) = digits.split_at(1);
+        text.push_str(first);
+        if !rest.is_empty() {
+            text.push('.');
+            text.push_str(rest);
+        }
+        text.push('E');
+        text.push_str(&decimal.exponent.to_string());
+    } else if let Ok(exponent) = usize::try_from(decimal.exponent) {
+        let padded = format!("{digits:0<width$}", width = exponent + 1);
+        let (integer, fraction) = padded.split_at(exponent + 1);
+        text.push_str(integer);
+        if !fraction.is_empty() {
+            text.push('.');
+            text.push_str(fraction);
+        }
+    } else {
+        text.push_str("0.");
+        for _ in decimal.exponent + 1..0 {
+            text.push('0');
+        }
+        text.push_str(&digits);
+    }
+
+    Some(text)
+}
+
+/// Parses a decimal number such as `-12.5` or `1.5E-20`, rounded to twelve
+/// significant digits. Returns `None` for malformed text and numbers too
+/// large for the exponent.
+#[must_use]
+pub fn from_decimal(text: &str) -> Option<[u8; NUMBER_SIZE]> {
+    let text = text.trim();
+    let negative = text.starts_with('-');
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (mantissa, exponent) = match unsigned.split_once(['E', 'e']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let digits: Vec<u8> = integer
+        .bytes()
+        .chain(fraction.bytes())
+        .map(|byte| byte.is_ascii_digit().then(|| byte - b'0'))
+        .collect::<Option<_>>()?;
+    if digits.is_empty() {
+        return None;
+    }
+
+    let point = i32::try_from(integer.len()).ok()?.checked_add(exponent)?;
+    encode(negative, &digits, point)
+}
+
+impl Pc1500 {
+    /// The number stored at `addr`, such as a variable or [`ARX`].
+    #[must_use]
+    pub fn read_number(&self, addr: u32) -> [u8; NUMBER_SIZE] {
+        let mut bytes = ZERO;
+        for (byte, byte_addr) in bytes.iter_mut().zip(addr..) {
+            *byte = self.read_byte(byte_addr);
+        }
+        bytes
+    }
+
+    pub fn write_number(&mut self, addr: u32, bytes: [u8; NUMBER_SIZE]) {
+        for (byte_addr, byte) in (addr..).zip(bytes) {
+            self.write_byte(byte_addr, byte);
+        }
+    }
+}
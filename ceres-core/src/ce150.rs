@@ -0,0 +1,403 @@
+// CE-150 four-colour printer/plotter
+
+use crate::{
+    Pc1500,
+    display::GLYPH_WIDTH,
+    lh5810::{self, Lh5810},
+    paper::{Paper, PenColor},
+    peripheral::{Bank, BusCycle, Peripheral},
+};
+
+// CE-150 ROM on ME0 and its LH5810 on ME1
+pub const ROM_BEGIN: u16 = 0xA000;
+pub const ROM_END: u16 = 0xBFFF;
+pub const IO_BEGIN: u16 = 0xB000;
+pub const IO_END: u16 = 0xB00F;
+
+// Port A drives the carriage motor coils on the low nibble and the paper
+// motor coils on the high nibble, port B lowers the pen
+const CARRIAGE_COILS: u8 = 0x0F;
+const PAPER_COILS_SHIFT: u8 = 4;
+const PEN_DOWN: u8 = 0x20;
+
+/// Carriage travel from the left stop, in plotter steps
+pub const CARRIAGE_TRAVEL: i32 = 216;
+
+// Character cells at the default CSIZE 2, in plotter steps: 5x7 dots two
+// steps apart with one blank column, 18 characters across the paper
+const DOT_PITCH: i32 = 2;
+const CELL_WIDTH: i32 = 6 * DOT_PITCH;
+const LINE_HEIGHT: i32 = 10 * DOT_PITCH;
+const GLYPH_HEIGHT: i32 = 7;
+
+// Coil patterns of the eight half steps of a four-phase stepper motor
+const PHASES: [u8; 8] = [0x1, 0x3, 0x2, 0x6, 0x4, 0xC, 0x8, 0x9];
+
+fn register(addr: u16) -> Option<lh5810::Reg> {
+    u8::try_from(addr - IO_BEGIN).ok().and_then(lh5810::Reg::at)
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Stepper {
+    phase: Option<u8>,
+}
+
+impl Stepper {
+    /// Energises `coils`, returning how many half steps the rotor turns.
+    /// Unknown patterns and released coils hold the rotor in place.
+    fn drive(&mut self, coils: u8) -> i32 {
+        let Some(phase) = (0..8).find(|&phase| PHASES[usize::from(phase)] == coils) else {
+            return 0;
+        };
+
+        let turn = self
+            .phase
+            .map_or(0, |previous| match (phase + 8 - previous) % 8 {
+                1 => 1,
+                2 => 2,
+                6 => -2,
+                7 => -1,
+                _ => 0,
+            });
+        self.phase = Some(phase);
+        turn
+    }
+}
+
+/// The CE-150 printer/plotter, attached to the PC-1500 expansion bus.
+///
+/// Its LH5810 drives the carriage and paper stepper motors and the pen
+/// solenoid, and the cassette lines of the [`TapeDeck`](crate::tape::TapeDeck)
+/// through its serial unit: SDO modulates the tape output and the tape
+/// input drives SDI, read in bit 6 of MSK. The ROM times the input against
+/// TP of the PD1990AC on port B of the PC-1500 LH5810.
+/// Pushing the carriage against the left stop turns the pen drum to the
+/// next colour once the carriage moves away again. The CE-150 ROM is not
+/// distributed with the emulator and has to be supplied to run LPRINT and
+/// GRAPH programs.
+///
+/// Paper follows whatever the ROM does to the motors. The statements of
+/// the CE-150 BASIC extension are also available as methods that move the
+/// pen directly, for driving the plotter without its ROM: [`text`],
+/// [`graph`], [`glcursor`], [`line`], [`rline`], [`set_color`] and
+/// [`Pc1500::lprint`]. BASIC programs do not reach them, as the CE-150 ROM
+/// entry points of those statements are not known.
+///
+/// [`text`]: Self::text
+/// [`graph`]: Self::graph
+/// [`glcursor`]: Self::glcursor
+/// [`line`]: Self::line
+/// [`rline`]: Self::rline
+/// [`set_color`]: Self::set_color
+pub struct Ce150 {
+    lh5810: Lh5810,
+    rom: Vec<u8>,
+    carriage: Stepper,
+    paper_feed: Stepper,
+    // Pen position in half steps
+    x: i32,
+    y: i32,
+    pen_down: bool,
+    color: PenColor,
+    at_left_stop: bool,
+    paper: Paper,
+    mode: PlotMode,
+    // GRAPH origin in plotter steps
+    origin: (i32, i32),
+}
+
+/// What the CE-150 statements draw: characters in lines, or lines in
+/// coordinates from the origin.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlotMode {
+    #[default]
+    Text,
+    Graph,
+}
+
+impl Ce150 {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rom(Vec::new())
+    }
+
+    #[must_use]
+    pub fn with_rom(rom: Vec<u8>) -> Self {
+        Self {
+            lh5810: Lh5810::new(),
+            rom,
+            carriage: Stepper::default(),
+            paper_feed: Stepper::default(),
+            x: 0,
+            y: 0,
+            pen_down: false,
+            color: PenColor::default(),
+            at_left_stop: false,
+            paper: Paper::default(),
+            mode: PlotMode::default(),
+            origin: (0, 0),
+        }
+    }
+
+    #[must_use]
+    pub const fn paper(&self) -> &Paper {
+        &self.paper
+    }
+
+    pub const fn paper_mut(&mut self) -> &mut Paper {
+        &mut self.paper
+    }
+
+    #[must_use]
+    pub const fn pen_color(&self) -> PenColor {
+        self.color
+    }
+
+    #[must_use]
+    pub const fn is_pen_down(&self) -> bool {
+        self.pen_down
+    }
+
+    /// Pen position in plotter steps, x from the left stop and y growing as
+    /// the paper feeds out.
+    #[must_use]
+    pub const fn position(&self) -> (i32, i32) {
+        (self.x.div_euclid(2), self.y.div_euclid(2))
+    }
+
+    #[must_use]
+    pub const fn mode(&self) -> PlotMode {
+        self.mode
+    }
+
+    /// TEXT: lifts the pen and returns the carriage to the start of the
+    /// next line.
+    pub fn text(&mut self) {
+        self.mode = PlotMode::Text;
+        if self.position().0 != 0 {
+            self.new_line();
+        }
+    }
+
+    /// GRAPH: the current pen position becomes the origin.
+    pub const fn graph(&mut self) {
+        self.mode = PlotMode::Graph;
+        self.sorgn();
+    }
+
+    /// SORGN: moves the origin to the current pen position.
+    pub const fn sorgn(&mut self) {
+        self.origin = self.position();
+    }
+
+    /// GLCURSOR: moves to `point` with the pen up.
+    pub fn glcursor(&mut self, point: (i32, i32)) {
+        self.plot_to(self.graph_to_paper(point), false);
+    }
+
+    /// LINE: draws from the pen position through `points`. `LINE (a)-(b)`
+    /// is [`glcursor`](Self::glcursor) to `a`, then a line to `b`.
+    pub fn line(&mut self, points: &[(i32, i32)]) {
+        self.plot_to(self.position(), true);
+        for &point in points {
+            self.plot_to(self.graph_to_paper(point), true);
+        }
+        self.plot_to(self.position(), false);
+    }
+
+    /// RLINE: draws through `offsets`, each relative to the point before.
+    pub fn rline(&mut self, offsets: &[(i32, i32)]) {
+        let (mut x, mut y) = self.paper_to_graph(self.position());
+        let points: Vec<(i32, i32)> = offsets
+            .iter()
+            .map(|&(dx, dy)| {
+                (x, y) = (x + dx, y + dy);
+                (x, y)
+            })
+            .collect();
+        self.line(&points);
+    }
+
+    /// COLOR: turns the pen drum to `color`.
+    pub const fn set_color(&mut self, color: PenColor) {
+        self.paper.pen_up();
+        self.color = color;
+    }
+
+    /// Draws the 5x7 `glyph` in the current character cell and moves to the
+    /// next one, starting a new line at the right edge of the paper.
+    pub fn print_glyph(&mut self, glyph: [u8; GLYPH_WIDTH]) {
+        if self.position().0 + CELL_WIDTH > CARRIAGE_TRAVEL {
+            self.new_line();
+        }
+        let (left, top) = self.position();
+        for (x, column) in (left..).step_by(2).zip(glyph) {
+            // Each run of dots in a column is one stroke
+            let mut row = 0;
+            while row < GLYPH_HEIGHT {
+                if column & (1 << row) == 0 {
+                    row += 1;
+                    continue;
+                }
+                let first = row;
+                while row < GLYPH_HEIGHT && column & (1 << row) != 0 {
+                    row += 1;
+                }
+                self.plot_to((x, top + first * DOT_PITCH), false);
+                self.plot_to((x, top + first * DOT_PITCH), true);
+                self.plot_to((x, top + (row - 1) * DOT_PITCH), true);
+            }
+        }
+        self.plot_to((left + CELL_WIDTH, top), false);
+    }
+
+    /// Returns the carriage and feeds the paper by one line.
+    pub fn new_line(&mut self) {
+        let (_, top) = self.position();
+        self.plot_to((0, top + LINE_HEIGHT), false);
+    }
+
+    // GRAPH coordinates grow upwards from the origin, paper feeds downwards
+    const fn graph_to_paper(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (self.origin.0 + x, self.origin.1 - y)
+    }
+
+    const fn paper_to_graph(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (x - self.origin.0, self.origin.1 - y)
+    }
+
+    /// Moves the pen straight to `point`, clamped to the carriage travel.
+    fn plot_to(&mut self, (x, y): (i32, i32), pen_down: bool) {
+        self.x = x.clamp(0, CARRIAGE_TRAVEL) * 2;
+        self.y = y * 2;
+        self.pen_down = pen_down;
+        if pen_down {
+            self.paper.pen_at(self.position(), self.color);
+        } else {
+            self.paper.pen_up();
+        }
+    }
+
+    /// Follows the motor coils and pen solenoid driven by the LH5810.
+    fn move_pen(&mut self) {
+        let port_a = self.lh5810.output_a();
+        let dx = self.carriage.drive(port_a & CARRIAGE_COILS);
+        let dy = self.paper_feed.drive(port_a >> PAPER_COILS_SHIFT);
+        let pen_down = self.lh5810.output_b() & PEN_DOWN != 0;
+
+        if dx == 0 && dy == 0 && pen_down == self.pen_down {
+            return;
+        }
+
+        if self.x + dx < 0 {
+            self.x = 0;
+            self.at_left_stop = true;
+        } else {
+            if dx > 0 && self.at_left_stop {
+                self.at_left_stop = false;
+                self.color = self.color.next();
+                self.paper.pen_up();
+            }
+            self.x = (self.x + dx).min(CARRIAGE_TRAVEL * 2);
+        }
+        self.y += dy;
+        self.pen_down = pen_down;
+
+        if pen_down {
+            self.paper.pen_at(self.position(), self.color);
+        } else {
+            self.paper.pen_up();
+        }
+    }
+}
+
+impl Pc1500 {
+    /// LPRINT: prints `text` and a new line on the first attached CE-150
+    /// with the character patterns of the PC-1500 ROM. Returns false
+    /// without a CE-150.
+    pub fn lprint(&mut self, text: &str) -> bool {
+        let glyphs: Vec<Option<[u8; GLYPH_WIDTH]>> = text
+            .chars()
+            .map(|c| match c {
+                '\n' => None,
+                c => Some(
+                    u8::try_from(c)
+                        .ok()
+                        .and_then(|code| self.glyph(code))
+                        .unwrap_or_default(),
+                ),
+            })
+            .collect();
+        let Some(ce150) = self.peripheral_mut::<Ce150>() else {
+            return false;
+        };
+        for glyph in glyphs {
+            match glyph {
+                Some(glyph) => ce150.print_glyph(glyph),
+                None => ce150.new_line(),
+            }
+        }
+        ce150.new_line();
+        true
+    }
+}
+
+impl Default for Ce150 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Peripheral for Ce150 {
+    fn read(&self, cycle: &BusCycle) -> Option<u8> {
+        if cycle.selects(Bank::Me0, ROM_BEGIN, ROM_END) {
+            Some(
+                // An empty ROM socket reads as an open bus
+                self.rom
+                    .get(usize::from(cycle.addr - ROM_BEGIN))
+                    .copied()
+                    .unwrap_or(0xFF),
+            )
+        } else if cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            register(cycle.addr).map(|reg| self.lh5810.get_reg(reg))
+        } else {
+            None
+        }
+    }
+
+    fn bus_read(&mut self, cycle: &BusCycle) -> Option<u8> {
+        if cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            register(cycle.addr).map(|reg| self.lh5810.read_reg(reg))
+        } else {
+            self.read(cycle)
+        }
+    }
+
+    fn write(&mut self, cycle: &BusCycle, value: u8) -> bool {
+        if !cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            // The ROM ignores writes but is still selected
+            return cycle.selects(Bank::Me0, ROM_BEGIN, ROM_END);
+        }
+        if let Some(reg) = register(cycle.addr) {
+            self.lh5810.set_reg(reg, value, cycle.timer_state);
+        }
+        true
+    }
+
+    fn step(&mut self, timer_state: usize) {
+        self.lh5810.step(timer_state);
+        self.move_pen();
+    }
+
+    fn interrupt(&self) -> bool {
+        self.lh5810.int()
+    }
+
+    fn tape_output(&self) -> Option<bool> {
+        Some(self.lh5810.get_sdo())
+    }
+
+    fn set_tape_input(&mut self, level: bool) {
+        self.lh5810.set_sdi(level);
+    }
+}
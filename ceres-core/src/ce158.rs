@@ -0,0 +1,273 @@
+// CE-158 RS-232C and parallel interface
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    lh5810::{self, Lh5810},
+    pd1990ac::FREQUENCY,
+    peripheral::{Bank, BusCycle, Peripheral},
+    serial::{self, SerialLink},
+};
+
+// CE-158 ROM on ME0 and its LH5810 on ME1. The LH5810 serial unit is the
+// UART: L holds the character to send, U the one received, G the bit rate,
+// and IF and MSK flag and mask TD and RD as on any LH5810.
+pub const ROM_BEGIN: u16 = 0x8000;
+pub const ROM_END: u16 = 0x9FFF;
+pub const IO_BEGIN: u16 = 0xD000;
+pub const IO_END: u16 = 0xD00F;
+
+// Port A is the Centronics data bus. Port B carries the strobe and the
+// RS-232 handshake lines; which pin is which has not been checked against
+// a CE-158 schematic.
+pub const STROBE: u8 = 0x01;
+pub const BUSY: u8 = 0x02;
+pub const RTS: u8 = 0x04;
+pub const DTR: u8 = 0x08;
+pub const CTS: u8 = 0x10;
+pub const DSR: u8 = 0x20;
+
+// Data bits of a character, framed by a start and a stop bit as the LH5810
+// shifts them
+const DATA_BITS: u8 = 8;
+
+fn register(addr: u16) -> Option<lh5810::Reg> {
+    u8::try_from(addr - IO_BEGIN).ok().and_then(lh5810::Reg::at)
+}
+
+/// The CE-158 interface, attached to the PC-1500 expansion bus.
+///
+/// The host end of the RS-232 line is a [`SerialLink`]: bytes shifted out
+/// on SDO are collected bit by bit at the LH5810 serial clock, and bytes
+/// from the host are shifted into SDI the same way while RTS is high. The
+/// parallel printer port prints to any writer, usually a file, on the
+/// falling edge of STROBE.
+pub struct Ce158 {
+    lh5810: Lh5810,
+    rom: Vec<u8>,
+    link: Option<Box<dyn SerialLink>>,
+    // Host side of TXD: bits collected after a start bit, None while idle
+    transmit: Option<(u8, u8)>,
+    // Levels still to be driven on RXD, start bit first
+    receive: VecDeque<bool>,
+    clo: bool,
+    parallel: Option<Box<dyn Write>>,
+    strobe: bool,
+    error: Option<io::Error>,
+}
+
+impl Ce158 {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_rom(Vec::new())
+    }
+
+    #[must_use]
+    pub fn with_rom(rom: Vec<u8>) -> Self {
+        let mut ce158 = Self {
+            lh5810: Lh5810::new(),
+            rom,
+            link: None,
+            transmit: None,
+            receive: VecDeque::new(),
+            clo: false,
+            parallel: None,
+            strobe: false,
+            error: None,
+        };
+        // RXD idles on the stop level
+        ce158.lh5810.set_sdi(true);
+        ce158.update_inputs();
+        ce158
+    }
+
+    /// Connects the RS-232 port to `link`, replacing the previous one.
+    pub fn connect_serial(&mut self, link: Box<dyn SerialLink>) {
+        self.link = Some(link);
+        self.update_inputs();
+    }
+
+    pub fn disconnect_serial(&mut self) -> Option<Box<dyn SerialLink>> {
+        let link = self.link.take();
+        self.update_inputs();
+        link
+    }
+
+    /// Connects a printer to the parallel port.
+    pub fn connect_parallel(&mut self, printer: Box<dyn Write>) {
+        self.parallel = Some(printer);
+        self.update_inputs();
+    }
+
+    /// Prints to the end of the file at `path`, creating it if needed.
+    pub fn connect_parallel_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.connect_parallel(Box::new(serial::append(path)?));
+        Ok(())
+    }
+
+    pub fn disconnect_parallel(&mut self) -> Option<Box<dyn Write>> {
+        let printer = self.parallel.take();
+        self.update_inputs();
+        printer
+    }
+
+    /// Bit rate selected in G, for configuring the host side. The LH5810
+    /// runs off the PC-1500 clock here, so the rates are its dividers of
+    /// it rather than the standard ones.
+    #[must_use]
+    pub const fn baud_rate(&self) -> usize {
+        FREQUENCY / self.lh5810.bit_ticks()
+    }
+
+    /// Takes the last host I/O error. The failing link or printer is
+    /// disconnected.
+    pub const fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    // A missing printer reads as busy, a missing host as not ready
+    fn update_inputs(&mut self) {
+        let mut levels = 0;
+        if self.parallel.is_none() {
+            levels |= BUSY;
+        }
+        if self.link.is_some() {
+            levels |= CTS | DSR;
+        }
+        self.lh5810.set_input_b(levels);
+    }
+
+    /// Runs the host end of both serial lines for one bit of the LH5810
+    /// serial clock.
+    fn serial_clock(&mut self) {
+        let txd = self.lh5810.get_sdo();
+        self.transmit = match self.transmit {
+            None if !txd => Some((0, 0)),
+            None => None,
+            Some((count, byte)) => {
+                let byte = byte | (u8::from(txd) << count);
+                if count + 1 < DATA_BITS {
+                    Some((count + 1, byte))
+                } else {
+                    self.send(byte);
+                    None
+                }
+            }
+        };
+
+        if self.receive.is_empty() && self.lh5810.output_b() & RTS != 0 {
+            self.poll_host();
+        }
+        let rxd = self.receive.pop_front().unwrap_or(true);
+        self.lh5810.set_sdi(rxd);
+    }
+
+    fn send(&mut self, byte: u8) {
+        if let Some(link) = &mut self.link
+            && let Err(err) = link.send(byte)
+        {
+            self.link = None;
+            self.error = Some(err);
+            self.update_inputs();
+        }
+    }
+
+    fn poll_host(&mut self) {
+        let Some(link) = &mut self.link else {
+            return;
+        };
+        match link.receive() {
+            Ok(Some(byte)) => {
+                self.receive.push_back(false);
+                self.receive
+                    .extend((0..DATA_BITS).map(|n| byte & (1 << n) != 0));
+                self.receive.push_back(true);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                self.link = None;
+                self.error = Some(err);
+                self.update_inputs();
+            }
+        }
+    }
+
+    fn strobe_printer(&mut self) {
+        let strobe = self.lh5810.output_b() & STROBE != 0;
+        if self.strobe && !strobe {
+            self.print(self.lh5810.output_a());
+        }
+        self.strobe = strobe;
+    }
+
+    fn print(&mut self, byte: u8) {
+        if let Some(printer) = &mut self.parallel
+            && let Err(err) = printer.write_all(&[byte]).and_then(|()| printer.flush())
+        {
+            self.parallel = None;
+            self.error = Some(err);
+            self.update_inputs();
+        }
+    }
+}
+
+impl Default for Ce158 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Peripheral for Ce158 {
+    fn read(&self, cycle: &BusCycle) -> Option<u8> {
+        if cycle.selects(Bank::Me0, ROM_BEGIN, ROM_END) {
+            Some(
+                // An empty ROM socket reads as an open bus
+                self.rom
+                    .get(usize::from(cycle.addr - ROM_BEGIN))
+                    .copied()
+                    .unwrap_or(0xFF),
+            )
+        } else if cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            register(cycle.addr).map(|reg| self.lh5810.get_reg(reg))
+        } else {
+            None
+        }
+    }
+
+    fn bus_read(&mut self, cycle: &BusCycle) -> Option<u8> {
+        if cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            register(cycle.addr).map(|reg| self.lh5810.read_reg(reg))
+        } else {
+            self.read(cycle)
+        }
+    }
+
+    fn write(&mut self, cycle: &BusCycle, value: u8) -> bool {
+        if !cycle.selects(Bank::Me1, IO_BEGIN, IO_END) {
+            // The ROM ignores writes but is still selected
+            return cycle.selects(Bank::Me0, ROM_BEGIN, ROM_END);
+        }
+        if let Some(reg) = register(cycle.addr) {
+            self.lh5810.set_reg(reg, value, cycle.timer_state);
+            self.strobe_printer();
+        }
+        true
+    }
+
+    fn step(&mut self, timer_state: usize) {
+        self.lh5810.step(timer_state);
+        let clo = self.lh5810.get_clo();
+        if clo && !self.clo {
+            self.serial_clock();
+        }
+        self.clo = clo;
+    }
+
+    fn interrupt(&self) -> bool {
+        self.lh5810.int()
+    }
+}
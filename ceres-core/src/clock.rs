@@ -0,0 +1,57 @@
+// Time sources for the PD1990AC real-time clock
+
+use chrono::{Local, NaiveDateTime, Utc};
+
+use crate::{ClockTime, Pc1500, replay::ClockEvent};
+
+/// Where the real-time clock takes its time from when the machine is
+/// created.
+///
+/// Except for [`ClockSource::Manual`], the clock then runs on emulated
+/// ticks like the crystal of the real chip, so it stays in step with
+/// programs however fast or slow the emulator runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockSource {
+    /// Host wall-clock time in the host time zone
+    #[default]
+    HostLocal,
+    /// Host wall-clock time in UTC
+    HostUtc,
+    /// Starts at the given time, for reproducible runs
+    Fixed(NaiveDateTime),
+    /// Stays at the given time until changed with
+    /// [`Pc1500::set_clock_time`] or by the ROM. Changes made by the host
+    /// are recorded with the input, so replays see them at the same tick.
+    Manual(NaiveDateTime),
+}
+
+impl ClockSource {
+    /// The time the clock starts at, read from the host now for host
+    /// sources.
+    #[must_use]
+    pub fn start_time(&self) -> NaiveDateTime {
+        match self {
+            Self::HostLocal => Local::now().naive_local(),
+            Self::HostUtc => Utc::now().naive_utc(),
+            Self::Fixed(datetime) | Self::Manual(datetime) => *datetime,
+        }
+    }
+
+    /// Whether the clock counts emulated seconds.
+    #[must_use]
+    pub const fn is_running(&self) -> bool {
+        !matches!(self, Self::Manual(_))
+    }
+}
+
+impl Pc1500 {
+    /// Loads the real-time clock counters, as TIME = does from BASIC.
+    pub fn set_clock_time(&mut self, time: ClockTime) {
+        self.pd1990ac.set_time(time, self.lh5801.timer_state());
+
+        let tick = self.lh5801.get_ticks();
+        if let Some(recording) = &mut self.recording {
+            recording.push_clock(ClockEvent { tick, time });
+        }
+    }
+}
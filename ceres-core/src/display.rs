@@ -1,9 +1,25 @@
-use crate::Pc1500;
+use crate::{Pc1500, basic};
 
 pub const DISPLAY_WIDTH: usize = 156;
 pub const DISPLAY_HEIGHT: usize = 7;
 pub const RGBA_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT * 4; // RGBA format
 
+/// Each character cell is five columns wide plus one blank column.
+pub const CELL_WIDTH: usize = 6;
+pub const GLYPH_WIDTH: usize = 5;
+
+// ROM character generator, five column bytes per character from 0x20 to
+// 0x7F. FCA0-FE7F is the character table in the PC-1500 ROM memory maps,
+// tests/display.rs checks it against the pattern of 'A' on a real ROM.
+const CHARSET_BEGIN: u16 = 0xFCA0;
+const CHARSET_FIRST: u8 = 0x20;
+const CHARSET_LAST: u8 = 0x7F;
+
+/// Number of character cells on the display.
+pub const CELLS: usize = DISPLAY_WIDTH / CELL_WIDTH;
+/// Shown for cells that match no character.
+pub const UNKNOWN_GLYPH: char = '\u{FFFD}';
+
 fn low(b: u8) -> u8 {
     b & 0x0F
 }
@@ -34,6 +50,8 @@ pub enum Symbol {
 pub struct DisplayController {
     /// RGBA buffer for GPU rendering
     rgba_buffer: [u8; RGBA_SIZE],
+    /// One byte per column, bit 0 is the top row
+    columns: [u8; DISPLAY_WIDTH],
     symbol_buffer: [bool; 14],
 }
 
@@ -41,6 +59,7 @@ impl DisplayController {
     pub fn new() -> Self {
         Self {
             rgba_buffer: [0; RGBA_SIZE],
+            columns: [0; DISPLAY_WIDTH],
             symbol_buffer: [false; 14],
         }
     }
@@ -49,6 +68,11 @@ impl DisplayController {
         &self.rgba_buffer
     }
 
+    #[must_use]
+    pub const fn columns(&self) -> &[u8; DISPLAY_WIDTH] {
+        &self.columns
+    }
+
     pub fn is_symbol_on(&self, symbol: Symbol) -> bool {
         self.symbol_buffer[symbol as usize]
     }
@@ -63,8 +87,21 @@ impl DisplayController {
         self.rgba_buffer[index..index + 4].copy_from_slice(&[255, 255, 255, 255]);
     }
 
+    fn draw_column(&mut self, x: usize, data: u8) {
+        self.columns[x] = data & 0x7F;
+
+        for b in 0..DISPLAY_HEIGHT {
+            if (data >> b) & 0x01 != 0 {
+                self.draw_black_pixel(x, b);
+            } else {
+                self.draw_white_pixel(x, b);
+            }
+        }
+    }
+
     fn clear(&mut self) {
         self.rgba_buffer.fill(0xff);
+        self.columns.fill(0);
     }
 }
 
@@ -80,24 +117,12 @@ impl Pc1500 {
             let data = low(self.read_byte(adr)) | (low(self.read_byte(adr + 1)) << 4);
             let x = ind >> 1;
 
-            for b in 0..7 {
-                if (data >> b) & 0x01 != 0 {
-                    self.display.draw_black_pixel(x as usize, b);
-                } else {
-                    self.display.draw_white_pixel(x as usize, b);
-                }
-            }
+            self.display.draw_column(x as usize, data);
 
             let data = high(self.read_byte(adr)) | (high(self.read_byte(adr + 1)) << 4);
             let x = x + 78;
 
-            for b in 0..7 {
-                if (data >> b) & 0x01 != 0 {
-                    self.display.draw_black_pixel(x as usize, b);
-                } else {
-                    self.display.draw_white_pixel(x as usize, b);
-                }
-            }
+            self.display.draw_column(x as usize, data);
         }
 
         for ind in (0..0x4D).step_by(2) {
@@ -105,24 +130,12 @@ impl Pc1500 {
             let data = low(self.read_byte(adr)) | (low(self.read_byte(adr + 1)) << 4);
             let x = (ind >> 1) + 39;
 
-            for b in 0..7 {
-                if (data >> b) & 0x01 != 0 {
-                    self.display.draw_black_pixel(x as usize, b);
-                } else {
-                    self.display.draw_white_pixel(x as usize, b);
-                }
-            }
+            self.display.draw_column(x as usize, data);
 
             let data = high(self.read_byte(adr)) | (high(self.read_byte(adr + 1)) << 4);
             let x = x + 78;
 
-            for b in 0..7 {
-                if (data >> b) & 0x01 != 0 {
-                    self.display.draw_black_pixel(x as usize, b);
-                } else {
-                    self.display.draw_white_pixel(x as usize, b);
-                }
-            }
+            self.display.draw_column(x as usize, data);
         }
 
         // Symbols
@@ -149,6 +162,92 @@ impl Pc1500 {
     }
 }
 
+impl Pc1500 {
+    /// Returns the 5x7 pattern the ROM character generator uses for `code`,
+    /// one byte per column with bit 0 as the top row.
+    #[must_use]
+    pub fn glyph(&self, code: u8) -> Option<[u8; GLYPH_WIDTH]> {
+        if !(CHARSET_FIRST..=CHARSET_LAST).contains(&code) {
+            return None;
+        }
+
+        let offset = usize::from(code - CHARSET_FIRST) * GLYPH_WIDTH;
+        let mut glyph = [0; GLYPH_WIDTH];
+        for (addr, column) in (u32::from(CHARSET_BEGIN)..)
+            .skip(offset)
+            .zip(glyph.iter_mut())
+        {
+            *column = self.read_byte(addr) & 0x7F;
+        }
+        Some(glyph)
+    }
+
+    /// The printable characters of the ROM character generator with their
+    /// patterns.
+    #[must_use]
+    pub fn charset(&self) -> Vec<(char, [u8; GLYPH_WIDTH])> {
+        (CHARSET_FIRST..=CHARSET_LAST)
+            .filter_map(|code| Some((basic::char_from_byte(code)?, self.glyph(code)?)))
+            .collect()
+    }
+
+    /// The current display contents as text, matched against the ROM
+    /// character generator.
+    pub fn display_text(&mut self) -> DisplayText {
+        let charset = self.charset();
+        decode_text(self.display().columns(), &charset)
+    }
+}
+
+/// The display read back as text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayText {
+    /// One character per cell, [`UNKNOWN_GLYPH`] for cells holding graphics
+    pub text: String,
+    /// Columns of the cells holding graphics, blank for the text cells
+    pub graphics: [u8; DISPLAY_WIDTH],
+}
+
+impl DisplayText {
+    /// Whether any cell holds graphics instead of a character.
+    #[must_use]
+    pub fn has_graphics(&self) -> bool {
+        self.graphics.iter().any(|&column| column != 0)
+    }
+}
+
+/// Reads display columns back as text by matching each cell against
+/// `glyphs`, the first match winning. A cell is a character only when its
+/// spacing column is blank.
+#[must_use]
+pub fn decode_text(
+    columns: &[u8; DISPLAY_WIDTH],
+    glyphs: &[(char, [u8; GLYPH_WIDTH])],
+) -> DisplayText {
+    let mut text = String::new();
+    let mut graphics = [0; DISPLAY_WIDTH];
+
+    for (cell, graphics_cell) in columns
+        .chunks_exact(CELL_WIDTH)
+        .zip(graphics.chunks_exact_mut(CELL_WIDTH))
+    {
+        let (glyph, spacing) = cell.split_at(GLYPH_WIDTH);
+        let found = glyphs
+            .iter()
+            .find(|(_, pattern)| pattern == glyph)
+            .filter(|_| spacing.iter().all(|&column| column == 0));
+
+        if let Some(&(c, _)) = found {
+            text.push(c);
+        } else {
+            text.push(UNKNOWN_GLYPH);
+            graphics_cell.copy_from_slice(cell);
+        }
+    }
+
+    DisplayText { text, graphics }
+}
+
 impl Default for DisplayController {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,166 @@
+// Headless BASIC runs, for tests of PC-1500 programs
+
+use core::{error::Error, fmt};
+
+use crate::{
+    Pc1500,
+    basic::{self, ParseProgramError},
+    pd1990ac::FREQUENCY,
+    typing::{UnsupportedChar, keys_for_char},
+    variables::{SetVariableError, Value},
+};
+
+/// One minute of emulated time.
+pub const DEFAULT_TIMEOUT: usize = 60 * FREQUENCY;
+// Longest the ROM may take from reset to the command prompt
+const BOOT_TIMEOUT: usize = 10 * FREQUENCY;
+
+/// What a program wrote while it ran.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunOutput {
+    /// Characters sent to the display, one line per PRINT statement
+    pub display: String,
+    /// Emulated CPU ticks the run took
+    pub ticks: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeoutError {
+    /// Output written before the timeout
+    pub output: RunOutput,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "program still running after {} ticks", self.output.ticks)
+    }
+}
+
+impl Error for TimeoutError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HarnessError {
+    Program(ParseProgramError),
+    /// The ROM never waited for a key at the command prompt
+    Boot,
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Program(error) => write!(f, "{error}"),
+            Self::Boot => write!(f, "the ROM did not reach the command prompt"),
+        }
+    }
+}
+
+impl Error for HarnessError {}
+
+impl From<ParseProgramError> for HarnessError {
+    fn from(error: ParseProgramError) -> Self {
+        Self::Program(error)
+    }
+}
+
+/// Loads a BASIC program into a machine without a window and RUNs it with
+/// scripted input.
+///
+/// A run ends when the ROM waits for a key with no scripted input left: the
+/// program has ended, or is waiting at an INPUT or a PRINT the script does
+/// not answer.
+pub struct BasicHarness {
+    pc1500: Pc1500,
+    input: String,
+    timeout: usize,
+}
+
+impl BasicHarness {
+    /// Boots a machine to the command prompt and loads the program written
+    /// as plain text in `source`. The program is checked before booting.
+    pub fn new(source: &str) -> Result<Self, HarnessError> {
+        basic::parse_program(source)?;
+
+        let mut pc1500 = Pc1500::new();
+        while !pc1500.waited_for_key_since(0) {
+            if pc1500.cpu().get_ticks() >= BOOT_TIMEOUT {
+                return Err(HarnessError::Boot);
+            }
+            pc1500.step_frame();
+        }
+
+        pc1500.load_program(source)?;
+        Ok(Self {
+            pc1500,
+            input: String::new(),
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Sets a fixed variable before the run, A to Z or A$ to Z$.
+    pub fn set_variable(&mut self, name: &str, value: &Value) -> Result<(), SetVariableError> {
+        self.pc1500.set_variable(name, value)
+    }
+
+    /// Text typed while the program runs, `\n` being ENTER. Each key is
+    /// pressed when the ROM waits for one.
+    pub fn set_input(&mut self, text: &str) -> Result<(), UnsupportedChar> {
+        if let Some(c) = text
+            .chars()
+            .find(|&c| c != '\r' && keys_for_char(c).is_none())
+        {
+            return Err(UnsupportedChar(c));
+        }
+        text.clone_into(&mut self.input);
+        Ok(())
+    }
+
+    /// Longest run allowed, in emulated CPU ticks.
+    pub const fn set_timeout(&mut self, ticks: usize) {
+        self.timeout = ticks;
+    }
+
+    /// Types RUN and the scripted input, and runs until the program is done
+    /// or the timeout is reached.
+    pub fn run(&mut self) -> Result<RunOutput, TimeoutError> {
+        let mut keys = String::from("RUN\n");
+        keys.push_str(&self.input);
+        // Every character was checked by set_input
+        self.pc1500.type_text(&keys).ok();
+        self.pc1500.capture_run_output();
+
+        let start = self.pc1500.cpu().get_ticks();
+        let finished = loop {
+            let frame_start = self.pc1500.cpu().get_ticks();
+            self.pc1500.step_frame();
+
+            if !self.pc1500.is_typing() && self.pc1500.waited_for_key_since(frame_start) {
+                break true;
+            }
+            if self.pc1500.cpu().get_ticks() - start >= self.timeout {
+                break false;
+            }
+        };
+
+        let output = RunOutput {
+            display: self.pc1500.take_display_output(),
+            ticks: self.pc1500.cpu().get_ticks() - start,
+        };
+        self.pc1500.disable_output_capture();
+        if finished {
+            Ok(output)
+        } else {
+            self.pc1500.cancel_typing();
+            Err(TimeoutError { output })
+        }
+    }
+
+    /// The machine, e.g. to read variables after a run.
+    #[must_use]
+    pub const fn pc1500(&self) -> &Pc1500 {
+        &self.pc1500
+    }
+
+    pub const fn pc1500_mut(&mut self) -> &mut Pc1500 {
+        &mut self.pc1500
+    }
+}
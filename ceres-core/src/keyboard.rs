@@ -1,3 +1,5 @@
+use core::{error::Error, fmt, str::FromStr};
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Key {
     A,
@@ -68,10 +70,306 @@ pub enum Key {
     Zero,
 }
 
+impl Key {
+    pub const ALL: [Self; 66] = [
+        Self::A,
+        Self::Asterisk,
+        Self::B,
+        Self::C,
+        Self::Cl,
+        Self::Control,
+        Self::D,
+        Self::Dot,
+        Self::Down,
+        Self::E,
+        Self::Eight,
+        Self::Enter,
+        Self::Equals,
+        Self::F,
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::Five,
+        Self::Four,
+        Self::G,
+        Self::H,
+        Self::I,
+        Self::J,
+        Self::K,
+        Self::L,
+        Self::Left,
+        Self::LeftParen,
+        Self::M,
+        Self::Minus,
+        Self::Mode,
+        Self::N,
+        Self::Nine,
+        Self::O,
+        Self::Off,
+        Self::On,
+        Self::One,
+        Self::P,
+        Self::Plus,
+        Self::Q,
+        Self::Quote,
+        Self::R,
+        Self::Rcl,
+        Self::Right,
+        Self::RightParen,
+        Self::Rsv,
+        Self::S,
+        Self::Seven,
+        Self::Shift,
+        Self::Six,
+        Self::Slash,
+        Self::Sml,
+        Self::Space,
+        Self::T,
+        Self::Three,
+        Self::Two,
+        Self::U,
+        Self::Up,
+        Self::V,
+        Self::W,
+        Self::X,
+        Self::Y,
+        Self::Z,
+        Self::Zero,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::Asterisk => "ASTERISK",
+            Self::B => "B",
+            Self::C => "C",
+            Self::Cl => "CL",
+            Self::Control => "DEF",
+            Self::D => "D",
+            Self::Dot => "DOT",
+            Self::Down => "DOWN",
+            Self::E => "E",
+            Self::Eight => "EIGHT",
+            Self::Enter => "ENTER",
+            Self::Equals => "EQUALS",
+            Self::F => "F",
+            Self::F1 => "F1",
+            Self::F2 => "F2",
+            Self::F3 => "F3",
+            Self::F4 => "F4",
+            Self::F5 => "F5",
+            Self::F6 => "F6",
+            Self::Five => "FIVE",
+            Self::Four => "FOUR",
+            Self::G => "G",
+            Self::H => "H",
+            Self::I => "I",
+            Self::J => "J",
+            Self::K => "K",
+            Self::L => "L",
+            Self::Left => "LEFT",
+            Self::LeftParen => "LEFTPAREN",
+            Self::M => "M",
+            Self::Minus => "MINUS",
+            Self::Mode => "MODE",
+            Self::N => "N",
+            Self::Nine => "NINE",
+            Self::O => "O",
+            Self::Off => "OFF",
+            Self::On => "ON",
+            Self::One => "ONE",
+            Self::P => "P",
+            Self::Plus => "PLUS",
+            Self::Q => "Q",
+            Self::Quote => "QUOTE",
+            Self::R => "R",
+            Self::Rcl => "RCL",
+            Self::Right => "RIGHT",
+            Self::RightParen => "RIGHTPAREN",
+            Self::Rsv => "RSV",
+            Self::S => "S",
+            Self::Seven => "SEVEN",
+            Self::Shift => "SHIFT",
+            Self::Six => "SIX",
+            Self::Slash => "SLASH",
+            Self::Sml => "SML",
+            Self::Space => "SPACE",
+            Self::T => "T",
+            Self::Three => "THREE",
+            Self::Two => "TWO",
+            Self::U => "U",
+            Self::Up => "UP",
+            Self::V => "V",
+            Self::W => "W",
+            Self::X => "X",
+            Self::Y => "Y",
+            Self::Z => "Z",
+            Self::Zero => "ZERO",
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Names accepted besides the current ones. DEF was called CONTROL in
+// recordings made before it was renamed after its keycap.
+const KEY_ALIASES: &[(&str, Key)] = &[("CONTROL", Key::Control)];
+
+impl FromStr for Key {
+    type Err = UnknownKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .map(|key| (key.name(), key))
+            .chain(KEY_ALIASES.iter().copied())
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, key)| key)
+            .ok_or(UnknownKey)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownKey;
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown key name")
+    }
+}
+
+impl Error for UnknownKey {}
+
+/// Which key sits on each KS strobe line (outer index) and data bit (inner
+/// index) of the keyboard matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyMatrix([[Option<Key>; 8]; 8]);
+
+impl KeyMatrix {
+    /// Layout of the export PC-1500.
+    pub const PC1500: Self = Self([
+        [
+            Some(Key::Two),
+            Some(Key::Five),
+            Some(Key::Eight),
+            Some(Key::H),
+            Some(Key::Shift),
+            Some(Key::Y),
+            Some(Key::N),
+            Some(Key::Up),
+        ],
+        [
+            Some(Key::Dot),
+            Some(Key::Minus),
+            Some(Key::Off),
+            Some(Key::S),
+            Some(Key::F1),
+            Some(Key::W),
+            Some(Key::X),
+            Some(Key::Rsv),
+        ],
+        [
+            Some(Key::One),
+            Some(Key::Four),
+            Some(Key::Seven),
+            Some(Key::J),
+            Some(Key::F5),
+            Some(Key::U),
+            Some(Key::M),
+            Some(Key::Zero),
+        ],
+        [
+            Some(Key::RightParen),
+            Some(Key::L),
+            Some(Key::O),
+            Some(Key::K),
+            Some(Key::F6),
+            Some(Key::I),
+            Some(Key::LeftParen),
+            Some(Key::Enter),
+        ],
+        [
+            Some(Key::Plus),
+            Some(Key::Asterisk),
+            Some(Key::Slash),
+            Some(Key::D),
+            Some(Key::F2),
+            Some(Key::E),
+            Some(Key::C),
+            Some(Key::Rcl),
+        ],
+        [
+            Some(Key::Equals),
+            Some(Key::Left),
+            Some(Key::P),
+            Some(Key::F),
+            Some(Key::F3),
+            Some(Key::R),
+            Some(Key::V),
+            Some(Key::Space),
+        ],
+        [
+            Some(Key::Right),
+            Some(Key::Mode),
+            Some(Key::Cl),
+            Some(Key::A),
+            Some(Key::Control),
+            Some(Key::Q),
+            Some(Key::Z),
+            Some(Key::Sml),
+        ],
+        [
+            Some(Key::Three),
+            Some(Key::Six),
+            Some(Key::Nine),
+            Some(Key::G),
+            Some(Key::F4),
+            Some(Key::T),
+            Some(Key::B),
+            Some(Key::Down),
+        ],
+    ]);
+
+    #[must_use]
+    pub const fn new(matrix: [[Option<Key>; 8]; 8]) -> Self {
+        Self(matrix)
+    }
+
+    #[must_use]
+    pub const fn key_at(&self, strobe: u8, bit: u8) -> Option<Key> {
+        self.0[strobe as usize][bit as usize]
+    }
+
+    /// Returns the KS strobe line and data bit `key` is wired to, or `None`
+    /// if the key is not part of the matrix.
+    #[must_use]
+    pub fn position(&self, key: Key) -> Option<(u8, u8)> {
+        (0..8)
+            .flat_map(|strobe| (0..8).map(move |bit| (strobe, bit)))
+            .find(|&(strobe, bit)| self.key_at(strobe, bit) == Some(key))
+    }
+}
+
+impl Default for KeyMatrix {
+    fn default() -> Self {
+        Self::PC1500
+    }
+}
+
 #[derive(Debug)]
 pub struct Keyboard {
     ks: u8,
     pressed_keys: [bool; 68],
+    matrix: KeyMatrix,
+    ghosting: bool,
 }
 
 impl Keyboard {
@@ -79,6 +377,8 @@ impl Keyboard {
         Self {
             ks: 0,
             pressed_keys: [false; 68],
+            matrix: KeyMatrix::PC1500,
+            ghosting: false,
         }
     }
 
@@ -98,230 +398,60 @@ impl Keyboard {
         self.pressed_keys[key as usize] = false;
     }
 
-    pub fn input(&self) -> u8 {
-        let mut data = 0;
-
-        if self.get_ks() & 0x01 != 0 {
-            if self.pressed_keys[Key::Two as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Five as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Eight as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::H as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::Shift as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::Y as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::N as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Up as usize] {
-                data |= 0x80;
-            }
-        }
-
-        if self.get_ks() & 0x02 != 0 {
-            if self.pressed_keys[Key::Dot as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Minus as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Off as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::S as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::F1 as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::W as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::X as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Rsv as usize] {
-                data |= 0x80;
-            }
-        }
+    #[must_use]
+    pub const fn matrix(&self) -> &KeyMatrix {
+        &self.matrix
+    }
 
-        if self.get_ks() & 0x4 != 0 {
-            if self.pressed_keys[Key::One as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Four as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Seven as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::J as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::F5 as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::U as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::M as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Zero as usize] {
-                data |= 0x80;
-            }
-        }
+    pub const fn set_matrix(&mut self, matrix: KeyMatrix) {
+        self.matrix = matrix;
+    }
 
-        if self.get_ks() & 0x8 != 0 {
-            if self.pressed_keys[Key::RightParen as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::L as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::O as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::K as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::F6 as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::I as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::LeftParen as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Enter as usize] {
-                data |= 0x80;
-            }
-        }
+    /// The real keyboard has no diodes, so holding three keys on the corners
+    /// of a rectangle makes the fourth one read as pressed too.
+    pub const fn set_ghosting(&mut self, ghosting: bool) {
+        self.ghosting = ghosting;
+    }
 
-        if self.get_ks() & 0x10 != 0 {
-            if self.pressed_keys[Key::Plus as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Asterisk as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Slash as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::D as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::F2 as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::E as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::C as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Rcl as usize] {
-                data |= 0x80;
+    /// Data bits pulled by the pressed keys on each strobe line.
+    fn pressed_bits(&self) -> [u8; 8] {
+        let mut rows = [0; 8];
+        for (strobe, row) in (0..8).zip(rows.iter_mut()) {
+            for bit in 0..8 {
+                if let Some(key) = self.matrix.key_at(strobe, bit)
+                    && self.pressed_keys[key as usize]
+                {
+                    *row |= 1 << bit;
+                }
             }
         }
+        rows
+    }
 
-        if self.get_ks() & 0x20 != 0 {
-            if self.pressed_keys[Key::Equals as usize] {
-                data |= 1;
-            }
-
-            if self.pressed_keys[Key::Left as usize] {
-                data |= 2;
-            }
-
-            if self.pressed_keys[Key::P as usize] {
-                data |= 4;
-            }
-
-            if self.pressed_keys[Key::F as usize] {
-                data |= 8;
-            }
-
-            if self.pressed_keys[Key::F3 as usize] {
-                data |= 0x10;
-            }
-
-            if self.pressed_keys[Key::R as usize] {
-                data |= 0x20;
-            }
+    pub fn input(&self) -> u8 {
+        let rows = self.pressed_bits();
+        let mut strobes = self.get_ks();
+        let mut data = 0;
 
-            if self.pressed_keys[Key::V as usize] {
-                data |= 0x40;
-            }
+        loop {
+            data = (0..8)
+                .filter(|strobe| strobes & (1 << strobe) != 0)
+                .fold(data, |data, strobe| data | rows[strobe]);
 
-            if self.pressed_keys[Key::Space as usize] {
-                data |= 0x80;
+            if !self.ghosting {
+                break;
             }
-        }
 
-        if self.get_ks() & 0x40 != 0 {
-            if self.pressed_keys[Key::Right as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Mode as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Cl as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::A as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::Control as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::Q as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::Z as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Sml as usize] {
-                data |= 0x80;
-            }
-        }
+            // Current flows back through any key sharing a data line, driving
+            // its strobe line as well
+            let reached = (0..8)
+                .filter(|&strobe| rows[strobe] & data != 0)
+                .fold(strobes, |strobes, strobe| strobes | (1 << strobe));
 
-        if self.get_ks() & 0x80 != 0 {
-            if self.pressed_keys[Key::Three as usize] {
-                data |= 1;
-            }
-            if self.pressed_keys[Key::Six as usize] {
-                data |= 2;
-            }
-            if self.pressed_keys[Key::Nine as usize] {
-                data |= 4;
-            }
-            if self.pressed_keys[Key::G as usize] {
-                data |= 8;
-            }
-            if self.pressed_keys[Key::F4 as usize] {
-                data |= 0x10;
-            }
-            if self.pressed_keys[Key::T as usize] {
-                data |= 0x20;
-            }
-            if self.pressed_keys[Key::B as usize] {
-                data |= 0x40;
-            }
-            if self.pressed_keys[Key::Down as usize] {
-                data |= 0x80;
+            if reached == strobes {
+                break;
             }
+            strobes = reached;
         }
 
         data ^ 0xff
@@ -1,6 +1,6 @@
 use core::panic;
 
-use crate::Pc1500;
+use crate::{Pc1500, typing};
 
 const DO_DEBUG_ROM: bool = false;
 
@@ -49,6 +49,12 @@ impl Lh5801 {
         self.ir2 = ir2;
     }
 
+    /// Pending maskable interrupt request
+    #[must_use]
+    pub const fn ir2(&self) -> bool {
+        self.ir2
+    }
+
     #[must_use]
     pub const fn a(&self) -> u8 {
         self.a
@@ -102,6 +108,16 @@ impl Lh5801 {
         self.a = a;
     }
 
+    pub const fn set_s(&mut self, s: u16) {
+        self.s = s;
+    }
+
+    /// Drops the reset pending since power on, so execution starts at the
+    /// current P instead of the reset vector.
+    pub const fn cancel_reset(&mut self) {
+        self.reset_flag = false;
+    }
+
     #[must_use]
     pub const fn xl(&self) -> u8 {
         (self.x & 0xFF) as u8
@@ -485,6 +501,12 @@ impl Pc1500 {
             }
         }
 
+        if matches!(addr, typing::WAIT_4_KB | typing::ISKEY) {
+            self.keyboard_scan_hook(addr);
+        }
+
+        self.output_hook(addr);
+
         self.lh5801.p = addr;
     }
 
@@ -533,7 +555,7 @@ impl Pc1500 {
     }
 
     fn cpu_readmem<I: Into<u32> + Copy>(&mut self, addr: I) -> u8 {
-        self.read_byte(addr.into())
+        self.bus_read(addr.into())
     }
 
     fn cpu_writemem<I: Into<u32> + Copy>(&mut self, addr: I, val: u8) {
@@ -541,7 +563,7 @@ impl Pc1500 {
     }
 
     fn cpu_readop(&mut self) -> u8 {
-        let byte = self.read_byte(self.lh5801.p.into());
+        let byte = self.bus_read(self.lh5801.p.into());
         self.lh5801.p = self.lh5801.p.wrapping_add(1);
         byte
     }
@@ -1,7 +1,11 @@
 // IO Controller
 
-use crate::pd1990ac::FREQUENCY;
+// Serial clock periods selectable in G, in CPU ticks. These are the φ/2,
+// φ/4 and φ/256 to φ/8192 dividers of the 2.6 MHz oscillator, which runs
+// the CPU at φ/2.
+const CLOCK_RATES: [usize; 8] = [1, 2, 128, 256, 512, 1024, 2048, 4096];
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Reg {
     RESET,
     U,
@@ -17,6 +21,28 @@ pub enum Reg {
     F,
 }
 
+impl Reg {
+    /// Register at `offset` from the base of the LH5810 address block.
+    #[must_use]
+    pub const fn at(offset: u8) -> Option<Self> {
+        match offset {
+            0x04 => Some(Self::RESET),
+            0x05 => Some(Self::U),
+            0x06 => Some(Self::L),
+            0x07 => Some(Self::F),
+            0x08 => Some(Self::OPC),
+            0x09 => Some(Self::G),
+            0x0A => Some(Self::MSK),
+            0x0B => Some(Self::IF),
+            0x0C => Some(Self::DDA),
+            0x0D => Some(Self::DDB),
+            0x0E => Some(Self::OPA),
+            0x0F => Some(Self::OPB),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Lh5810 {
     // Registers
@@ -32,6 +58,9 @@ pub struct Lh5810 {
     r_opb: u8,
     r_opc: u8,
     r_f: u8,
+    // Levels driven on the port pins from outside, seen on input pins
+    input_a: u8,
+    input_b: u8,
 
     // Signals
     irq: bool,
@@ -50,20 +79,47 @@ pub struct Lh5810 {
 
     rol_reg: u16,
     bit_count: u8,
+    // Bits shifted in from SDI after a start bit, None while idle
+    receive_count: Option<u8>,
+    receive_reg: u8,
+    sdi_sample: bool,
+    // Receive data flag, cleared by reading U
+    rd: bool,
+    cli_edge: bool,
     bit: bool,
     modulation_send: bool,
     last_pulse_state: usize,
     clock_rate_state: usize,
+    // CPU ticks per serial bit, selected by G
     clock_rate: usize,
-    clock_rate_wait: usize,
     clock_output: bool,
 }
 
 impl Lh5810 {
+    #[must_use]
     pub fn int(&self) -> bool {
         self.int
     }
 
+    /// Serial data output, the modulated tape signal
+    #[must_use]
+    pub const fn get_sdo(&self) -> bool {
+        self.sdo
+    }
+
+    /// Levels driven on the port A pins configured as outputs
+    #[must_use]
+    pub const fn output_a(&self) -> u8 {
+        self.r_opa & self.r_dda
+    }
+
+    /// Levels driven on the port B pins configured as outputs
+    #[must_use]
+    pub const fn output_b(&self) -> u8 {
+        self.r_opb & self.r_ddb
+    }
+
+    #[must_use]
     pub fn new_opc(&self) -> bool {
         self.new_opc
     }
@@ -72,25 +128,89 @@ impl Lh5810 {
         self.new_opc = new_opc;
     }
 
-    // TODO: serial peripherals
-    // pub fn set_irq(&mut self, irq: bool) {
-    //     self.irq = irq;
-    // }
+    /// CPU ticks per serial bit, as selected in G
+    #[must_use]
+    pub const fn bit_ticks(&self) -> usize {
+        CLOCK_RATES[(self.r_g & 0x07) as usize]
+    }
 
-    fn lh5810_pb7(&self) -> bool {
-        (self.r_opb & 0x80) != 0
+    /// Serial clock output, high for a tenth of each bit period
+    #[must_use]
+    pub const fn get_clo(&self) -> bool {
+        self.clo
+    }
+
+    /// Drives the IRQ input, flagged in IF bit 0.
+    pub const fn set_irq(&mut self, irq: bool) {
+        self.irq = irq;
+    }
+
+    /// Drives the serial data input.
+    pub const fn set_sdi(&mut self, sdi: bool) {
+        self.sdi = sdi;
+    }
+
+    /// Drives the external serial clock input. While the internal clock is
+    /// disabled in G, every rising edge shifts a bit in and out.
+    pub const fn set_cli(&mut self, cli: bool) {
+        if cli && !self.cli {
+            self.cli_edge = true;
+        }
+        self.cli = cli;
+    }
+
+    /// Drives the port A pins from outside. Only pins configured as inputs
+    /// in DDA read the level back.
+    pub const fn set_input_a(&mut self, levels: u8) {
+        self.input_a = levels;
+    }
+
+    /// Drives the port B pins from outside, see [`Lh5810::set_input_a`].
+    pub const fn set_input_b(&mut self, levels: u8) {
+        self.input_b = levels;
+    }
+
+    /// Drives a single port pin from outside, `port` being OPA or OPB.
+    pub const fn set_input_bit(&mut self, port: Reg, bit: u8, level: bool) {
+        let input = match port {
+            Reg::OPA => &mut self.input_a,
+            Reg::OPB => &mut self.input_b,
+            _ => return,
+        };
+        if level {
+            *input |= 0x01 << bit;
+        } else {
+            *input &= !(0x01 << bit);
+        }
+    }
+
+    // Output pins read back their latch, input pins the level driven on them
+    const fn port_a(&self) -> u8 {
+        (self.r_opa & self.r_dda) | (self.input_a & !self.r_dda)
+    }
+
+    const fn port_b(&self) -> u8 {
+        (self.r_opb & self.r_ddb) | (self.input_b & !self.r_ddb)
+    }
+
+    const fn lh5810_pb7(&self) -> bool {
+        (self.port_b() & 0x80) != 0
     }
 
     fn reset_divider(&mut self, timer_state: usize) {
         self.clock_rate_state = timer_state;
     }
 
+    /// Register contents without the side effects of a CPU read, for
+    /// inspecting the chip.
+    #[must_use]
     pub fn get_reg(&self, reg: Reg) -> u8 {
         match reg {
             Reg::RESET => self.reset,
             Reg::U => self.r_u,
             Reg::L => self.r_l,
             Reg::G => self.r_g,
+            // The high nibble reads the IRQ, PB7, SDI and CLI input levels
             Reg::MSK => {
                 let mut t = self.r_msk;
                 if self.irq {
@@ -107,16 +227,24 @@ impl Lh5810 {
                 }
                 t
             }
-            Reg::IF => self.r_if,
+            Reg::IF => self.r_if | if self.rd { 0x04 } else { 0 },
             Reg::DDA => self.r_dda,
             Reg::DDB => self.r_ddb,
-            Reg::OPA => self.r_opa & !self.r_dda,
-            Reg::OPB => self.r_opb & !self.r_ddb,
+            Reg::OPA => self.port_a(),
+            Reg::OPB => self.port_b(),
             Reg::OPC => self.r_opc,
             Reg::F => self.r_f,
         }
     }
 
+    /// Register read by the CPU. Reading U clears RD.
+    pub fn read_reg(&mut self, reg: Reg) -> u8 {
+        if reg == Reg::U {
+            self.rd = false;
+        }
+        self.get_reg(reg)
+    }
+
     pub fn set_reg(&mut self, reg: Reg, data: u8, timer_state: usize) {
         match reg {
             Reg::RESET => {
@@ -133,17 +261,21 @@ impl Lh5810 {
                 self.r_g = data;
             }
             Reg::MSK => self.r_msk = data & 0x0F,
+            // Only the IRQ and PB7 flags can be written, RD is cleared by
+            // reading U and TD by writing L
             Reg::IF => self.r_if = (self.r_if & 0xFC) | (data & 0x03),
             Reg::DDA => {
                 self.r_dda = data;
                 // println!("set_reg DDA: {:02X}", self.r_dda);
             }
             Reg::DDB => self.r_ddb = data,
+            // The latch takes every bit, DDA and DDB select which pins it
+            // drives
             Reg::OPA => {
-                self.r_opa = (self.r_opa & !self.r_dda) | (data & self.r_dda);
+                self.r_opa = data;
                 // println!("set_reg OPA: {:02X}", self.r_opa);
             }
-            Reg::OPB => self.r_opb = (self.r_opb & !self.r_ddb) | (data & self.r_ddb),
+            Reg::OPB => self.r_opb = data,
             Reg::OPC => {
                 self.new_opc = true;
                 self.r_opc = data;
@@ -185,13 +317,17 @@ impl Lh5810 {
                 Reg::DDB => self.r_ddb &= !(0x01 << bit),
                 Reg::OPA => self.r_opa &= !(0x01 << bit),
                 Reg::OPB => self.r_opb &= !(0x01 << bit),
-                Reg::OPC => self.r_opc &= !(0x01 << bit),
+                Reg::OPC => {
+                    self.new_opc = true;
+                    self.r_opc &= !(0x01 << bit);
+                }
                 Reg::F => self.r_f &= !(0x01 << bit),
                 _ => {} // No action for other registers
             }
         }
     }
 
+    #[must_use]
     pub fn new() -> Self {
         Self {
             new_g: true,
@@ -208,6 +344,37 @@ impl Lh5810 {
         self.clock_rate_state = timer_state;
     }
 
+    /// Moves the serial shift registers on by one bit. Received characters
+    /// are framed like transmitted ones: a start bit falling from the idle
+    /// high level, then 8 data bits from the least significant one.
+    fn shift_bit(&mut self) {
+        let falling = self.sdi_sample && !self.sdi;
+        self.sdi_sample = self.sdi;
+
+        match self.receive_count {
+            None if falling => self.receive_count = Some(0),
+            None => {}
+            Some(count) => {
+                self.receive_reg = (self.receive_reg >> 1) | if self.sdi { 0x80 } else { 0 };
+                if count == 7 {
+                    self.r_u = self.receive_reg;
+                    self.rd = true;
+                    self.receive_count = None;
+                } else {
+                    self.receive_count = Some(count + 1);
+                }
+            }
+        }
+
+        self.bit_count = self.bit_count.wrapping_add(1);
+        if self.bit_count == 9 {
+            self.set_reg_bit(Reg::IF, 3, true);
+        }
+
+        self.rol_reg >>= 1;
+        self.rol_reg |= 0x8000;
+    }
+
     pub fn step(&mut self, timer_state: usize) {
         self.int = false;
 
@@ -235,42 +402,34 @@ impl Lh5810 {
                     self.last_pulse_state += wait_state as usize;
                 }
             }
+        } else {
+            // Unmodulated, SDO carries the bit levels, high while idle
+            self.sdo = (self.rol_reg & 0x01) != 0;
         }
 
         if self.clock_output {
-            if (timer_state - self.clock_rate_state) >= (self.clock_rate / 1) as usize {
+            if (timer_state - self.clock_rate_state) >= self.clock_rate {
                 self.clo = true;
-                self.bit_count = self.bit_count.wrapping_add(1);
-                if self.bit_count == 9 {
-                    self.set_reg_bit(Reg::IF, 3, true);
-                }
-
-                self.rol_reg >>= 1;
-                self.rol_reg |= 0x8000;
+                self.shift_bit();
 
-                while (timer_state - self.clock_rate_state) >= (self.clock_rate / 1) as usize {
-                    self.clock_rate_state += self.clock_rate / 1;
+                while (timer_state - self.clock_rate_state) >= self.clock_rate {
+                    self.clock_rate_state += self.clock_rate;
                 }
             }
-            if self.clo && ((timer_state - self.clock_rate_state) > (self.clock_rate / 10) as usize)
-            {
+            if self.clo && ((timer_state - self.clock_rate_state) > self.clock_rate / 10) {
                 self.clo = false;
             }
         }
 
+        if self.cli_edge {
+            self.cli_edge = false;
+            if !self.clock_output {
+                self.shift_bit();
+            }
+        }
+
         if self.new_g {
-            self.clock_rate = match self.r_g & 0x07 {
-                0x00 => 1,
-                0x01 => 2,
-                0x02 => 128,
-                0x03 => 256,
-                0x04 => 512,
-                0x05 => 1024,
-                0x06 => 2048,
-                0x07 => 4096,
-                _ => unreachable!(),
-            };
-            self.clock_rate_wait = FREQUENCY / self.clock_rate;
+            self.clock_rate = CLOCK_RATES[usize::from(self.r_g & 0x07)];
             self.clock_output = (self.r_g & 0x10) != 0;
             self.new_g = false;
         }
@@ -290,7 +449,10 @@ impl Lh5810 {
             self.r_if |= 0x02;
         }
 
-        if ((self.r_msk & 0x01 != 0) && self.irq) || ((self.r_msk & 0x02 != 0) && self.lh5810_pb7())
+        if ((self.r_msk & 0x01 != 0) && self.irq)
+            || ((self.r_msk & 0x02 != 0) && self.lh5810_pb7())
+            || ((self.r_msk & 0x04 != 0) && self.rd)
+            || ((self.r_msk & 0x08 != 0) && (self.r_if & 0x08 != 0))
         {
             self.int = true;
         }
@@ -1,17 +1,45 @@
+pub mod audio;
+pub mod basic;
+pub mod battery;
+pub mod bcd;
+pub mod ce150;
+pub mod ce158;
+pub mod clock;
 pub mod display;
+pub mod harness;
 pub mod keyboard;
 mod lh5801;
-mod lh5810;
+pub mod lh5810;
+pub mod macros;
 mod memory;
-mod pd1990ac;
+pub mod output;
+pub mod paper;
+pub mod pd1990ac;
+pub mod peripheral;
+mod png;
+pub mod replay;
+pub mod serial;
+pub mod tape;
+pub mod tape_image;
+pub mod typing;
+pub mod variables;
+mod wav;
 
 use std::time::Duration;
 
+use audio::Beeper;
+use clock::ClockSource;
 use display::DisplayController;
 pub use keyboard::Key;
-use keyboard::Keyboard;
+use keyboard::{KeyMatrix, Keyboard};
 pub use lh5801::Lh5801;
 use memory::MemoryBus;
+use output::OutputCapture;
+pub use pd1990ac::ClockTime;
+use peripheral::Peripheral;
+use replay::{InputEvent, Recording};
+use tape::TapeDeck;
+use typing::TypeQueue;
 
 use crate::{lh5810::Lh5810, pd1990ac::Pd1990ac};
 
@@ -25,18 +53,54 @@ pub struct Pc1500 {
     memory: MemoryBus,
     keyboard: Keyboard,
     display: DisplayController,
+    clock_source: ClockSource,
+    rtc_start: chrono::NaiveDateTime,
+    recording: Option<Recording>,
+    typing: TypeQueue,
+    tape: TapeDeck,
+    peripherals: Vec<Box<dyn Peripheral>>,
+    beeper: Option<Beeper>,
+    output: Option<OutputCapture>,
 }
 
 impl Pc1500 {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_clock(ClockSource::default())
+    }
+
+    /// Creates a machine whose real-time clock starts at `rtc_start` instead
+    /// of the host wall-clock time.
+    #[must_use]
+    pub fn with_rtc_time(rtc_start: chrono::NaiveDateTime) -> Self {
+        Self::with_clock(ClockSource::Fixed(rtc_start))
+    }
+
+    /// Creates a machine whose real-time clock is driven by `source`.
+    #[must_use]
+    pub fn with_clock(source: ClockSource) -> Self {
+        let rtc_start = source.start_time();
+        let mut pd1990ac = Pd1990ac::with_datetime(rtc_start);
+        pd1990ac.set_running(source.is_running());
+        // CS and OE are tied high on the main board
+        pd1990ac.set_cs(true);
+        pd1990ac.set_out_enable(true);
+
         Self {
             lh5801: Lh5801::new(),
             memory: MemoryBus::new(),
             keyboard: Keyboard::new(),
             display: DisplayController::new(),
             lh5810: Lh5810::new(),
-            pd1990ac: Pd1990ac::new(),
+            pd1990ac,
+            clock_source: source,
+            rtc_start,
+            recording: None,
+            typing: TypeQueue::new(),
+            tape: TapeDeck::default(),
+            peripherals: Vec::new(),
+            beeper: None,
+            output: None,
         }
     }
 
@@ -65,25 +129,86 @@ impl Pc1500 {
         &self.display
     }
 
+    #[must_use]
+    pub const fn cpu(&self) -> &Lh5801 {
+        &self.lh5801
+    }
+
+    pub const fn cpu_mut(&mut self) -> &mut Lh5801 {
+        &mut self.lh5801
+    }
+
+    /// Current contents of the real-time clock counters.
+    #[must_use]
+    pub fn clock_time(&self) -> ClockTime {
+        self.pd1990ac.time()
+    }
+
     pub fn press(&mut self, key: Key) {
+        self.record(key, true);
         self.keyboard.press(key);
     }
 
     pub fn release(&mut self, key: Key) {
+        self.record(key, false);
         self.keyboard.release(key);
     }
 
+    #[must_use]
+    pub const fn keyboard_matrix(&self) -> &KeyMatrix {
+        self.keyboard.matrix()
+    }
+
+    /// Swaps the keyboard wiring, e.g. for a model with a different layout.
+    pub const fn set_keyboard_matrix(&mut self, matrix: KeyMatrix) {
+        self.keyboard.set_matrix(matrix);
+    }
+
+    pub const fn set_keyboard_ghosting(&mut self, ghosting: bool) {
+        self.keyboard.set_ghosting(ghosting);
+    }
+
+    /// Starts recording every key press and release together with the CPU
+    /// tick it happened at. Replays start from power-on, so only recordings
+    /// begun before the first frame is run can be replayed.
+    pub fn start_recording(&mut self) {
+        let mut recording = Recording::with_clock(self.clock_source, self.rtc_start);
+        recording.set_start_tick(self.lh5801.get_ticks());
+        self.recording = Some(recording);
+    }
+
+    /// Stops the current recording, if any, and returns it.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        let ticks = self.lh5801.get_ticks();
+        self.recording.take().map(|mut recording| {
+            recording.set_end_tick(ticks);
+            recording
+        })
+    }
+
+    #[must_use]
+    pub const fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    fn record(&mut self, key: Key, pressed: bool) {
+        let tick = self.lh5801.get_ticks();
+        if let Some(recording) = &mut self.recording {
+            recording.push(InputEvent { tick, key, pressed });
+        }
+    }
+
     fn read_bit(byte: u8, position: u8) -> bool {
         ((byte >> position) & 0x01) != 0
     }
 
     fn step(&mut self) {
+        self.pd1990ac.tick(self.lh5801.timer_state());
         if self.lh5810.new_opc() {
             let t = self.lh5810.get_reg(lh5810::Reg::OPC);
             self.pd1990ac.set_data(Self::read_bit(t, 0));
             self.pd1990ac.set_stb(Self::read_bit(t, 1));
             self.pd1990ac.set_clk(Self::read_bit(t, 2));
-            self.pd1990ac.set_out_enable(Self::read_bit(t, 3));
             self.pd1990ac.set_c0(Self::read_bit(t, 3));
             self.pd1990ac.set_c1(Self::read_bit(t, 4));
             self.pd1990ac.set_c2(Self::read_bit(t, 5));
@@ -92,17 +217,21 @@ impl Pc1500 {
             self.lh5810.set_new_opc(false);
         }
 
-        self.lh5810.set_reg_bit(
+        // TP is the time base of the tape routines
+        self.lh5810.set_input_bit(
             lh5810::Reg::OPB,
             5,
             self.pd1990ac.get_tp(self.lh5801.timer_state()),
         );
         self.lh5810
-            .set_reg_bit(lh5810::Reg::OPB, 6, self.pd1990ac.get_data());
+            .set_input_bit(lh5810::Reg::OPB, 6, self.pd1990ac.get_data());
 
-        self.lh5810.set_reg_bit(lh5810::Reg::OPB, 3, true); // Export model vs domestic model
-        self.lh5810.set_reg_bit(lh5810::Reg::OPB, 4, false); // PB4 to GND
+        self.lh5810.set_input_bit(lh5810::Reg::OPB, 3, true); // Export model vs domestic model
+        self.lh5810.set_input_bit(lh5810::Reg::OPB, 4, false); // PB4 to GND
 
         self.lh5810.step(self.lh5801.timer_state());
+        self.step_peripherals();
+        self.step_tape();
+        self.step_audio();
     }
 }
@@ -0,0 +1,403 @@
+// Keyboard macros: scripted key sequences with timing
+
+use core::{error::Error, fmt};
+
+use crate::{
+    Key, Pc1500, TICKS_PER_FRAME,
+    display::{CELL_WIDTH, DISPLAY_WIDTH, GLYPH_WIDTH},
+    pd1990ac::FREQUENCY,
+    replay::Recording,
+};
+
+const TICKS_PER_MILLI: u64 = FREQUENCY as u64 / 1000;
+const DEFAULT_TAP: Delay = Delay::Millis(100);
+const DEFAULT_TEXT_TIMEOUT: Delay = Delay::Millis(10_000);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delay {
+    Millis(u64),
+    Frames(u64),
+}
+
+impl Delay {
+    #[must_use]
+    pub fn ticks(self) -> usize {
+        let ticks = match self {
+            Self::Millis(millis) => millis * TICKS_PER_MILLI,
+            Self::Frames(frames) => frames * TICKS_PER_FRAME as u64,
+        };
+        usize::try_from(ticks).unwrap_or(usize::MAX)
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match (s.strip_suffix("ms"), s.strip_suffix("frames")) {
+            (Some(millis), _) => millis.parse().ok().map(Self::Millis),
+            (_, Some(frames)) => frames.parse().ok().map(Self::Frames),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Millis(millis) => write!(f, "{millis}ms"),
+            Self::Frames(frames) => write!(f, "{frames}frames"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Press(Key),
+    Release(Key),
+    /// Press, keep the key down for the delay, release
+    Hold(Key, Delay),
+    Wait(Delay),
+    /// Type text through [`Pc1500::type_text`] and wait until it is entered
+    Type(String),
+    /// Wait until the text is visible on the display, failing after the delay
+    Until(String, Delay),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Press(key) => write!(f, "press {key}"),
+            Self::Release(key) => write!(f, "release {key}"),
+            Self::Hold(key, delay) => write!(f, "hold {key} {delay}"),
+            Self::Wait(delay) => write!(f, "wait {delay}"),
+            Self::Type(text) => write!(f, "type \"{}\"", escape(text)),
+            Self::Until(text, delay) => write!(f, "until \"{}\" {delay}", escape(text)),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Splits a quoted string argument off the front of `s`, returning the
+/// unescaped text and whatever follows the closing quote.
+fn parse_quoted(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut text = String::new();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((text, s.get(index + 2..)?.trim())),
+            '\\' => match chars.next()?.1 {
+                'n' => text.push('\n'),
+                escaped => text.push(escaped),
+            },
+            c => text.push(c),
+        }
+    }
+
+    None
+}
+
+/// A scripted key sequence. The text format has one step per line, `#`
+/// starts a comment and key names are the ones printed by [`Key`]:
+///
+/// ```text
+/// # Switch to RUN mode and call DEF A
+/// tap MODE
+/// wait 10frames
+/// press DEF
+/// hold A 150ms
+/// release DEF
+/// type "42\n"
+/// until "DONE" 5000ms
+/// ```
+///
+/// `tap KEY` is shorthand for holding the key for 100 ms and `until` waits
+/// 10 s by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyMacro {
+    steps: Vec<Step>,
+}
+
+impl KeyMacro {
+    #[must_use]
+    pub const fn new(steps: Vec<Step>) -> Self {
+        Self { steps }
+    }
+
+    #[must_use]
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn from_text(source: &str) -> Result<Self, ParseMacroError> {
+        let mut steps = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = ParseMacroError { line: index + 1 };
+            let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+            let args = args.trim();
+            let mut fields = args.split_whitespace();
+            let key = fields.next().and_then(|key| key.parse::<Key>().ok());
+            let delay = fields.next().and_then(Delay::parse);
+
+            let step = match command {
+                "press" => Step::Press(key.ok_or(error)?),
+                "release" => Step::Release(key.ok_or(error)?),
+                "tap" => Step::Hold(key.ok_or(error)?, DEFAULT_TAP),
+                "hold" => Step::Hold(key.ok_or(error)?, delay.ok_or(error)?),
+                "wait" => Step::Wait(Delay::parse(args).ok_or(error)?),
+                "type" => match parse_quoted(args) {
+                    Some((text, "")) => Step::Type(text),
+                    _ => return Err(error),
+                },
+                "until" => match parse_quoted(args) {
+                    Some((text, "")) => Step::Until(text, DEFAULT_TEXT_TIMEOUT),
+                    Some((text, timeout)) => Step::Until(text, Delay::parse(timeout).ok_or(error)?),
+                    None => return Err(error),
+                },
+                _ => return Err(error),
+            };
+            steps.push(step);
+        }
+
+        Ok(Self { steps })
+    }
+
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Turns recorded live input into a macro, replacing the time between
+    /// events with waits.
+    #[must_use]
+    pub fn from_recording(recording: &Recording) -> Self {
+        let mut steps = Vec::new();
+        let mut last_tick = recording.events().first().map_or(0, |event| event.tick);
+
+        for event in recording.events() {
+            let millis = (event.tick - last_tick) as u64 / TICKS_PER_MILLI;
+            if millis > 0 {
+                steps.push(Step::Wait(Delay::Millis(millis)));
+            }
+            last_tick = event.tick;
+
+            steps.push(if event.pressed {
+                Step::Press(event.key)
+            } else {
+                Step::Release(event.key)
+            });
+        }
+
+        Self { steps }
+    }
+}
+
+impl fmt::Display for KeyMacro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseMacroError {
+    line: usize,
+}
+
+impl ParseMacroError {
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseMacroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid macro step on line {}", self.line)
+    }
+}
+
+impl Error for ParseMacroError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroStatus {
+    Running,
+    Finished,
+    /// An `until` step did not see its text in time
+    TimedOut {
+        step: usize,
+    },
+    /// A `type` step contains characters that cannot be typed
+    InvalidText {
+        step: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pending {
+    None,
+    Wait { until: usize },
+    Hold { key: Key, until: usize },
+    Type,
+    Until { step: usize, deadline: usize },
+}
+
+/// Executes a [`KeyMacro`] against a machine, one frame at a time so it can
+/// drive a frontend as well as headless tests.
+pub struct MacroPlayer {
+    steps: Vec<Step>,
+    next_step: usize,
+    pending: Pending,
+    status: MacroStatus,
+}
+
+impl MacroPlayer {
+    #[must_use]
+    pub fn new(key_macro: KeyMacro) -> Self {
+        Self {
+            steps: key_macro.steps,
+            next_step: 0,
+            pending: Pending::None,
+            status: MacroStatus::Running,
+        }
+    }
+
+    #[must_use]
+    pub const fn status(&self) -> MacroStatus {
+        self.status
+    }
+
+    /// Runs the machine for one frame while executing macro steps.
+    pub fn step_frame(&mut self, pc1500: &mut Pc1500) -> MacroStatus {
+        let end = pc1500.lh5801.get_ticks() + TICKS_PER_FRAME;
+
+        // Display text is only matched once per frame
+        if let Pending::Until { step, deadline } = self.pending
+            && let Some(Step::Until(text, _)) = self.steps.get(step)
+        {
+            if pc1500.display_shows(text) {
+                self.pending = Pending::None;
+            } else if pc1500.lh5801.get_ticks() >= deadline {
+                self.status = MacroStatus::TimedOut { step };
+            } else {
+                // Keep waiting
+            }
+        }
+
+        while pc1500.lh5801.get_ticks() < end {
+            self.advance(pc1500);
+            if self.status != MacroStatus::Running {
+                break;
+            }
+            pc1500.run();
+        }
+
+        if self.status != MacroStatus::Running {
+            // Finish the frame so the caller keeps a steady pace
+            while pc1500.lh5801.get_ticks() < end {
+                pc1500.run();
+            }
+        }
+
+        self.status
+    }
+
+    /// Runs frames until the macro finishes or fails.
+    pub fn run(&mut self, pc1500: &mut Pc1500) -> MacroStatus {
+        while self.step_frame(pc1500) == MacroStatus::Running {}
+        self.status
+    }
+
+    fn advance(&mut self, pc1500: &mut Pc1500) {
+        let ticks = pc1500.lh5801.get_ticks();
+
+        loop {
+            match self.pending {
+                Pending::None => {}
+                Pending::Wait { until } if ticks >= until => self.pending = Pending::None,
+                Pending::Hold { key, until } if ticks >= until => {
+                    pc1500.release(key);
+                    self.pending = Pending::None;
+                }
+                Pending::Type if !pc1500.is_typing() => self.pending = Pending::None,
+                Pending::Wait { .. }
+                | Pending::Hold { .. }
+                | Pending::Type
+                | Pending::Until { .. } => {
+                    return;
+                }
+            }
+
+            let Some(step) = self.steps.get(self.next_step) else {
+                self.status = MacroStatus::Finished;
+                return;
+            };
+            self.next_step += 1;
+
+            self.pending = match step {
+                Step::Press(key) => {
+                    pc1500.press(*key);
+                    Pending::None
+                }
+                Step::Release(key) => {
+                    pc1500.release(*key);
+                    Pending::None
+                }
+                Step::Hold(key, delay) => {
+                    pc1500.press(*key);
+                    Pending::Hold {
+                        key: *key,
+                        until: ticks + delay.ticks(),
+                    }
+                }
+                Step::Wait(delay) => Pending::Wait {
+                    until: ticks + delay.ticks(),
+                },
+                Step::Type(text) => {
+                    if pc1500.type_text(text).is_err() {
+                        self.status = MacroStatus::InvalidText {
+                            step: self.next_step - 1,
+                        };
+                        return;
+                    }
+                    Pending::Type
+                }
+                Step::Until(_, timeout) => Pending::Until {
+                    step: self.next_step - 1,
+                    deadline: ticks + timeout.ticks(),
+                },
+            };
+        }
+    }
+}
+
+impl Pc1500 {
+    /// Whether `text` is currently visible on the display, aligned to the
+    /// character cells.
+    pub fn display_shows(&mut self, text: &str) -> bool {
+        let glyphs: Option<Vec<_>> = text.bytes().map(|c| self.glyph(c)).collect();
+        let Some(glyphs) = glyphs else {
+            return false;
+        };
+
+        let columns = *self.display().columns();
+        let cells = DISPLAY_WIDTH / CELL_WIDTH;
+
+        (0..=cells.saturating_sub(glyphs.len())).any(|first| {
+            glyphs.iter().enumerate().all(|(offset, glyph)| {
+                let x = (first + offset) * CELL_WIDTH;
+                columns[x..x + GLYPH_WIDTH] == *glyph
+            })
+        })
+    }
+}
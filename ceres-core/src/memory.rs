@@ -4,14 +4,14 @@ const PC1500_ROM_BYTES: &[u8] =
     include_bytes!("../../Sharp_PC-1500_ROM_Disassembly/PC-1500_ROM-A04.bin");
 const INITIAL_VALUE: u8 = 0xFF;
 
-const STANDARD_USER_MEMORY_BEGIN: u32 = 0x4000;
-const STANDARD_USER_MEMORY_END: u32 = 0x57FF;
-const STANDARD_USER_MEMORY_SIZE: usize =
+pub const STANDARD_USER_MEMORY_BEGIN: u32 = 0x4000;
+pub const STANDARD_USER_MEMORY_END: u32 = 0x57FF;
+pub const STANDARD_USER_MEMORY_SIZE: usize =
     (STANDARD_USER_MEMORY_END - STANDARD_USER_MEMORY_BEGIN + 1) as usize;
 
 const STANDARD_USER_SYSTEM_MEMORY_BEGIN: u32 = 0x7600;
 const STANDARD_USER_SYSTEM_MEMORY_END: u32 = 0x7FFF;
-const STANDARD_USER_SYSTEM_MEMORY_SIZE: usize =
+pub const STANDARD_USER_SYSTEM_MEMORY_SIZE: usize =
     (STANDARD_USER_SYSTEM_MEMORY_END - STANDARD_USER_SYSTEM_MEMORY_BEGIN + 1) as usize;
 
 const ROM_BEGIN: u32 = 0xC000;
@@ -62,6 +62,16 @@ impl MemoryBus {
 }
 
 impl Pc1500 {
+    /// Whether the ROM image holds the BASIC interpreter. The image bundled
+    /// with the sources is a stub that only loops after reset, so nothing
+    /// that needs the ROM, like booting to the prompt, works with it.
+    #[must_use]
+    pub fn has_basic_rom(&self) -> bool {
+        // The stub has no character generator
+        self.glyph(b'A')
+            .is_some_and(|glyph| glyph.iter().any(|&column| column != 0))
+    }
+
     fn mirror_addresses(&self, addr: u32) -> u32 {
         if addr >= 0x7000 && addr <= 0x75FF {
             return addr & 0x1FF | 0x7600;
@@ -100,14 +110,43 @@ impl Pc1500 {
                     [(addr - STANDARD_USER_SYSTEM_MEMORY_BEGIN) as usize]
             }
             ROM_BEGIN..=ROM_END => self.memory.rom[(addr - ROM_BEGIN) as usize],
-            // Unmapped
-            _ => {
+            // Expansion connector, otherwise unmapped
+            _ => self.read_peripherals(addr).unwrap_or_else(|| {
                 println!("Read from unmapped address: {:#06X}", addr);
                 INITIAL_VALUE
-            }
+            }),
+        }
+    }
+
+    /// Reads `addr` as the CPU does, with the side effects some registers
+    /// have on reads. [`Pc1500::read_byte`] leaves them alone.
+    pub fn bus_read(&mut self, addr: u32) -> u8 {
+        let addr = self.mirror_addresses(addr);
+
+        match addr {
+            0x1F005 => self.lh5810.read_reg(lh5810::Reg::U),
+            0x1F006..=0x1F00F
+            | STANDARD_USER_MEMORY_BEGIN..=STANDARD_USER_MEMORY_END
+            | STANDARD_USER_SYSTEM_MEMORY_BEGIN..=STANDARD_USER_SYSTEM_MEMORY_END
+            | ROM_BEGIN..=ROM_END => self.read_byte(addr),
+            // Expansion connector, otherwise unmapped
+            _ => self
+                .bus_read_peripherals(addr)
+                .unwrap_or_else(|| self.read_byte(addr)),
         }
     }
 
+    /// Big-endian, the byte order of the ROM's pointers
+    pub(crate) fn read_word(&self, addr: u32) -> u16 {
+        (u16::from(self.read_byte(addr)) << 8) | u16::from(self.read_byte(addr + 1))
+    }
+
+    pub(crate) fn read_bytes(&self, start: u16, end: u16) -> Vec<u8> {
+        (start..end)
+            .map(|addr| self.read_byte(u32::from(addr)))
+            .collect()
+    }
+
     pub fn write_byte(&mut self, addr: u32, value: u8) {
         let addr = self.mirror_addresses(addr);
 
@@ -161,9 +200,11 @@ impl Pc1500 {
             ROM_BEGIN..=ROM_END => {
                 // ROM is read-only, ignore writes
             }
-            // Unmapped
+            // Expansion connector, otherwise unmapped
             _ => {
-                println!("Write to unmapped address: {:#06X}", addr);
+                if !self.write_peripherals(addr, value) {
+                    println!("Write to unmapped address: {:#06X}", addr);
+                }
             }
         }
     }
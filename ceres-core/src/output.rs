@@ -0,0 +1,90 @@
+// Text written by the ROM, trapped at its character output routines
+
+use std::mem;
+
+use crate::{Pc1500, basic};
+
+// ROM entry points. CHAR_OUT is entered with the character in A.
+pub const CHAR_OUT: u16 = 0xED4D;
+pub const BCMD_PRINT: u16 = 0xE4EB;
+pub const BCMD_RUN: u16 = 0xC8B4;
+
+const CR: u8 = 0x0D;
+
+#[derive(Debug, Default)]
+pub(crate) struct OutputCapture {
+    display: String,
+    // Nothing is kept until a program is RUN
+    waiting_for_run: bool,
+}
+
+impl OutputCapture {
+    fn push(output: &mut String, byte: u8) {
+        if byte == CR {
+            output.push('\n');
+        } else {
+            basic::push_char(output, byte);
+        }
+    }
+
+    // Each PRINT statement starts a new line
+    fn new_line(output: &mut String) {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+    }
+}
+
+impl Pc1500 {
+    /// Starts capturing every character the ROM writes to the display.
+    /// Characters without a text form are written as `[XX]`, and each PRINT
+    /// statement starts a new line.
+    ///
+    /// LPRINT output to the CE-150 is not captured: its character output
+    /// routine is in the CE-150 ROM, and no listing of that ROM gives its
+    /// address.
+    pub fn enable_output_capture(&mut self) {
+        self.output = Some(OutputCapture::default());
+    }
+
+    /// Stops capturing, dropping any text not taken yet.
+    pub fn disable_output_capture(&mut self) {
+        self.output = None;
+    }
+
+    #[must_use]
+    pub const fn is_capturing_output(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Display text captured since the last call.
+    pub fn take_display_output(&mut self) -> String {
+        self.output
+            .as_mut()
+            .map(|output| mem::take(&mut output.display))
+            .unwrap_or_default()
+    }
+
+    // Like enable_output_capture, but from the next RUN
+    pub(crate) fn capture_run_output(&mut self) {
+        self.output = Some(OutputCapture {
+            waiting_for_run: true,
+            ..OutputCapture::default()
+        });
+    }
+
+    pub(crate) fn output_hook(&mut self, addr: u16) {
+        let a = self.lh5801.a();
+        let Some(output) = &mut self.output else {
+            return;
+        };
+
+        match addr {
+            BCMD_RUN => output.waiting_for_run = false,
+            _ if output.waiting_for_run => {}
+            CHAR_OUT => OutputCapture::push(&mut output.display, a),
+            BCMD_PRINT => OutputCapture::new_line(&mut output.display),
+            _ => {}
+        }
+    }
+}
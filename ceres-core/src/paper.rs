@@ -0,0 +1,256 @@
+// Plotter paper: pen strokes drawn by the CE-150, exported as SVG or PNG
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use crate::png;
+
+/// Plotter steps per millimetre, each step is 0.2 mm
+pub const STEPS_PER_MM: i32 = 5;
+// Pen tip width in steps and rasterised pixels per step
+const PEN_WIDTH: i32 = 2;
+const PIXELS_PER_STEP: i32 = 2;
+// Blank paper around the drawing, in steps
+const MARGIN: i32 = 10;
+
+/// The four pens of the CE-150 drum, in rotation order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PenColor {
+    #[default]
+    Black,
+    Blue,
+    Green,
+    Red,
+}
+
+impl PenColor {
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Black => Self::Blue,
+            Self::Blue => Self::Green,
+            Self::Green => Self::Red,
+            Self::Red => Self::Black,
+        }
+    }
+
+    #[must_use]
+    pub const fn rgb(self) -> [u8; 3] {
+        match self {
+            Self::Black => [0x20, 0x20, 0x20],
+            Self::Blue => [0x20, 0x40, 0xC0],
+            Self::Green => [0x20, 0x90, 0x40],
+            Self::Red => [0xD0, 0x30, 0x30],
+        }
+    }
+
+    fn svg(self) -> String {
+        let [r, g, b] = self.rgb();
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// A continuous pen-down line in plotter steps, x across the paper and y
+/// growing as the paper feeds out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stroke {
+    color: PenColor,
+    points: Vec<(i32, i32)>,
+}
+
+impl Stroke {
+    #[must_use]
+    pub const fn color(&self) -> PenColor {
+        self.color
+    }
+
+    #[must_use]
+    pub fn points(&self) -> &[(i32, i32)] {
+        &self.points
+    }
+
+    fn extend(&mut self, point: (i32, i32)) {
+        // Merge steps in the same direction into one segment
+        if let [.., a, b] = self.points.as_mut_slice() {
+            let (dx1, dy1) = (b.0 - a.0, b.1 - a.1);
+            let (dx2, dy2) = (point.0 - b.0, point.1 - b.1);
+            if dx1 * dy2 == dy1 * dx2 && dx1 * dx2 + dy1 * dy2 > 0 {
+                *b = point;
+                return;
+            }
+        }
+        if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+    }
+}
+
+/// Everything the plotter has drawn.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Paper {
+    strokes: Vec<Stroke>,
+    // Whether the last stroke is still being drawn
+    drawing: bool,
+}
+
+impl Paper {
+    #[must_use]
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.drawing = false;
+    }
+
+    /// Draws to `point` if the pen is down, starting a new stroke when the
+    /// pen was just lowered.
+    pub(crate) fn pen_at(&mut self, point: (i32, i32), color: PenColor) {
+        match self.strokes.last_mut() {
+            Some(stroke) if self.drawing && stroke.color == color => stroke.extend(point),
+            _ => {
+                self.strokes.push(Stroke {
+                    color,
+                    points: vec![point],
+                });
+                self.drawing = true;
+            }
+        }
+    }
+
+    pub(crate) const fn pen_up(&mut self) {
+        self.drawing = false;
+    }
+
+    /// Smallest and largest coordinates drawn, with a margin.
+    fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        let points = self.strokes.iter().flat_map(|stroke| &stroke.points);
+        let (min, max) = points.fold(
+            ((0, 0), (0, 0)),
+            |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+                ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+            },
+        );
+        (
+            (min.0 - MARGIN, min.1 - MARGIN),
+            (max.0 + MARGIN, max.1 + MARGIN),
+        )
+    }
+
+    /// Renders the strokes as an SVG document sized in millimetres.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds();
+        let (width, height) = (max_x - min_x, max_y - min_y);
+
+        let mut lines = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" \
+                 viewBox=\"{min_x} {min_y} {width} {height}\">",
+                width / STEPS_PER_MM,
+                height / STEPS_PER_MM,
+            ),
+            format!(
+                "<rect x=\"{min_x}\" y=\"{min_y}\" width=\"{width}\" height=\"{height}\" \
+                 fill=\"white\"/>"
+            ),
+        ];
+
+        for stroke in &self.strokes {
+            let points: Vec<String> = stroke
+                .points
+                .iter()
+                .map(|(x, y)| format!("{x},{y}"))
+                .collect();
+            // A single point still leaves a dot
+            let points = match points.as_slice() {
+                [point] => format!("{point} {point}"),
+                _ => points.join(" "),
+            };
+            lines.push(format!(
+                "<polyline points=\"{points}\" fill=\"none\" stroke=\"{}\" \
+                 stroke-width=\"{PEN_WIDTH}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                stroke.color.svg()
+            ));
+        }
+
+        lines.push("</svg>\n".to_owned());
+        lines.join("\n")
+    }
+
+    pub fn save_svg<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_svg())
+    }
+
+    /// Rasterises the strokes on white paper. Returns the width, height and
+    /// RGB pixels.
+    #[must_use]
+    pub fn rasterize(&self) -> (u32, u32, Vec<u8>) {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds();
+        let width = (max_x - min_x + 1) * PIXELS_PER_STEP;
+        let height = (max_y - min_y + 1) * PIXELS_PER_STEP;
+        let (width_px, height_px) = (width.unsigned_abs(), height.unsigned_abs());
+        let mut rgb = vec![0xFF; width_px as usize * height_px as usize * 3];
+
+        let mut dot = |x: i32, y: i32, color: [u8; 3]| {
+            let radius = PEN_WIDTH * PIXELS_PER_STEP / 2;
+            for py in y - radius..=y + radius {
+                for px in x - radius..=x + radius {
+                    if (0..width).contains(&px) && (0..height).contains(&py) {
+                        let index = (py * width + px).unsigned_abs() as usize * 3;
+                        rgb[index..index + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+        };
+
+        for stroke in &self.strokes {
+            let color = stroke.color.rgb();
+            let pixels: Vec<(i32, i32)> = stroke
+                .points
+                .iter()
+                .map(|&(x, y)| ((x - min_x) * PIXELS_PER_STEP, (y - min_y) * PIXELS_PER_STEP))
+                .collect();
+
+            if let [(x, y)] = pixels.as_slice() {
+                dot(*x, *y, color);
+            }
+            for segment in pixels.windows(2) {
+                let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+                // Bresenham
+                let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+                let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+                let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+                loop {
+                    dot(x, y, color);
+                    if (x, y) == (x1, y1) {
+                        break;
+                    }
+                    if error * 2 >= dy {
+                        error += dy;
+                        x += sx;
+                    }
+                    if error * 2 <= dx {
+                        error += dx;
+                        y += sy;
+                    }
+                }
+            }
+        }
+
+        (width_px, height_px, rgb)
+    }
+
+    pub fn write_png<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        let (width, height, rgb) = self.rasterize();
+        png::write_png(writer, width, height, &rgb)
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
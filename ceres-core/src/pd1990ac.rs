@@ -2,7 +2,76 @@ use chrono::{Datelike, Timelike};
 
 pub const FREQUENCY: usize = 2600000 / 2;
 
+// Commands, selected with C2 C1 C0 and executed on the rising edge of STB
+pub const REGISTER_HOLD: u8 = 0x0;
+pub const REGISTER_SHIFT: u8 = 0x1;
+pub const TIME_SET: u8 = 0x2;
+pub const TIME_READ: u8 = 0x3;
+pub const TP_64HZ: u8 = 0x4;
+pub const TP_256HZ: u8 = 0x5;
+pub const TP_2048HZ: u8 = 0x6;
+/// Counts seconds 1024 times faster, on the uPD1990AC
+pub const TEST_MODE: u8 = 0x7;
+/// Executes the serial command shifted into the command register, on the
+/// uPD4990A
+pub const SERIAL: u8 = 0x7;
+
+// Serial commands of the uPD4990A, which extends the uPD1990AC command set
+// through a 4-bit command register. 0x0 to 0x6 are the same as above.
+pub const TP_4096HZ: u8 = 0x7;
+pub const TP_1S_INTERVAL: u8 = 0x8;
+pub const TP_10S_INTERVAL: u8 = 0x9;
+pub const TP_30S_INTERVAL: u8 = 0xA;
+pub const TP_60S_INTERVAL: u8 = 0xB;
+pub const INTERVAL_RESET: u8 = 0xC;
+pub const INTERVAL_START: u8 = 0xD;
+pub const INTERVAL_STOP: u8 = 0xE;
+pub const SERIAL_TEST_MODE: u8 = 0xF;
+
+// Seconds counted per second in test mode
+const TEST_RATE: usize = 1024;
+// Seconds, minutes, hours, days, weekday and month
+const REGISTER_BITS: u32 = 40;
+
+/// Calendar and time of day held by the real-time clock. The chip has no
+/// year counter, February always has 28 days.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockTime {
+    pub month: u8,
+    pub day: u8,
+    /// 0 is Sunday
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl From<chrono::NaiveDateTime> for ClockTime {
+    fn from(datetime: chrono::NaiveDateTime) -> Self {
+        // chrono keeps every field in range
+        let field = |value: u32| u8::try_from(value).unwrap_or_default();
+        Self {
+            month: field(datetime.month()),
+            day: field(datetime.day()),
+            weekday: field(datetime.weekday().num_days_from_sunday()),
+            hour: field(datetime.hour()),
+            minute: field(datetime.minute()),
+            second: field(datetime.second()),
+        }
+    }
+}
+
+/// The chip fitted. The PC-1500 has a uPD1990AC, the pin compatible
+/// uPD4990A adds the serial commands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    #[default]
+    Upd1990ac,
+    Upd4990a,
+}
+
 pub struct Pd1990ac {
+    variant: Variant,
     seconds: u16,
     minutes: u16,
     hours: u16,
@@ -19,7 +88,6 @@ pub struct Pd1990ac {
     data_in: bool,
     gnd: bool,
     clk: bool,
-    data_out: bool,
     tp: bool,
     out_enable: bool,
     n_xtal: bool,
@@ -27,16 +95,21 @@ pub struct Pd1990ac {
     vdd: bool,
 
     // Internal
-    mode: u8,
-    bitno: u8,
-    current_bit: u8,
-    new_mode: bool,
-    new_clk: bool,
-    prev_mode: u8,
+    shift_register: u64,
+    command_register: u8,
+    // Whether DATA OUT shows the shift register instead of 1 Hz
+    shifting: bool,
+    test_mode: bool,
+    prev_stb: bool,
     prev_clk: bool,
-    flip_clk: bool,
-    tp_frequency: usize,
-
+    // TP period in ticks, interval periods only run while started
+    tp_period: usize,
+    tp_phase: usize,
+    interval: bool,
+    interval_running: bool,
+    running: bool,
+
+    timer_state: usize,
     previous_state: usize,
     previous_state_tp: usize,
 }
@@ -48,37 +121,39 @@ fn hex2bcd(d: u32) -> u16 {
     ((a as u16) << 8) + ((c as u16) << 4) + (b as u16) - ((c as u16) * 10)
 }
 
-fn read_bit(value: u16, position: u8) -> bool {
-    ((value >> position) & 0x01) != 0
+fn bcd2hex(value: u16) -> u8 {
+    u8::try_from((value >> 4) * 10 + (value & 0x0F)).unwrap_or_default()
 }
 
-fn set_bit(value: &mut u16, position: u8) {
-    *value |= 0x01 << position;
-}
-
-fn unset_bit(value: &mut u16, position: u8) {
-    *value &= !(0x01 << position);
+// Counts one up in BCD, invalid digits count up to the next ten as the chip does
+const fn bcd_increment(value: u16) -> u16 {
+    if value & 0x0F == 0x09 {
+        (value & 0xF0) + 0x10
+    } else {
+        value + 1
+    }
 }
 
-fn put_bit(value: &mut u16, position: u8, bit: bool) {
-    if bit {
-        set_bit(value, position);
-    } else {
-        unset_bit(value, position);
+// Last day of the month in BCD, months are counted in binary
+const fn days_in_month(month: u16) -> u16 {
+    match month {
+        2 => 0x28,
+        4 | 6 | 9 | 11 => 0x30,
+        _ => 0x31,
     }
 }
 
 impl Pd1990ac {
-    pub fn new() -> Self {
-        let now = std::time::SystemTime::now();
-        let datetime: chrono::DateTime<chrono::Utc> = now.into();
-        Self {
-            seconds: hex2bcd(datetime.second()),
-            minutes: hex2bcd(datetime.minute()),
-            hours: hex2bcd(datetime.hour()),
-            days: hex2bcd(datetime.day()),
-            weekday: hex2bcd(datetime.weekday().num_days_from_sunday()),
-            month: datetime.month() as u16,
+    #[must_use]
+    pub fn with_datetime(datetime: chrono::NaiveDateTime) -> Self {
+        let mut pd1990ac = Self {
+            variant: Variant::Upd1990ac,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            weekday: 0,
+            month: 0,
 
             // Signals
             c0: false,
@@ -89,7 +164,6 @@ impl Pd1990ac {
             data_in: false,
             gnd: false,
             clk: false,
-            data_out: false,
             tp: false,
             out_enable: false,
             n_xtal: false,
@@ -97,161 +171,245 @@ impl Pd1990ac {
             vdd: false,
 
             // Internal
-            mode: 0,
-            bitno: 0,
-            current_bit: 0,
-            new_mode: false,
-            new_clk: false,
-            prev_mode: 0x10,
+            shift_register: 0,
+            command_register: 0,
+            shifting: false,
+            test_mode: false,
+            prev_stb: false,
             prev_clk: false,
-            flip_clk: false,
-            tp_frequency: 1,
+            tp_period: FREQUENCY,
+            tp_phase: 0,
+            interval: false,
+            interval_running: false,
+            running: true,
 
+            timer_state: 0,
             previous_state: 0,
             previous_state_tp: 0,
+        };
+        pd1990ac.set_time(ClockTime::from(datetime), 0);
+        pd1990ac
+    }
+
+    #[must_use]
+    pub const fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub const fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    #[must_use]
+    pub fn time(&self) -> ClockTime {
+        ClockTime {
+            month: u8::try_from(self.month).unwrap_or_default(),
+            day: bcd2hex(self.days),
+            weekday: bcd2hex(self.weekday),
+            hour: bcd2hex(self.hours),
+            minute: bcd2hex(self.minutes),
+            second: bcd2hex(self.seconds),
         }
     }
 
-    pub fn step(&mut self, timer_state: usize) -> bool {
-        // Mode:
-        // 0 - Register Hold DATA OUT = 1 Hz
-        // 1 - Register Shift DATA OUT = [LSB] = 0 or 1
-        // 2 - Time Set DATA OUT = [LSB] = 0 or 1
-        // 3 - Time Read DATA OUT = 1 Hz
+    /// Loads the time counters and starts a new second at `timer_state`.
+    pub fn set_time(&mut self, time: ClockTime, timer_state: usize) {
+        self.seconds = hex2bcd(time.second.into());
+        self.minutes = hex2bcd(time.minute.into());
+        self.hours = hex2bcd(time.hour.into());
+        self.days = hex2bcd(time.day.into());
+        self.weekday = hex2bcd(time.weekday.into());
+        self.month = time.month.into();
+        self.previous_state = timer_state;
+    }
+
+    /// Stops or restarts counting seconds, for a clock driven by the host.
+    pub const fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
 
-        if self.previous_state == 0 {
+    /// Runs the time counter up to `timer_state`, one second every
+    /// FREQUENCY ticks.
+    pub const fn tick(&mut self, timer_state: usize) {
+        // The timer state starts over when the CPU is reset
+        if self.previous_state == 0 || timer_state < self.previous_state || !self.running {
             self.previous_state = timer_state;
         }
+        self.timer_state = timer_state;
 
-        while (timer_state - self.previous_state) >= FREQUENCY {
-            self.previous_state += FREQUENCY;
+        let second = self.second_ticks();
+        while (timer_state - self.previous_state) >= second {
+            self.previous_state += second;
+            self.count_second();
         }
+    }
 
-        if self.stb {
-            // Mode can change
-            self.mode = (self.c0 as u8) + ((self.c1 as u8) << 1) + ((self.c2 as u8) << 2);
-            if self.mode != self.prev_mode {
-                self.new_mode = true;
-                self.prev_mode = self.mode;
-            } else {
-                self.new_mode = false;
-            }
+    const fn second_ticks(&self) -> usize {
+        if self.test_mode {
+            FREQUENCY / TEST_RATE
+        } else {
+            FREQUENCY
         }
+    }
 
-        if self.clk != self.prev_clk {
-            self.flip_clk = true;
-            self.prev_clk = self.clk;
-        } else {
-            self.flip_clk = false;
+    const fn count_second(&mut self) {
+        self.seconds = bcd_increment(self.seconds);
+        if self.seconds < 0x60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = bcd_increment(self.minutes);
+        if self.minutes < 0x60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = bcd_increment(self.hours);
+        if self.hours < 0x24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.weekday = (self.weekday + 1) % 7;
+        self.days = bcd_increment(self.days);
+        if self.days <= days_in_month(self.month) {
+            return;
         }
+        self.days = 1;
+
+        self.month = self.month % 12 + 1;
+    }
 
-        if self.mode == 4 {
-            self.tp_frequency = 64;
+    /// Applies the input pins set since the last call: commands on the
+    /// rising edge of STB, shifts on the rising edge of CLK. Both are
+    /// ignored while CS is low.
+    pub fn step(&mut self, timer_state: usize) {
+        self.tick(timer_state);
+        self.get_tp(timer_state);
+
+        let strobe = self.stb && !self.prev_stb;
+        let clock = self.clk && !self.prev_clk;
+        self.prev_stb = self.stb;
+        self.prev_clk = self.clk;
+        if !self.cs {
+            return;
         }
-        if self.mode == 0 {
-            self.clk = true;
-            self.flip_clk = true;
-            self.bitno = 0;
+
+        let pins = u8::from(self.c0) | (u8::from(self.c1) << 1) | (u8::from(self.c2) << 2);
+        let serial = self.variant == Variant::Upd4990a && pins == SERIAL;
+        if clock && serial {
+            self.command_register = (self.command_register >> 1) | (u8::from(self.data_in) << 3);
         }
+        if clock && !serial && self.shifting {
+            self.shift_register =
+                (self.shift_register >> 1) | (u64::from(self.data_in) << (REGISTER_BITS - 1));
+        }
+        if strobe {
+            // Commands are numbered as serial commands from here on
+            let command = match (self.variant, pins) {
+                (Variant::Upd4990a, SERIAL) => self.command_register,
+                (Variant::Upd1990ac, TEST_MODE) => SERIAL_TEST_MODE,
+                _ => pins,
+            };
+            self.execute(command, timer_state);
+        }
+    }
 
-        if self.clk && self.flip_clk {
-            match self.mode {
-                0x00 | 0x01 => {
-                    // Start afresh with shifting or Load Register
-                    match self.bitno {
-                        0x00..=0x03 => {
-                            // Seconds (1)
-                            self.data_out = read_bit(self.seconds, self.bitno);
-                        }
-                        0x04..=0x07 => {
-                            // Seconds (10)
-                            self.data_out = read_bit(self.seconds, self.bitno);
-                        }
-                        0x08..=0x0B => {
-                            // Minutes (1)
-                            self.data_out = read_bit(self.minutes, self.bitno - 0x08);
-                        }
-                        0x0C..=0x0F => {
-                            // Minutes (10)
-                            self.data_out = read_bit(self.minutes, self.bitno - 0x08);
-                        }
-                        0x10..=0x13 => {
-                            // Hours (1)
-                            self.data_out = read_bit(self.hours, self.bitno - 0x10);
-                        }
-                        0x14..=0x17 => {
-                            // Hours (10)
-                            self.data_out = read_bit(self.hours, self.bitno - 0x10);
-                        }
-                        0x18..=0x1B => {
-                            // Days (1)
-                            self.data_out = read_bit(self.days, self.bitno - 0x18);
-                        }
-                        0x1C..=0x1F => {
-                            // Days (10)
-                            self.data_out = read_bit(self.days, self.bitno - 0x18);
-                        }
-                        0x20..=0x23 => {
-                            // Weekday
-                            self.data_out = read_bit(self.weekday, self.bitno - 0x20);
-                        }
-                        0x24..=0x27 => {
-                            // Month
-                            self.data_out = read_bit(self.month, self.bitno - 0x24);
-                        }
-                        _ => {}
-                    }
-
-                    self.bitno = self.bitno.wrapping_add(1);
-                }
-                0x02 => {
-                    match self.bitno {
-                        0x00..=0x07 => {
-                            put_bit(&mut self.seconds, self.bitno, self.data_in);
-                        }
-                        0x08..=0x0F => {
-                            put_bit(&mut self.minutes, self.bitno - 0x08, self.data_in);
-                        }
-                        0x10..=0x17 => {
-                            put_bit(&mut self.hours, self.bitno - 0x10, self.data_in);
-                        }
-                        0x18..=0x1F => {
-                            put_bit(&mut self.days, self.bitno - 0x18, self.data_in);
-                        }
-                        0x20..=0x23 => {
-                            put_bit(&mut self.weekday, self.bitno - 0x20, self.data_in);
-                        }
-                        0x24..=0x27 => {
-                            put_bit(&mut self.month, self.bitno - 0x24, self.data_in);
-                        }
-                        _ => {}
-                    }
-                    self.bitno = self.bitno.wrapping_add(1);
-                }
-                _ => {}
+    fn execute(&mut self, command: u8, timer_state: usize) {
+        match command {
+            REGISTER_HOLD => self.shifting = false,
+            REGISTER_SHIFT => self.shifting = true,
+            TIME_SET => {
+                self.load_counters();
+                // Setting the time starts a new second
+                self.previous_state = timer_state;
+                self.shifting = true;
             }
+            TIME_READ => {
+                self.shift_register = self.counters();
+                self.shifting = false;
+            }
+            TP_64HZ => self.set_tp_frequency(64),
+            TP_256HZ => self.set_tp_frequency(256),
+            TP_2048HZ => self.set_tp_frequency(2048),
+            TP_4096HZ => self.set_tp_frequency(4096),
+            TP_1S_INTERVAL => self.set_tp_interval(1),
+            TP_10S_INTERVAL => self.set_tp_interval(10),
+            TP_30S_INTERVAL => self.set_tp_interval(30),
+            TP_60S_INTERVAL => self.set_tp_interval(60),
+            INTERVAL_RESET => self.tp_phase = 0,
+            INTERVAL_START => self.interval_running = true,
+            INTERVAL_STOP => self.interval_running = false,
+            SERIAL_TEST_MODE => self.test_mode = true,
+            _ => {}
+        }
+        if command != SERIAL_TEST_MODE {
+            self.test_mode = false;
         }
+    }
 
-        true
+    // Time counters in shift register order, LSB first
+    fn counters(&self) -> u64 {
+        u64::from(self.seconds)
+            | (u64::from(self.minutes) << 8)
+            | (u64::from(self.hours) << 16)
+            | (u64::from(self.days) << 24)
+            | (u64::from(self.weekday & 0x0F) << 32)
+            | (u64::from(self.month & 0x0F) << 36)
     }
 
-    pub fn get_data(&self) -> bool {
-        self.data_out
+    fn load_counters(&mut self) {
+        let field = |shift: u32, mask: u64| {
+            u16::try_from((self.shift_register >> shift) & mask).unwrap_or_default()
+        };
+        self.seconds = field(0, 0xFF);
+        self.minutes = field(8, 0xFF);
+        self.hours = field(16, 0xFF);
+        self.days = field(24, 0xFF);
+        self.weekday = field(32, 0x0F);
+        self.month = field(36, 0x0F);
     }
 
-    pub fn get_tp(&mut self, timer_state: usize) -> bool {
-        // generate tp signal. Used by tape functionality
-        let tp_state = FREQUENCY / self.tp_frequency;
+    const fn set_tp_frequency(&mut self, frequency: usize) {
+        self.tp_period = FREQUENCY / frequency;
+        self.tp_phase = 0;
+        self.interval = false;
+    }
 
-        if self.previous_state_tp == 0 {
-            self.previous_state_tp = timer_state;
+    const fn set_tp_interval(&mut self, seconds: usize) {
+        self.tp_period = FREQUENCY * seconds;
+        self.tp_phase = 0;
+        self.interval = true;
+        self.interval_running = true;
+    }
+
+    /// DATA OUT: the LSB of the shift register while shifting or setting
+    /// the time, a 1 Hz square wave otherwise. Low while OE is.
+    #[must_use]
+    pub const fn get_data(&self) -> bool {
+        if !self.out_enable {
+            false
+        } else if self.shifting {
+            self.shift_register & 1 != 0
+        } else {
+            // High for the first half of each second
+            self.timer_state - self.previous_state < self.second_ticks() / 2
         }
+    }
 
-        while (timer_state - self.previous_state_tp) >= (tp_state / 2) {
-            self.tp = !self.tp;
-            self.previous_state_tp += tp_state / 2;
+    /// TP, a square wave starting high at the selected frequency or
+    /// interval. Used by tape functionality.
+    pub fn get_tp(&mut self, timer_state: usize) -> bool {
+        if self.previous_state_tp == 0 || timer_state < self.previous_state_tp {
+            self.previous_state_tp = timer_state;
+        }
+        if !self.interval || self.interval_running {
+            self.tp_phase = (self.tp_phase + timer_state - self.previous_state_tp) % self.tp_period;
         }
+        self.previous_state_tp = timer_state;
 
+        self.tp = self.tp_phase < self.tp_period / 2;
         self.tp
     }
 
@@ -0,0 +1,168 @@
+// Devices on the 60-pin expansion connector
+
+use std::any::Any;
+
+use crate::Pc1500;
+
+// ME1 accesses are decoded from bit 16 of bus addresses
+const ME1: u32 = 0x1_0000;
+
+/// Memory space selected by the CPU for a bus cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bank {
+    /// Memory and most peripherals
+    Me0,
+    /// I/O space, where the on-board LH5810 sits at 0xF000
+    Me1,
+}
+
+/// The connector lines of one bus cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusCycle {
+    pub bank: Bank,
+    pub addr: u16,
+    /// PU and PV flip-flops of the CPU, used to switch memory banks
+    pub pu: bool,
+    pub pv: bool,
+    /// CPU timer state, in ticks
+    pub timer_state: usize,
+}
+
+impl BusCycle {
+    /// Whether this cycle addresses `begin..=end` in `bank`.
+    #[must_use]
+    pub fn selects(&self, bank: Bank, begin: u16, end: u16) -> bool {
+        self.bank == bank && (begin..=end).contains(&self.addr)
+    }
+}
+
+/// A board plugged into the 60-pin expansion connector, such as the CE-150
+/// plotter or the CE-158 interface.
+///
+/// Peripherals only see cycles the PC-1500 does not decode on board. The
+/// on-board LH5810 and PD1990AC share signals with the keyboard and each
+/// other, so they stay wired directly.
+pub trait Peripheral: Any {
+    /// Answers a read cycle, or returns `None` if the peripheral is not
+    /// selected. Leaves the peripheral as it was, so debuggers can inspect
+    /// it.
+    fn read(&self, cycle: &BusCycle) -> Option<u8>;
+
+    /// Answers a read cycle of the CPU, for registers whose reads have side
+    /// effects such as emptying a receive buffer.
+    fn bus_read(&mut self, cycle: &BusCycle) -> Option<u8> {
+        self.read(cycle)
+    }
+
+    /// Handles a write cycle, returning whether the peripheral was selected.
+    fn write(&mut self, cycle: &BusCycle, value: u8) -> bool;
+
+    /// Runs the peripheral up to `timer_state`, called after every CPU
+    /// instruction.
+    fn step(&mut self, _timer_state: usize) {}
+
+    /// Level of the interrupt output, wired to the CPU IR2 input.
+    fn interrupt(&self) -> bool {
+        false
+    }
+
+    /// Level of the tape output line, for boards with a cassette interface.
+    fn tape_output(&self) -> Option<bool> {
+        None
+    }
+
+    /// Drives the tape input line while a tape is playing.
+    fn set_tape_input(&mut self, _level: bool) {}
+}
+
+impl Pc1500 {
+    /// Plugs `peripheral` into the expansion connector. Several peripherals
+    /// of the same type may be attached, accessors return the first one.
+    pub fn attach<P: Peripheral>(&mut self, peripheral: P) {
+        self.peripherals.push(Box::new(peripheral));
+    }
+
+    /// Unplugs the first attached peripheral of type `P`.
+    pub fn detach<P: Peripheral>(&mut self) -> Option<P> {
+        let index = self
+            .peripherals
+            .iter()
+            .position(|peripheral| (&**peripheral as &dyn Any).is::<P>())?;
+        let peripheral: Box<dyn Any> = self.peripherals.remove(index);
+        peripheral.downcast().ok().map(|peripheral| *peripheral)
+    }
+
+    #[must_use]
+    pub fn peripheral<P: Peripheral>(&self) -> Option<&P> {
+        self.peripherals
+            .iter()
+            .find_map(|peripheral| (&**peripheral as &dyn Any).downcast_ref())
+    }
+
+    pub fn peripheral_mut<P: Peripheral>(&mut self) -> Option<&mut P> {
+        self.peripherals
+            .iter_mut()
+            .find_map(|peripheral| (&mut **peripheral as &mut dyn Any).downcast_mut())
+    }
+
+    fn bus_cycle(&self, addr: u32) -> BusCycle {
+        BusCycle {
+            bank: if addr & ME1 == 0 {
+                Bank::Me0
+            } else {
+                Bank::Me1
+            },
+            addr: u16::try_from(addr & 0xFFFF).unwrap_or_default(),
+            pu: self.lh5801.pu(),
+            pv: self.lh5801.pv(),
+            timer_state: self.lh5801.timer_state(),
+        }
+    }
+
+    pub(crate) fn read_peripherals(&self, addr: u32) -> Option<u8> {
+        let cycle = self.bus_cycle(addr);
+        self.peripherals
+            .iter()
+            .find_map(|peripheral| peripheral.read(&cycle))
+    }
+
+    pub(crate) fn bus_read_peripherals(&mut self, addr: u32) -> Option<u8> {
+        let cycle = self.bus_cycle(addr);
+        self.peripherals
+            .iter_mut()
+            .find_map(|peripheral| peripheral.bus_read(&cycle))
+    }
+
+    pub(crate) fn write_peripherals(&mut self, addr: u32, value: u8) -> bool {
+        let cycle = self.bus_cycle(addr);
+        // Every selected peripheral latches the data bus
+        self.peripherals
+            .iter_mut()
+            .fold(false, |selected, peripheral| {
+                peripheral.write(&cycle, value) | selected
+            })
+    }
+
+    /// Level of the first tape output line on the connector, if any.
+    pub(crate) fn tape_output(&self) -> Option<bool> {
+        self.peripherals
+            .iter()
+            .find_map(|peripheral| peripheral.tape_output())
+    }
+
+    pub(crate) fn set_tape_input(&mut self, level: bool) {
+        for peripheral in &mut self.peripherals {
+            peripheral.set_tape_input(level);
+        }
+    }
+
+    pub(crate) fn step_peripherals(&mut self) {
+        let timer_state = self.lh5801.timer_state();
+        for peripheral in &mut self.peripherals {
+            peripheral.step(timer_state);
+            if peripheral.interrupt() {
+                self.lh5801.set_ir2(true);
+            }
+        }
+    }
+}
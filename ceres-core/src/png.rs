@@ -0,0 +1,90 @@
+// Minimal PNG encoder for plotter paper, stored deflate blocks only
+
+use std::io::{self, Write};
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// PNG integers are big endian
+const fn be32(value: u32) -> [u8; 4] {
+    let mut bytes = value.to_le_bytes();
+    bytes.reverse();
+    bytes
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    // Largest prime below 2^16
+    const MODULUS: u32 = 0xFFF1;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: [u8; 4], data: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(data.len())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    writer.write_all(&be32(length))?;
+
+    let mut checked = kind.to_vec();
+    checked.extend_from_slice(data);
+    writer.write_all(&checked)?;
+    writer.write_all(&be32(crc32(&checked)))
+}
+
+/// Writes 8-bit RGB pixels, three bytes per pixel row by row, as a PNG file.
+pub fn write_png<W: Write>(mut writer: W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let row_size = width as usize * 3;
+
+    // Every row starts with filter type 0, none
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgb.chunks_exact(row_size.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream of uncompressed deflate blocks
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let length = u16::try_from(block.len()).unwrap_or(u16::MAX);
+        zlib.push(u8::from(blocks.peek().is_none()));
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&be32(adler32(&raw)));
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&be32(width));
+    header.extend_from_slice(&be32(height));
+    header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGB, 0, 0, 0]);
+
+    writer.write_all(SIGNATURE)?;
+    write_chunk(&mut writer, *b"IHDR", &header)?;
+    write_chunk(&mut writer, *b"IDAT", &zlib)?;
+    write_chunk(&mut writer, *b"IEND", &[])?;
+    writer.flush()
+}
@@ -0,0 +1,406 @@
+// Deterministic input recording and replay
+
+use core::{error::Error, fmt};
+
+use chrono::NaiveDateTime;
+
+use crate::{ClockTime, Key, Pc1500, TICKS_PER_FRAME, clock::ClockSource};
+
+const HEADER: &str = "PC1500 RECORDING 1";
+const RTC_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub tick: usize,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+/// The host setting the real-time clock with [`Pc1500::set_clock_time`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockEvent {
+    pub tick: usize,
+    pub time: ClockTime,
+}
+
+/// Every key press and release of a session, keyed on the CPU tick it
+/// happened at, plus the real-time clock value the machine was started with
+/// and every time the host set the clock.
+///
+/// Only recordings started at power-on, tick 0, can be replayed. Later ones
+/// still make macros, see [`crate::macros::KeyMacro::from_recording`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recording {
+    rtc_start: NaiveDateTime,
+    clock_running: bool,
+    start_tick: usize,
+    end_tick: usize,
+    events: Vec<InputEvent>,
+    clock_events: Vec<ClockEvent>,
+}
+
+impl Recording {
+    #[must_use]
+    pub const fn new(rtc_start: NaiveDateTime) -> Self {
+        Self::with_clock(ClockSource::Fixed(rtc_start), rtc_start)
+    }
+
+    /// Creates a recording of a machine driven by `source`, whose clock
+    /// started at `rtc_start`.
+    #[must_use]
+    pub const fn with_clock(source: ClockSource, rtc_start: NaiveDateTime) -> Self {
+        Self {
+            rtc_start,
+            clock_running: source.is_running(),
+            start_tick: 0,
+            end_tick: 0,
+            events: Vec::new(),
+            clock_events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        self.end_tick = self.end_tick.max(event.tick);
+        self.events.push(event);
+    }
+
+    pub fn push_clock(&mut self, event: ClockEvent) {
+        self.end_tick = self.end_tick.max(event.tick);
+        self.clock_events.push(event);
+    }
+
+    /// Sets the tick recording started at, 0 for power-on.
+    pub const fn set_start_tick(&mut self, start_tick: usize) {
+        self.start_tick = start_tick;
+        if self.end_tick < start_tick {
+            self.end_tick = start_tick;
+        }
+    }
+
+    pub const fn set_end_tick(&mut self, end_tick: usize) {
+        self.end_tick = end_tick;
+    }
+
+    #[must_use]
+    pub const fn rtc_start(&self) -> NaiveDateTime {
+        self.rtc_start
+    }
+
+    /// The clock source to replay with. Host sources are replayed from the
+    /// time they were read at.
+    #[must_use]
+    pub const fn clock_source(&self) -> ClockSource {
+        if self.clock_running {
+            ClockSource::Fixed(self.rtc_start)
+        } else {
+            ClockSource::Manual(self.rtc_start)
+        }
+    }
+
+    #[must_use]
+    pub const fn start_tick(&self) -> usize {
+        self.start_tick
+    }
+
+    #[must_use]
+    pub const fn end_tick(&self) -> usize {
+        self.end_tick
+    }
+
+    #[must_use]
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    #[must_use]
+    pub fn clock_events(&self) -> &[ClockEvent] {
+        &self.clock_events
+    }
+
+    /// Serializes the recording to its line based text format, see
+    /// [`Recording::from_text`].
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Parses the line based text format:
+    ///
+    /// ```text
+    /// PC1500 RECORDING 1
+    /// RTC 2024-01-01T12:00:00
+    /// CLOCK MANUAL
+    /// START 0
+    /// END 1500000
+    /// 45000 PRESS MODE
+    /// 60000 CLOCK 03-14 3 09:30:00
+    /// 90000 RELEASE MODE
+    /// ```
+    ///
+    /// The CLOCK line is either RUNNING or MANUAL.
+    pub fn from_text(text: &str) -> Result<Self, ParseRecordingError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((line, _)) => return Err(ParseRecordingError::new(line, "missing header")),
+            None => return Err(ParseRecordingError::new(1, "empty recording")),
+        }
+
+        let (rtc_line, rtc) = lines
+            .next()
+            .ok_or_else(|| ParseRecordingError::new(2, "missing RTC line"))?;
+        let rtc_start = rtc
+            .strip_prefix("RTC ")
+            .and_then(|rtc| NaiveDateTime::parse_from_str(rtc, RTC_FORMAT).ok())
+            .ok_or_else(|| ParseRecordingError::new(rtc_line, "invalid RTC line"))?;
+
+        let (clock_line, clock) = lines
+            .next()
+            .ok_or_else(|| ParseRecordingError::new(3, "missing CLOCK line"))?;
+        let source = match clock {
+            "CLOCK RUNNING" => ClockSource::Fixed(rtc_start),
+            "CLOCK MANUAL" => ClockSource::Manual(rtc_start),
+            _ => return Err(ParseRecordingError::new(clock_line, "invalid CLOCK line")),
+        };
+
+        let (start_line, start) = lines
+            .next()
+            .ok_or_else(|| ParseRecordingError::new(4, "missing START line"))?;
+        let start_tick = start
+            .strip_prefix("START ")
+            .and_then(|start| start.parse().ok())
+            .ok_or_else(|| ParseRecordingError::new(start_line, "invalid START line"))?;
+
+        let (end_line, end) = lines
+            .next()
+            .ok_or_else(|| ParseRecordingError::new(5, "missing END line"))?;
+        let end_tick = end
+            .strip_prefix("END ")
+            .and_then(|end| end.parse().ok())
+            .ok_or_else(|| ParseRecordingError::new(end_line, "invalid END line"))?;
+
+        let mut recording = Self::with_clock(source, rtc_start);
+        recording.set_start_tick(start_tick);
+        for (event_line, event) in lines {
+            let mut fields = event.split_whitespace();
+            let tick = fields
+                .next()
+                .and_then(|tick| tick.parse().ok())
+                .ok_or_else(|| ParseRecordingError::new(event_line, "invalid tick"))?;
+            let pressed = match fields.next() {
+                Some("PRESS") => true,
+                Some("RELEASE") => false,
+                Some("CLOCK") => {
+                    let time = parse_clock_time(fields).ok_or_else(|| {
+                        ParseRecordingError::new(event_line, "invalid clock time")
+                    })?;
+                    recording.push_clock(ClockEvent { tick, time });
+                    continue;
+                }
+                _ => return Err(ParseRecordingError::new(event_line, "invalid action")),
+            };
+            let key = fields
+                .next()
+                .and_then(|key| key.parse().ok())
+                .ok_or_else(|| ParseRecordingError::new(event_line, "invalid key"))?;
+            recording.push(InputEvent { tick, key, pressed });
+        }
+        recording.set_end_tick(end_tick);
+
+        Ok(recording)
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "RTC {}", self.rtc_start.format(RTC_FORMAT))?;
+        let state = if self.clock_running {
+            "RUNNING"
+        } else {
+            "MANUAL"
+        };
+        writeln!(f, "CLOCK {state}")?;
+        writeln!(f, "START {}", self.start_tick)?;
+        writeln!(f, "END {}", self.end_tick)?;
+
+        // Merged in tick order, key events first on the same tick
+        let mut clock_events = self.clock_events.iter().peekable();
+        for event in &self.events {
+            while let Some(change) = clock_events.next_if(|change| change.tick < event.tick) {
+                write_clock_event(f, change)?;
+            }
+            let action = if event.pressed { "PRESS" } else { "RELEASE" };
+            writeln!(f, "{} {action} {}", event.tick, event.key)?;
+        }
+        for change in clock_events {
+            write_clock_event(f, change)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_clock_event(f: &mut fmt::Formatter<'_>, event: &ClockEvent) -> fmt::Result {
+    let time = event.time;
+    writeln!(
+        f,
+        "{} CLOCK {:02}-{:02} {} {:02}:{:02}:{:02}",
+        event.tick, time.month, time.day, time.weekday, time.hour, time.minute, time.second
+    )
+}
+
+/// Parses "MM-DD W HH:MM:SS".
+fn parse_clock_time<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<ClockTime> {
+    let (month, day) = fields.next()?.split_once('-')?;
+    let weekday = fields.next()?;
+    let mut time = fields.next()?.split(':');
+    let clock_time = ClockTime {
+        month: month.parse().ok()?,
+        day: day.parse().ok()?,
+        weekday: weekday.parse().ok()?,
+        hour: time.next()?.parse().ok()?,
+        minute: time.next()?.parse().ok()?,
+        second: time.next()?.parse().ok()?,
+    };
+    (time.next().is_none() && fields.next().is_none()).then_some(clock_time)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseRecordingError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl ParseRecordingError {
+    const fn new(line: usize, reason: &'static str) -> Self {
+        Self { line, reason }
+    }
+
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseRecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for ParseRecordingError {}
+
+/// A recording that did not start at power-on cannot be replayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MidSessionRecording {
+    start_tick: usize,
+}
+
+impl MidSessionRecording {
+    #[must_use]
+    pub const fn start_tick(&self) -> usize {
+        self.start_tick
+    }
+}
+
+impl fmt::Display for MidSessionRecording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "recording started at tick {} instead of power-on",
+            self.start_tick
+        )
+    }
+}
+
+impl Error for MidSessionRecording {}
+
+/// Feeds a [`Recording`] back into a machine, applying each event at the
+/// exact CPU tick it was recorded at.
+pub struct Replay {
+    recording: Recording,
+    next_event: usize,
+    next_clock_event: usize,
+}
+
+impl Replay {
+    /// Prepares `recording` for replay. Recordings started after power-on
+    /// are refused, the state of the machine they started from is unknown.
+    pub fn new(recording: Recording) -> Result<Self, MidSessionRecording> {
+        if recording.start_tick != 0 {
+            return Err(MidSessionRecording {
+                start_tick: recording.start_tick,
+            });
+        }
+
+        Ok(Self {
+            recording,
+            next_event: 0,
+            next_clock_event: 0,
+        })
+    }
+
+    /// Creates a machine in the same power-on state as the recorded one,
+    /// with the real-time clock started at the recorded time and running
+    /// or held as it was.
+    #[must_use]
+    pub fn machine(&self) -> Pc1500 {
+        Pc1500::with_clock(self.recording.clock_source())
+    }
+
+    #[must_use]
+    pub fn is_finished(&self, pc1500: &Pc1500) -> bool {
+        self.next_event == self.recording.events.len()
+            && self.next_clock_event == self.recording.clock_events.len()
+            && pc1500.lh5801.get_ticks() >= self.recording.end_tick
+    }
+
+    pub fn step_frame(&mut self, pc1500: &mut Pc1500) {
+        let end = pc1500.lh5801.get_ticks() + TICKS_PER_FRAME;
+        self.run_until(pc1500, end);
+    }
+
+    /// Runs the machine up to the tick the recording was stopped at.
+    pub fn run_to_end(&mut self, pc1500: &mut Pc1500) {
+        self.run_until(pc1500, self.recording.end_tick);
+    }
+
+    fn run_until(&mut self, pc1500: &mut Pc1500, tick: usize) {
+        while pc1500.lh5801.get_ticks() < tick {
+            self.apply_events(pc1500);
+            pc1500.run();
+        }
+        self.apply_events(pc1500);
+    }
+
+    fn apply_events(&mut self, pc1500: &mut Pc1500) {
+        let ticks = pc1500.lh5801.get_ticks();
+
+        while let Some(event) = self.recording.events.get(self.next_event) {
+            if event.tick > ticks {
+                break;
+            }
+
+            if event.pressed {
+                pc1500.keyboard.press(event.key);
+            } else {
+                pc1500.keyboard.release(event.key);
+            }
+            self.next_event += 1;
+        }
+
+        while let Some(event) = self.recording.clock_events.get(self.next_clock_event) {
+            if event.tick > ticks {
+                break;
+            }
+
+            pc1500.set_clock_time(event.time);
+            self.next_clock_event += 1;
+        }
+    }
+}
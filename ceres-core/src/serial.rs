@@ -0,0 +1,289 @@
+// Host ends of the CE-158 serial line
+
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::fs::OpenOptionsExt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// How often the device reader looks for input and for being stopped
+#[cfg(unix)]
+const DEVICE_POLL: Duration = Duration::from_millis(1);
+
+/// A host connection for the CE-158 RS-232 port. Receiving must never
+/// block, the emulator polls it while running.
+pub trait SerialLink {
+    fn send(&mut self, byte: u8) -> io::Result<()>;
+
+    /// Returns the next byte sent by the host, if one has arrived.
+    fn receive(&mut self) -> io::Result<Option<u8>>;
+}
+
+/// Appends transmitted bytes to a file and optionally receives the bytes
+/// of another file, e.g. a program listing to `INPUT#`.
+pub struct FileLink {
+    output: File,
+    input: Option<BufReader<File>>,
+}
+
+impl FileLink {
+    pub fn open<P: AsRef<Path>>(output: P) -> io::Result<Self> {
+        Ok(Self {
+            output: append(output)?,
+            input: None,
+        })
+    }
+
+    /// Receives the contents of `input` once, then nothing.
+    pub fn with_input<P: AsRef<Path>, Q: AsRef<Path>>(output: P, input: Q) -> io::Result<Self> {
+        Ok(Self {
+            output: append(output)?,
+            input: Some(BufReader::new(File::open(input)?)),
+        })
+    }
+}
+
+impl SerialLink for FileLink {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        self.output.write_all(&[byte])
+    }
+
+    fn receive(&mut self) -> io::Result<Option<u8>> {
+        let Some(input) = &mut self.input else {
+            return Ok(None);
+        };
+        let mut byte = [0];
+        match input.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+}
+
+/// A local TCP socket, either connecting to a listening host program or
+/// waiting for one to connect.
+///
+/// Bytes the socket cannot take yet are buffered and sent as the host
+/// catches up, so a slow host never stalls the emulator.
+pub struct TcpLink {
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+    pending: VecDeque<u8>,
+}
+
+impl TcpLink {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            listener: None,
+            stream: Some(stream),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Listens on `addr`, accepting the first connection while running.
+    /// Bytes sent before a host connects are dropped, as on an unplugged
+    /// cable.
+    pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            stream: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match (&self.listener, &self.stream) {
+            (Some(listener), _) => listener.local_addr(),
+            (None, Some(stream)) => stream.local_addr(),
+            (None, None) => Err(ErrorKind::NotConnected.into()),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn accept(&mut self) -> io::Result<()> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        let Some(listener) = &self.listener else {
+            return Ok(());
+        };
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                self.stream = Some(stream);
+                Ok(())
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes as much of the pending bytes as the socket takes without
+    /// blocking.
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+        while !self.pending.is_empty() {
+            let (front, _) = self.pending.as_slices();
+            match stream.write(front) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of sent bytes still waiting for the host to take them
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        self.accept()?;
+        if self.stream.is_none() {
+            return Ok(());
+        }
+        self.pending.push_back(byte);
+        self.flush_pending()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<u8>> {
+        self.accept()?;
+        self.flush_pending()?;
+        let Some(stream) = &mut self.stream else {
+            return Ok(None);
+        };
+        let mut byte = [0];
+        match stream.read(&mut byte) {
+            Ok(0) => {
+                // The host hung up, wait for the next one
+                self.stream = None;
+                self.pending.clear();
+                Ok(None)
+            }
+            Ok(_) => Ok(Some(byte[0])),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A host terminal device, such as a real serial port or one end of a
+/// pseudo-terminal pair.
+///
+/// `socat -d -d pty,raw,echo=0 pty,raw,echo=0` creates such a pair. The
+/// line settings of the device are left to the host.
+///
+/// Input is read on a thread of its own, stopped and joined when the link
+/// is dropped.
+#[cfg(unix)]
+pub struct DeviceLink {
+    output: File,
+    input: Receiver<io::Result<u8>>,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl DeviceLink {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let output = OpenOptions::new().write(true).open(&path)?;
+        // Opened without blocking so the reader can notice being stopped
+        let mut device = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)?;
+
+        let (sender, input) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let reader = thread::spawn(move || {
+            let mut byte = [0];
+            while !stopped.load(Ordering::Relaxed) {
+                match device.read(&mut byte) {
+                    Ok(0) => thread::sleep(DEVICE_POLL),
+                    Ok(_) => {
+                        if sender.send(Ok(byte[0])).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err)
+                        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) =>
+                    {
+                        thread::sleep(DEVICE_POLL);
+                    }
+                    Err(err) => {
+                        drop(sender.send(Err(err)));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            output,
+            input,
+            stop,
+            reader: Some(reader),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for DeviceLink {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            drop(reader.join());
+        }
+    }
+}
+
+#[cfg(unix)]
+impl SerialLink for DeviceLink {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        self.output.write_all(&[byte])
+    }
+
+    fn receive(&mut self) -> io::Result<Option<u8>> {
+        match self.input.try_recv() {
+            Ok(byte) => byte.map(Some),
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => Ok(None),
+        }
+    }
+}
+
+pub(crate) fn append<P: AsRef<Path>>(path: P) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
@@ -0,0 +1,345 @@
+// Cassette tape deck attached to the CE-150 tape lines
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use crate::{Pc1500, pd1990ac::FREQUENCY, tape_image::TapeImage, wav};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+pub(crate) const AMPLITUDE: i16 = i16::MAX / 2;
+
+// Demodulator tuning
+const SILENCE_LEVEL: i64 = 256;
+const HYSTERESIS_PERCENT: i64 = 30;
+const GAP_FACTOR: u64 = 4;
+const DRIFT_SMOOTHING: u64 = 16;
+const FIXED_ONE: u64 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TapeState {
+    Stopped,
+    Recording,
+    Playing,
+}
+
+/// A cassette holding mono PCM audio, recorded by sampling the tape output
+/// line at a fixed host sample rate and played back into the tape input line.
+///
+/// The tape lines belong to the CE-150, without one attached the deck
+/// records silence and plays into nothing.
+#[derive(Debug)]
+pub struct TapeDeck {
+    state: TapeState,
+    sample_rate: u32,
+    samples: Vec<i16>,
+    position: usize,
+    // CPU tick of the next sample, in units of 1 / sample_rate ticks
+    next_sample: u64,
+    input: bool,
+}
+
+impl TapeDeck {
+    #[must_use]
+    pub const fn new(sample_rate: u32) -> Self {
+        Self {
+            state: TapeState::Stopped,
+            sample_rate,
+            samples: Vec::new(),
+            position: 0,
+            next_sample: 0,
+            input: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn state(&self) -> TapeState {
+        self.state
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[must_use]
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Starts recording at the current position, overwriting whatever was
+    /// on the tape there.
+    pub const fn record(&mut self) {
+        self.state = TapeState::Recording;
+    }
+
+    /// Starts playing from the current position into the tape input line.
+    pub const fn play(&mut self) {
+        self.state = TapeState::Playing;
+    }
+
+    pub const fn stop(&mut self) {
+        self.state = TapeState::Stopped;
+    }
+
+    pub const fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    /// Removes everything on the tape.
+    pub fn erase(&mut self) {
+        self.samples.clear();
+        self.position = 0;
+    }
+
+    pub fn write_wav<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        wav::write_wav(writer, self.sample_rate, &self.samples)
+    }
+
+    pub fn save_wav<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_wav(BufWriter::new(File::create(path)?))
+    }
+
+    /// Level of the tape input line while playing.
+    #[must_use]
+    pub const fn input(&self) -> bool {
+        self.input
+    }
+
+    /// Replaces the tape contents with a WAV recording. The audio is
+    /// demodulated into a clean square wave, see [`demodulate`].
+    pub fn load_wav<R: io::Read>(&mut self, reader: R) -> io::Result<()> {
+        let (sample_rate, samples) = wav::read_wav(reader)?;
+        self.sample_rate = sample_rate;
+        self.samples = demodulate(&samples);
+        self.position = 0;
+        self.state = TapeState::Stopped;
+        Ok(())
+    }
+
+    pub fn open_wav<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.load_wav(BufReader::new(File::open(path)?))
+    }
+
+    /// Replaces the tape contents with the files of a tape image.
+    pub fn load_image(&mut self, image: &TapeImage) {
+        self.samples = image.to_samples(self.sample_rate);
+        self.position = 0;
+        self.state = TapeState::Stopped;
+    }
+
+    /// Replaces the tape contents with either a tape image or a WAV
+    /// recording.
+    pub fn load<R: io::Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if wav::is_wav(&bytes) {
+            self.load_wav(bytes.as_slice())
+        } else {
+            self.load_image(&TapeImage::from_bytes(&bytes)?);
+            Ok(())
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Decodes the files recorded on the tape.
+    #[must_use]
+    pub fn to_image(&self) -> TapeImage {
+        TapeImage::from_samples(&self.samples)
+    }
+
+    fn step(&mut self, ticks: usize, output: Option<bool>) {
+        let ticks = ticks as u64 * u64::from(self.sample_rate);
+
+        if self.state == TapeState::Stopped {
+            self.next_sample = ticks;
+            return;
+        }
+
+        let sample = match output {
+            Some(true) => AMPLITUDE,
+            Some(false) => -AMPLITUDE,
+            None => 0,
+        };
+        while self.next_sample <= ticks {
+            match (self.state, self.samples.get_mut(self.position)) {
+                (TapeState::Recording, Some(slot)) => *slot = sample,
+                (TapeState::Recording, None) => self.samples.push(sample),
+                (TapeState::Playing, Some(slot)) => self.input = *slot > 0,
+                _ => {
+                    self.state = TapeState::Stopped;
+                    return;
+                }
+            }
+            self.position += 1;
+            self.next_sample += FREQUENCY as u64;
+        }
+    }
+}
+
+/// Turns recorded tape audio into an ideal square wave the ROM can load.
+///
+/// The signal is DC-corrected and squared with a Schmitt trigger whose
+/// thresholds follow the signal envelope, so quiet or uneven recordings are
+/// handled. Half periods are then sorted into the short and long tones of
+/// the FSK modulation while tracking slow tape speed drift, and regenerated
+/// with constant lengths. Inverted polarity only swaps the levels of each
+/// half period, which the ROM does not distinguish.
+#[must_use]
+pub fn demodulate(samples: &[i16]) -> Vec<i16> {
+    let (first_level, half_periods) = square(samples);
+    let half_periods = remove_drift(&half_periods);
+
+    let mut output = Vec::with_capacity(samples.len());
+    let mut level = first_level;
+    let mut end = 0;
+    for length in half_periods {
+        end += length;
+        let sample = if level { AMPLITUDE } else { -AMPLITUDE };
+        output.resize(((end + FIXED_ONE / 2) / FIXED_ONE) as usize, sample);
+        level = !level;
+    }
+    output
+}
+
+/// Returns the initial level and the length in samples of each half period.
+fn square(samples: &[i16]) -> (bool, Vec<u64>) {
+    // Roughly 5 ms at common sample rates
+    const WINDOW: i64 = 256;
+
+    let mut dc_sum = 0;
+    let mut envelope = 0;
+    let mut level = None;
+    let mut first_level = false;
+    let mut length = 0;
+    let mut half_periods = Vec::new();
+
+    for &sample in samples {
+        let sample = i64::from(sample);
+        dc_sum += sample - dc_sum / WINDOW;
+        let value = sample - dc_sum / WINDOW;
+        envelope = value.abs().max(envelope - envelope / WINDOW);
+        length += 1;
+
+        if envelope < SILENCE_LEVEL {
+            continue;
+        }
+
+        let threshold = envelope * HYSTERESIS_PERCENT / 100;
+        let new_level = match level {
+            _ if value > threshold => true,
+            _ if value < -threshold => false,
+            Some(level) => level,
+            None => continue,
+        };
+
+        match level {
+            None => first_level = new_level,
+            Some(level) if level != new_level => {
+                half_periods.push(length);
+                length = 0;
+            }
+            Some(_) => {}
+        }
+        level = Some(new_level);
+    }
+    half_periods.push(length);
+
+    (first_level, half_periods)
+}
+
+/// Normalizes half period lengths to the average length of their tone, in
+/// `FIXED_ONE` fixed point.
+fn remove_drift(half_periods: &[u64]) -> Vec<u64> {
+    let mut sorted = half_periods.to_vec();
+    sorted.sort_unstable();
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or_default();
+    let tones: Vec<u64> = sorted
+        .into_iter()
+        .filter(|&length| length <= median * GAP_FACTOR)
+        .map(|length| length * FIXED_ONE)
+        .collect();
+
+    let (Some(&min), Some(&max)) = (tones.first(), tones.last()) else {
+        return Vec::new();
+    };
+
+    // Two-means clustering into the short and the long tone
+    let (mut short, mut long) = (min, max);
+    for _ in 0..16 {
+        let split = u64::midpoint(short, long);
+        let (below, above): (Vec<u64>, Vec<u64>) =
+            tones.iter().partition(|&&length| length <= split);
+        short = mean(&below).unwrap_or(short);
+        long = mean(&above).unwrap_or(long);
+    }
+
+    // A single tone, e.g. only the leader
+    if long * 10 < short * 13 {
+        short = mean(&tones).unwrap_or(short);
+        long = short;
+    }
+
+    let (mut drift_short, mut drift_long) = (short, long);
+    half_periods
+        .iter()
+        .map(|&length| {
+            let length = length * FIXED_ONE;
+            if length > median * GAP_FACTOR * FIXED_ONE {
+                return length;
+            }
+
+            if length * 2 <= drift_short + drift_long {
+                drift_short =
+                    drift_short + length / DRIFT_SMOOTHING - drift_short / DRIFT_SMOOTHING;
+                short
+            } else {
+                drift_long = drift_long + length / DRIFT_SMOOTHING - drift_long / DRIFT_SMOOTHING;
+                long
+            }
+        })
+        .collect()
+}
+
+fn mean(values: &[u64]) -> Option<u64> {
+    let count = u64::try_from(values.len())
+        .ok()
+        .filter(|&count| count > 0)?;
+    Some(values.iter().sum::<u64>() / count)
+}
+
+impl Default for TapeDeck {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLE_RATE)
+    }
+}
+
+impl Pc1500 {
+    #[must_use]
+    pub const fn tape(&self) -> &TapeDeck {
+        &self.tape
+    }
+
+    pub const fn tape_mut(&mut self) -> &mut TapeDeck {
+        &mut self.tape
+    }
+
+    pub(crate) fn step_tape(&mut self) {
+        self.tape.step(self.lh5801.get_ticks(), self.tape_output());
+
+        if self.tape.state() == TapeState::Playing {
+            self.set_tape_input(self.tape.input());
+        }
+    }
+}
@@ -0,0 +1,578 @@
+// Digital tape images: the files on a cassette as decoded bytes
+//
+// Files are laid out as the CE-150 records them: a header block and the
+// payload in 80 byte blocks, each followed by its checksum. The bytes go
+// out through the CE-150's LH5810 serial port with its modulator on, so
+// the audio is the FSK its F and G dividers produce.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    mem,
+    path::Path,
+};
+
+use crate::{
+    pd1990ac::FREQUENCY,
+    tape::{self, AMPLITUDE},
+    wav,
+};
+
+pub const NAME_LENGTH: usize = 16;
+/// Payload bytes covered by each checksum
+pub const BLOCK_SIZE: usize = 80;
+/// Entry address of files that are not started after loading
+pub const NO_ENTRY: u16 = 0xFFFF;
+// Zeros between the name and the addresses
+const RESERVED_SIZE: usize = 9;
+const START_AT: usize = 1 + NAME_LENGTH + RESERVED_SIZE;
+const LENGTH_AT: usize = START_AT + 2;
+const ENTRY_AT: usize = LENGTH_AT + 2;
+// Kind, name, reserved bytes, start address, length, entry address
+const HEADER_SIZE: usize = ENTRY_AT + 2;
+const CHECKSUM_SIZE: usize = 2;
+
+// Modulation, in CPU ticks: the LH5810 toggles SDO every 256 ticks for a
+// one bit (F divider 3, 2539 Hz) and every 512 ticks for a zero bit (F
+// divider 4, 1270 Hz), and shifts out a bit every 2048 ticks (G rate 6).
+// Bytes are framed by the LH5810 as a start bit and 8 data bits from the
+// least significant one, the line idles on ones between them.
+const BIT_TICKS: u64 = 2048;
+const SHORT_HALF_PERIOD: u64 = 256;
+const LONG_HALF_PERIOD: u64 = 512;
+// Bit length measured in short half periods
+const BIT_UNITS: u64 = BIT_TICKS / SHORT_HALF_PERIOD;
+const STOP_BITS: usize = 2;
+// About two seconds of the one tone before a file
+const LEADER_BITS: usize = 1270;
+const GAP_BITS: usize = 80;
+const SILENCE_MILLIS: u64 = 1000;
+// Idle time that ends a burst of bytes
+const IDLE_BITS: u64 = 20;
+// Half periods longer than this many short tones are silence
+const SILENCE_UNITS: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Basic,
+    Machine,
+    Data,
+}
+
+impl FileKind {
+    const fn ident(self) -> u8 {
+        match self {
+            Self::Machine => 0xA0,
+            Self::Basic => 0xA1,
+            Self::Data => 0xA4,
+        }
+    }
+
+    const fn from_ident(ident: u8) -> Option<Self> {
+        match ident {
+            0xA0 => Some(Self::Machine),
+            0xA1 => Some(Self::Basic),
+            0xA4 => Some(Self::Data),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Basic => "BASIC",
+            Self::Machine => "ML",
+            Self::Data => "DATA",
+        }
+    }
+}
+
+/// A file as stored on tape. Checksums are kept as read so damaged files
+/// can still be inspected, see [`TapeFile::checksums_ok`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TapeFile {
+    kind: FileKind,
+    name: String,
+    start: u16,
+    entry: u16,
+    data: Vec<u8>,
+    header_checksum: u16,
+    block_checksums: Vec<u16>,
+}
+
+impl TapeFile {
+    /// Creates a file with valid checksums. Names longer than
+    /// [`NAME_LENGTH`] are truncated and characters other than printable
+    /// ASCII are replaced with `?`, as when the name is read back.
+    #[must_use]
+    pub fn new(kind: FileKind, name: &str, start: u16, entry: u16, data: Vec<u8>) -> Self {
+        let mut file = Self {
+            kind,
+            name: name
+                .chars()
+                .take(NAME_LENGTH)
+                .map(|c| u8::try_from(c).map_or('?', name_char))
+                .collect(),
+            start,
+            entry,
+            data,
+            header_checksum: 0,
+            block_checksums: Vec::new(),
+        };
+        file.header_checksum = checksum(&file.header());
+        file.block_checksums = file.data.chunks(BLOCK_SIZE).map(checksum).collect();
+        file
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Address machine code files are called at after loading
+    #[must_use]
+    pub const fn entry(&self) -> u16 {
+        self.entry
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[must_use]
+    pub const fn header_checksum(&self) -> u16 {
+        self.header_checksum
+    }
+
+    #[must_use]
+    pub fn block_checksums(&self) -> &[u16] {
+        &self.block_checksums
+    }
+
+    /// Whether the header and every payload block match their checksums.
+    #[must_use]
+    pub fn checksums_ok(&self) -> bool {
+        self.header_ok() && self.blocks().all(|(_, ok)| ok)
+    }
+
+    /// Whether the header matches its checksum.
+    #[must_use]
+    pub fn header_ok(&self) -> bool {
+        checksum(&self.header()) == self.header_checksum
+    }
+
+    /// The payload blocks, each with whether it matches its checksum.
+    pub fn blocks(&self) -> impl Iterator<Item = (&[u8], bool)> {
+        self.data
+            .chunks(BLOCK_SIZE)
+            .zip(&self.block_checksums)
+            .map(|(block, &sum)| (block, checksum(block) == sum))
+    }
+
+    fn header(&self) -> [u8; HEADER_SIZE] {
+        let mut header = [0; HEADER_SIZE];
+        header[0] = self.kind.ident();
+        // The name only holds printable ASCII, see `new`
+        for (slot, byte) in header[1..=NAME_LENGTH].iter_mut().zip(self.name.bytes()) {
+            *slot = byte;
+        }
+
+        let length = u16::try_from(self.data.len()).unwrap_or(u16::MAX);
+        let fields = [self.start, length, self.entry];
+        for (bytes, field) in header[START_AT..].chunks_exact_mut(2).zip(fields) {
+            bytes.copy_from_slice(&word_bytes(field));
+        }
+        header
+    }
+
+    /// The header with its checksum, as sent in the first burst.
+    fn header_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header().to_vec();
+        bytes.extend_from_slice(&word_bytes(self.header_checksum));
+        bytes
+    }
+
+    /// The payload blocks each followed by its checksum, as sent in the
+    /// second burst.
+    fn payload_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (block, sum) in self.data.chunks(BLOCK_SIZE).zip(&self.block_checksums) {
+            bytes.extend_from_slice(block);
+            bytes.extend_from_slice(&word_bytes(*sum));
+        }
+        bytes
+    }
+
+    /// Reads a file from its header and payload bursts, advancing `bytes`.
+    fn read(bytes: &mut &[u8]) -> io::Result<Self> {
+        let header = take(bytes, HEADER_SIZE)?;
+        let header_checksum = read_u16(bytes)?;
+
+        let kind = header
+            .first()
+            .copied()
+            .and_then(FileKind::from_ident)
+            .ok_or_else(|| invalid_data("unknown file kind"))?;
+        let name = header[1..=NAME_LENGTH]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| name_char(b))
+            .collect();
+        let field = |at: usize| word(header[at], header[at + 1]);
+        let start = field(START_AT);
+        let length = usize::from(field(LENGTH_AT));
+        let entry = field(ENTRY_AT);
+
+        let mut data = Vec::with_capacity(length);
+        let mut block_checksums = Vec::new();
+        while data.len() < length {
+            let block = take(bytes, BLOCK_SIZE.min(length - data.len()))?;
+            data.extend_from_slice(block);
+            block_checksums.push(read_u16(bytes)?);
+        }
+
+        Ok(Self {
+            kind,
+            name,
+            start,
+            entry,
+            data,
+            header_checksum,
+            block_checksums,
+        })
+    }
+}
+
+/// A file name character: printable ASCII as is, anything else as `?`.
+fn name_char(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        char::from(byte)
+    } else {
+        '?'
+    }
+}
+
+/// Sum of the bytes, as appended to the header and to every payload block.
+fn checksum(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .fold(0_u16, |sum, &b| sum.wrapping_add(u16::from(b)))
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], count: usize) -> io::Result<&'a [u8]> {
+    let (head, tail) = bytes
+        .split_at_checked(count)
+        .ok_or_else(|| invalid_data("truncated tape file"))?;
+    *bytes = tail;
+    Ok(head)
+}
+
+fn read_u16(bytes: &mut &[u8]) -> io::Result<u16> {
+    let field = take(bytes, 2)?;
+    Ok(word(field[0], field[1]))
+}
+
+// Multi-byte fields are stored high byte first, like LH5801 addresses
+const fn word(high: u8, low: u8) -> u16 {
+    u16::from_le_bytes([low, high])
+}
+
+const fn word_bytes(value: u16) -> [u8; 2] {
+    let [low, high] = value.to_le_bytes();
+    [high, low]
+}
+
+/// The files on a cassette.
+///
+/// Images hold every file in the byte layout it has on tape, as
+/// [`TapeImage::to_samples`] modulates it:
+///
+/// | Bytes | Content                                                  |
+/// |-------|----------------------------------------------------------|
+/// | 1     | Kind: `A0` machine code, `A1` BASIC, `A4` data           |
+/// | 16    | Name, padded with zeros                                  |
+/// | 9     | Reserved, zero                                           |
+/// | 2     | Start address                                            |
+/// | 2     | Payload length                                           |
+/// | 2     | Entry address, [`NO_ENTRY`] for files that are not run   |
+/// | 2     | Header checksum                                          |
+/// | ...   | Payload in blocks of up to 80 bytes, each followed by its checksum |
+///
+/// Multi-byte fields are big endian and checksums are the 16-bit sum of the
+/// bytes they cover.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TapeImage {
+    files: Vec<TapeFile>,
+}
+
+impl TapeImage {
+    #[must_use]
+    pub const fn new(files: Vec<TapeFile>) -> Self {
+        Self { files }
+    }
+
+    #[must_use]
+    pub fn files(&self) -> &[TapeFile] {
+        &self.files
+    }
+
+    pub fn push(&mut self, file: TapeFile) {
+        self.files.push(file);
+    }
+
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for file in &self.files {
+            bytes.extend(file.header_bytes());
+            bytes.extend(file.payload_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut rest = bytes;
+        let mut files = Vec::new();
+        while !rest.is_empty() {
+            files.push(TapeFile::read(&mut rest)?);
+        }
+        Ok(Self { files })
+    }
+
+    /// Reads either a tape image or a WAV recording.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if wav::is_wav(&bytes) {
+            let (_, samples) = wav::read_wav(bytes.as_slice())?;
+            Ok(Self::from_samples(&samples))
+        } else {
+            Self::from_bytes(&bytes)
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Decodes the files recorded on tape audio at any sample rate. Files
+    /// with unreadable headers or cut short are skipped.
+    #[must_use]
+    pub fn from_samples(samples: &[i16]) -> Self {
+        let mut bursts = decode_bursts(&tape::demodulate(samples)).into_iter();
+        let mut files = Vec::new();
+
+        while let Some(header) = bursts.next() {
+            if header.len() != HEADER_SIZE + CHECKSUM_SIZE
+                || FileKind::from_ident(header[0]).is_none()
+            {
+                continue;
+            }
+
+            let mut bytes = header;
+            if word(bytes[LENGTH_AT], bytes[LENGTH_AT + 1]) > 0 {
+                bytes.extend(bursts.next().unwrap_or_default());
+            }
+
+            if let Ok(file) = TapeFile::read(&mut bytes.as_slice()) {
+                files.push(file);
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Modulates the files into tape audio, each preceded by a leader tone
+    /// and separated by silence.
+    #[must_use]
+    pub fn to_samples(&self, sample_rate: u32) -> Vec<i16> {
+        let mut modulator = Modulator::new(sample_rate);
+        for file in &self.files {
+            modulator.silence(SILENCE_MILLIS);
+            modulator.idle(LEADER_BITS);
+            modulator.bytes(&file.header_bytes());
+            if !file.data.is_empty() {
+                modulator.idle(GAP_BITS);
+                modulator.bytes(&file.payload_bytes());
+            }
+            modulator.idle(GAP_BITS);
+        }
+        modulator.silence(SILENCE_MILLIS);
+        modulator.samples
+    }
+
+    pub fn write_wav<W: Write>(&self, writer: W, sample_rate: u32) -> io::Result<()> {
+        wav::write_wav(writer, sample_rate, &self.to_samples(sample_rate))
+    }
+
+    pub fn save_wav<P: AsRef<Path>>(&self, path: P, sample_rate: u32) -> io::Result<()> {
+        self.write_wav(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+struct Modulator {
+    sample_rate: u64,
+    samples: Vec<i16>,
+    // Elapsed CPU ticks
+    ticks: u64,
+    level: bool,
+}
+
+impl Modulator {
+    const fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as u64,
+            samples: Vec::new(),
+            ticks: 0,
+            level: true,
+        }
+    }
+
+    fn advance(&mut self, ticks: u64, sample: i16) {
+        self.ticks += ticks;
+        let end = self.ticks * self.sample_rate / FREQUENCY as u64;
+        self.samples
+            .resize(usize::try_from(end).unwrap_or(usize::MAX), sample);
+    }
+
+    fn silence(&mut self, millis: u64) {
+        self.advance(millis * FREQUENCY as u64 / 1000, 0);
+    }
+
+    fn bit(&mut self, bit: bool) {
+        let half_period = if bit {
+            SHORT_HALF_PERIOD
+        } else {
+            LONG_HALF_PERIOD
+        };
+        for _ in 0..BIT_TICKS / half_period {
+            let sample = if self.level { AMPLITUDE } else { -AMPLITUDE };
+            self.advance(half_period, sample);
+            self.level = !self.level;
+        }
+    }
+
+    fn idle(&mut self, bits: usize) {
+        for _ in 0..bits {
+            self.bit(true);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.bit(false);
+            for n in 0..8 {
+                self.bit(byte & (1 << n) != 0);
+            }
+            self.idle(STOP_BITS);
+        }
+    }
+}
+
+/// Returns the bursts of bytes on a demodulated tape. Bursts end where the
+/// tape is silent or idles for a while.
+fn decode_bursts(square: &[i16]) -> Vec<Vec<u8>> {
+    let mut half_periods = Vec::new();
+    let mut length = 0_u64;
+    let mut level = square.first().is_some_and(|&sample| sample > 0);
+    for &sample in square {
+        if (sample > 0) != level {
+            half_periods.push(length);
+            length = 0;
+            level = !level;
+        }
+        length += 1;
+    }
+    half_periods.push(length);
+
+    // Roughly the short tone, which idle time and stop bits are made of
+    let mut sorted = half_periods.clone();
+    sorted.sort_unstable();
+    let short = sorted
+        .get(sorted.len() / 10)
+        .copied()
+        .unwrap_or_default()
+        .max(1);
+
+    let mut bursts = Vec::new();
+    // Start of each half period in short tones and whether it is long
+    let mut tones = Vec::new();
+    let mut units = 0;
+    for half_period in half_periods {
+        if half_period > short * SILENCE_UNITS {
+            decode_bytes(&mem::take(&mut tones), &mut bursts);
+            units = 0;
+            continue;
+        }
+
+        let long = half_period * 2 > short * 3;
+        tones.push((units, long));
+        units += if long { 2 } else { 1 };
+    }
+    decode_bytes(&tones, &mut bursts);
+
+    bursts
+}
+
+fn decode_bytes(tones: &[(u64, bool)], bursts: &mut Vec<Vec<u8>>) {
+    let long_at = |units: u64| {
+        let index = tones.partition_point(|&(start, _)| start <= units);
+        tones
+            .get(index.saturating_sub(1))
+            .is_some_and(|&(_, long)| long)
+    };
+
+    let mut burst = Vec::new();
+    let mut burst_end = 0;
+    let mut from = 0;
+    while let Some(&(start, _)) = tones
+        .get(from..)
+        .and_then(|rest| rest.iter().find(|&&(_, long)| long))
+    {
+        // Sample every bit in its middle
+        let bit = |n: u64| !long_at(start + n * BIT_UNITS + BIT_UNITS / 2);
+        let next = start + 9 * BIT_UNITS + BIT_UNITS / 2;
+        from = tones.partition_point(|&(tone_start, _)| tone_start < next);
+
+        if bit(0) {
+            // Too short for a start bit
+            from = tones.partition_point(|&(tone_start, _)| tone_start <= start);
+            continue;
+        }
+
+        if !burst.is_empty() && start - burst_end > IDLE_BITS * BIT_UNITS {
+            bursts.push(mem::take(&mut burst));
+        }
+        burst.push((0..8).fold(0, |byte, n| byte | (u8::from(bit(n + 1)) << n)));
+        burst_end = next;
+    }
+
+    if !burst.is_empty() {
+        bursts.push(burst);
+    }
+}
@@ -0,0 +1,204 @@
+// Typed text input, fed to the keyboard matrix in step with the ROM scan
+
+use core::{error::Error, fmt};
+use std::collections::VecDeque;
+
+use crate::{Key, Pc1500};
+
+// ROM entry points
+pub const WAIT_4_KB: u16 = 0xE243;
+pub const ISKEY: u16 = 0xE418;
+
+/// Key strokes producing each character. SHIFT on the PC-1500 is a latching
+/// prefix key, so shifted characters are two separate strokes.
+const CHAR_KEYS: &[(char, &[Key])] = &[
+    ('A', &[Key::A]),
+    ('B', &[Key::B]),
+    ('C', &[Key::C]),
+    ('D', &[Key::D]),
+    ('E', &[Key::E]),
+    ('F', &[Key::F]),
+    ('G', &[Key::G]),
+    ('H', &[Key::H]),
+    ('I', &[Key::I]),
+    ('J', &[Key::J]),
+    ('K', &[Key::K]),
+    ('L', &[Key::L]),
+    ('M', &[Key::M]),
+    ('N', &[Key::N]),
+    ('O', &[Key::O]),
+    ('P', &[Key::P]),
+    ('Q', &[Key::Q]),
+    ('R', &[Key::R]),
+    ('S', &[Key::S]),
+    ('T', &[Key::T]),
+    ('U', &[Key::U]),
+    ('V', &[Key::V]),
+    ('W', &[Key::W]),
+    ('X', &[Key::X]),
+    ('Y', &[Key::Y]),
+    ('Z', &[Key::Z]),
+    ('0', &[Key::Zero]),
+    ('1', &[Key::One]),
+    ('2', &[Key::Two]),
+    ('3', &[Key::Three]),
+    ('4', &[Key::Four]),
+    ('5', &[Key::Five]),
+    ('6', &[Key::Six]),
+    ('7', &[Key::Seven]),
+    ('8', &[Key::Eight]),
+    ('9', &[Key::Nine]),
+    (' ', &[Key::Space]),
+    ('\n', &[Key::Enter]),
+    ('.', &[Key::Dot]),
+    ('=', &[Key::Equals]),
+    ('+', &[Key::Plus]),
+    ('-', &[Key::Minus]),
+    ('*', &[Key::Asterisk]),
+    ('/', &[Key::Slash]),
+    ('(', &[Key::LeftParen]),
+    (')', &[Key::RightParen]),
+    ('!', &[Key::Shift, Key::F1]),
+    ('"', &[Key::Shift, Key::F2]),
+    ('#', &[Key::Shift, Key::F3]),
+    ('$', &[Key::Shift, Key::F4]),
+    ('%', &[Key::Shift, Key::F5]),
+    ('&', &[Key::Shift, Key::F6]),
+    ('?', &[Key::Shift, Key::H]),
+    (':', &[Key::Shift, Key::J]),
+    (';', &[Key::Shift, Key::K]),
+    (',', &[Key::Shift, Key::L]),
+    ('<', &[Key::Shift, Key::N]),
+    ('>', &[Key::Shift, Key::M]),
+    ('@', &[Key::Shift, Key::P]),
+    ('^', &[Key::Shift, Key::Asterisk]),
+];
+
+/// Returns the key strokes that enter `c`, if it can be typed at all.
+/// Lowercase letters are entered as their uppercase counterparts.
+#[must_use]
+pub fn keys_for_char(c: char) -> Option<&'static [Key]> {
+    let c = c.to_ascii_uppercase();
+    CHAR_KEYS
+        .iter()
+        .find(|(candidate, _)| *candidate == c)
+        .map(|(_, keys)| *keys)
+}
+
+/// Whether `key` is part of typing some character. Other keys, such as
+/// BREAK, are read by the ROM outside its key wait and must be pressed
+/// directly.
+#[must_use]
+pub fn is_text_key(key: Key) -> bool {
+    CHAR_KEYS.iter().any(|(_, keys)| keys.contains(&key))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedChar(pub char);
+
+impl fmt::Display for UnsupportedChar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "character '{}' cannot be typed on the PC-1500",
+            self.0.escape_default()
+        )
+    }
+}
+
+impl Error for UnsupportedChar {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Stroke {
+    #[default]
+    Idle,
+    // Key is down, waiting for the ROM to go back to WAIT_4_KB
+    Held(Key),
+    // Key is up, waiting for the ROM to scan the released matrix
+    Released,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TypeQueue {
+    keys: VecDeque<Key>,
+    stroke: Stroke,
+    // Last tick the ROM waited for a key with nothing left to type
+    idle_tick: Option<usize>,
+}
+
+impl TypeQueue {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            keys: VecDeque::new(),
+            stroke: Stroke::Idle,
+            idle_tick: None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.stroke == Stroke::Idle
+    }
+}
+
+impl Pc1500 {
+    /// Queues `text` to be typed on the keyboard. Each key is pressed when the
+    /// ROM waits for input and released once the ROM has consumed it, so text
+    /// of any length is entered without dropped or repeated keys.
+    pub fn type_text(&mut self, text: &str) -> Result<(), UnsupportedChar> {
+        let mut keys = Vec::new();
+        for c in text.chars().filter(|&c| c != '\r') {
+            keys.extend_from_slice(keys_for_char(c).ok_or(UnsupportedChar(c))?);
+        }
+
+        self.typing.keys.extend(keys);
+        Ok(())
+    }
+
+    /// Queues key strokes paced like [`Pc1500::type_text`], for keys no
+    /// character stands for such as MODE or CL.
+    pub fn type_keys(&mut self, keys: &[Key]) {
+        self.typing.keys.extend(keys);
+    }
+
+    #[must_use]
+    pub fn is_typing(&self) -> bool {
+        !self.typing.is_empty()
+    }
+
+    /// Drops any text still waiting to be typed and releases the held key.
+    pub fn cancel_typing(&mut self) {
+        if let Stroke::Held(key) = self.typing.stroke {
+            self.release(key);
+        }
+        self.typing = TypeQueue::new();
+    }
+
+    /// Whether the ROM has waited for a key since `tick` with nothing left
+    /// to type, at the command prompt or an INPUT.
+    pub(crate) fn waited_for_key_since(&self, tick: usize) -> bool {
+        self.typing.idle_tick.is_some_and(|idle| idle >= tick)
+    }
+
+    pub(crate) fn keyboard_scan_hook(&mut self, addr: u16) {
+        match (self.typing.stroke, addr) {
+            (Stroke::Idle, WAIT_4_KB | ISKEY) => match self.typing.keys.pop_front() {
+                Some(key) => {
+                    self.press(key);
+                    self.typing.stroke = Stroke::Held(key);
+                }
+                None if addr == WAIT_4_KB => {
+                    self.typing.idle_tick = Some(self.lh5801.get_ticks());
+                }
+                None => {}
+            },
+            (Stroke::Held(key), WAIT_4_KB) => {
+                self.release(key);
+                self.typing.stroke = Stroke::Released;
+            }
+            (Stroke::Released, ISKEY) => self.typing.stroke = Stroke::Idle,
+            _ => {}
+        }
+    }
+}
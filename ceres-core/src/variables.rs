@@ -0,0 +1,283 @@
+// BASIC variables in RAM, decoded for inspection
+
+use core::{error::Error, fmt, iter};
+
+use crate::{
+    Pc1500, basic,
+    bcd::{self, NUMBER_SIZE},
+    memory::{STANDARD_USER_MEMORY_BEGIN, STANDARD_USER_MEMORY_END},
+};
+
+// Fixed numeric variables A to Z, one number each
+const FIXED_NUMBERS: u32 = 0x7900;
+// Fixed string variables, in blocks starting at the given letter
+const FIXED_STRINGS: [(u8, u32); 3] = [(b'A', 0x78C0), (b'E', 0x7650), (b'P', 0x7750)];
+const FIXED_STRING_SIZE: u8 = 16;
+// Strings shorter than their space end with this byte
+const STRING_END: u8 = 0x00;
+
+// Bounds of the dynamic variable area, kept by the ROM, big-endian
+const VARIABLES_START: u32 = 0x7899;
+const VARIABLES_END: u32 = 0x789B;
+
+// Dynamic variable layout: two name characters (the second is 0 for one
+// letter names), flags, size of the rest (big-endian), the largest index
+// of each dimension, for strings the length of each element, then the
+// elements
+const ENTRY_HEADER_SIZE: u8 = 5;
+const STRING_FLAG: u8 = 0x80;
+const DIMENSIONS_MASK: u8 = 0x03;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// NaN when the bytes are not a valid number
+    Number(f64),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variable {
+    /// Name as written in BASIC, with `$` for strings
+    pub name: String,
+    /// Number of elements along each dimension, empty for simple variables
+    pub dimensions: Vec<usize>,
+    /// Elements in memory order, a single one for simple variables
+    pub values: Vec<Value>,
+}
+
+impl Variable {
+    fn simple(name: String, value: Value) -> Self {
+        Self {
+            name,
+            dimensions: Vec::new(),
+            values: vec![value],
+        }
+    }
+
+    #[must_use]
+    pub const fn is_array(&self) -> bool {
+        !self.dimensions.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetVariableError {
+    name: String,
+    reason: &'static str,
+}
+
+impl SetVariableError {
+    fn new(name: &str, reason: &'static str) -> Self {
+        Self {
+            name: name.to_owned(),
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for SetVariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.reason)
+    }
+}
+
+impl Error for SetVariableError {}
+
+fn decode_number(bytes: &[u8]) -> Value {
+    let number = bytes
+        .try_into()
+        .ok()
+        .and_then(bcd::to_f64)
+        .unwrap_or(f64::NAN);
+    Value::Number(number)
+}
+
+fn decode_string(bytes: &[u8]) -> Value {
+    let mut text = String::new();
+    for &byte in bytes.iter().take_while(|&&byte| byte != STRING_END) {
+        basic::push_char(&mut text, byte);
+    }
+    Value::String(text)
+}
+
+fn decode_entry(header: &[u8], body: &[u8]) -> Option<Variable> {
+    let mut name = String::new();
+    for &byte in header[..2].iter().filter(|&&byte| byte != 0) {
+        name.push(basic::char_from_byte(byte)?);
+    }
+
+    let flags = header[2];
+    let string = flags & STRING_FLAG != 0;
+    if string {
+        name.push('$');
+    }
+
+    let (largest, body) = body.split_at_checked((flags & DIMENSIONS_MASK).into())?;
+    let dimensions: Vec<usize> = largest
+        .iter()
+        .map(|&index| usize::from(index) + 1)
+        .collect();
+    let (element_size, elements) = if string {
+        let (&length, elements) = body.split_first()?;
+        (usize::from(length), elements)
+    } else {
+        (NUMBER_SIZE, body)
+    };
+
+    let count: usize = dimensions.iter().product();
+    if element_size == 0 || elements.len() < count * element_size {
+        return None;
+    }
+    let values = elements
+        .chunks_exact(element_size)
+        .take(count)
+        .map(|element| {
+            if string {
+                decode_string(element)
+            } else {
+                decode_number(element)
+            }
+        })
+        .collect();
+
+    Some(Variable {
+        name,
+        dimensions,
+        values,
+    })
+}
+
+fn fixed_number_addr(letter: u8) -> u32 {
+    (FIXED_NUMBERS..)
+        .step_by(NUMBER_SIZE)
+        .nth(usize::from(letter - b'A'))
+        .unwrap_or(FIXED_NUMBERS)
+}
+
+fn fixed_string_addr(letter: u8) -> u32 {
+    let (first, base) = FIXED_STRINGS
+        .iter()
+        .rev()
+        .find(|(first, _)| *first <= letter)
+        .copied()
+        .unwrap_or(FIXED_STRINGS[0]);
+    base + u32::from(letter - first) * u32::from(FIXED_STRING_SIZE)
+}
+
+impl Pc1500 {
+    fn read_range(&self, addr: u32, len: usize) -> Vec<u8> {
+        (addr..)
+            .take(len)
+            .map(|addr| self.read_byte(addr))
+            .collect()
+    }
+
+    /// The fixed variables A to Z, then A$ to Z$. They always exist.
+    #[must_use]
+    pub fn fixed_variables(&self) -> Vec<Variable> {
+        let numbers = (b'A'..=b'Z').map(|letter| {
+            let value = decode_number(&self.read_number(fixed_number_addr(letter)));
+            Variable::simple(char::from(letter).to_string(), value)
+        });
+
+        let strings = (b'A'..=b'Z').map(|letter| {
+            let bytes = self.read_range(fixed_string_addr(letter), FIXED_STRING_SIZE.into());
+            Variable::simple(format!("{}$", char::from(letter)), decode_string(&bytes))
+        });
+
+        numbers.chain(strings).collect()
+    }
+
+    /// The variables and arrays created while a program runs, in memory
+    /// order. Decoding stops at the first entry that does not fit the area.
+    #[must_use]
+    pub fn dynamic_variables(&self) -> Vec<Variable> {
+        let mut variables = Vec::new();
+        let mut addr = u32::from(self.read_word(VARIABLES_START));
+        let end = u32::from(self.read_word(VARIABLES_END));
+        if addr < STANDARD_USER_MEMORY_BEGIN || end > STANDARD_USER_MEMORY_END + 1 {
+            return variables;
+        }
+
+        while addr + u32::from(ENTRY_HEADER_SIZE) <= end {
+            let header = self.read_range(addr, ENTRY_HEADER_SIZE.into());
+            let size = u16::from(header[3]) << 8 | u16::from(header[4]);
+            let body_addr = addr + u32::from(ENTRY_HEADER_SIZE);
+            let entry_end = body_addr + u32::from(size);
+            if entry_end > end {
+                break;
+            }
+
+            let body = self.read_range(body_addr, size.into());
+            let Some(variable) = decode_entry(&header, &body) else {
+                break;
+            };
+            variables.push(variable);
+            addr = entry_end;
+        }
+
+        variables
+    }
+
+    /// Every variable, fixed ones first.
+    #[must_use]
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables = self.fixed_variables();
+        variables.extend(self.dynamic_variables());
+        variables
+    }
+
+    /// Stores `value` in the fixed variable called `name`, A to Z or A$ to
+    /// Z$. Numbers are rounded to twelve digits.
+    pub fn set_variable(&mut self, name: &str, value: &Value) -> Result<(), SetVariableError> {
+        let (letter, string) = match *name.as_bytes() {
+            [letter] => (letter, false),
+            [letter, b'$'] => (letter, true),
+            _ => return Err(SetVariableError::new(name, "not a fixed variable")),
+        };
+        if !letter.is_ascii_uppercase() {
+            return Err(SetVariableError::new(name, "not a fixed variable"));
+        }
+
+        match (value, string) {
+            (Value::Number(number), false) => {
+                let bytes = bcd::from_f64(*number)
+                    .ok_or_else(|| SetVariableError::new(name, "number out of range"))?;
+                self.write_number(fixed_number_addr(letter), bytes);
+            }
+            (Value::String(text), true) => {
+                let bytes: Vec<u8> = text
+                    .chars()
+                    .map(|c| {
+                        u8::try_from(c)
+                            .ok()
+                            .filter(|&byte| basic::char_from_byte(byte).is_some())
+                    })
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| {
+                        SetVariableError::new(name, "character not in the PC-1500 character set")
+                    })?;
+                if bytes.len() > FIXED_STRING_SIZE.into() {
+                    return Err(SetVariableError::new(name, "string too long"));
+                }
+
+                let addr = fixed_string_addr(letter);
+                let padded = bytes.into_iter().chain(iter::repeat(STRING_END));
+                for (byte_addr, byte) in (addr..).take(FIXED_STRING_SIZE.into()).zip(padded) {
+                    self.write_byte(byte_addr, byte);
+                }
+            }
+            _ => return Err(SetVariableError::new(name, "value does not match the type")),
+        }
+
+        Ok(())
+    }
+
+    /// The variable called `name`, with `$` for strings.
+    #[must_use]
+    pub fn variable(&self, name: &str) -> Option<Variable> {
+        self.variables()
+            .into_iter()
+            .find(|variable| variable.name == name)
+    }
+}
@@ -0,0 +1,126 @@
+// Minimal RIFF/WAVE PCM support for tape audio
+
+use std::io::{self, Read, Write};
+
+const PCM_FORMAT: u16 = 1;
+const CHANNELS: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 16;
+const HEADER_SIZE: u32 = 44;
+
+/// Whether `bytes` start like a WAV file.
+pub fn is_wav(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"RIFF")
+}
+
+/// Writes mono 16-bit PCM samples as a WAV file.
+pub fn write_wav<W: Write>(mut writer: W, sample_rate: u32, samples: &[i16]) -> io::Result<()> {
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = u32::try_from(samples.len() * usize::from(block_align))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    writer.write_all(&PCM_FORMAT.to_le_bytes())?;
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads an 8 or 16-bit PCM WAV file, mixing all channels down to mono.
+/// Returns the sample rate and the samples.
+pub fn read_wav<R: Read>(mut reader: R) -> io::Result<(u32, Vec<i16>)> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err(invalid_data("not a RIFF/WAVE file"));
+    }
+
+    let mut format = None;
+    let mut offset = 12;
+    while let (Some(id), Some(size)) = (bytes.get(offset..offset + 4), read_u32(&bytes, offset + 4))
+    {
+        let body = offset + 8;
+        // Recorders that stream to disk or get cut off leave sizes running
+        // past the end of the file, keep whatever is there
+        let size = (size as usize).min(bytes.len().saturating_sub(body));
+        let chunk = bytes.get(body..body + size).unwrap_or_default();
+
+        match id {
+            b"fmt " => {
+                let field = |at| read_u16(chunk, at).ok_or_else(|| invalid_data("short fmt chunk"));
+                if field(0)? != PCM_FORMAT {
+                    return Err(invalid_data("only PCM WAV files are supported"));
+                }
+                let channels = field(2)?;
+                let sample_rate =
+                    read_u32(chunk, 4).ok_or_else(|| invalid_data("short fmt chunk"))?;
+                let bits = field(14)?;
+                format = Some((channels, sample_rate, bits));
+            }
+            b"data" => {
+                let (channels, sample_rate, bits) =
+                    format.ok_or_else(|| invalid_data("data chunk before fmt chunk"))?;
+                if channels == 0 {
+                    return Err(invalid_data("no channels"));
+                }
+
+                let frames: Vec<i32> = match bits {
+                    8 => chunk.iter().map(|&b| (i32::from(b) - 128) << 8).collect(),
+                    16 => chunk
+                        .chunks_exact(2)
+                        .map(|b| i32::from(i16::from_le_bytes([b[0], b[1]])))
+                        .collect(),
+                    _ => return Err(invalid_data("only 8 and 16-bit samples are supported")),
+                };
+
+                let samples = frames
+                    .chunks_exact(usize::from(channels))
+                    .map(|frame| {
+                        let sum: i32 = frame.iter().sum();
+                        let mixed = sum / i32::from(channels);
+                        i16::try_from(mixed).unwrap_or(i16::MAX)
+                    })
+                    .collect();
+
+                return Ok((sample_rate, samples));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        offset = body + size + (size & 1);
+    }
+
+    Err(invalid_data("missing data chunk"))
+}
@@ -0,0 +1,110 @@
+use std::io::Cursor;
+
+use ceres_core::{Pc1500, audio};
+
+const PROGRAM: u16 = 0x4000;
+const STACK: u16 = 0x7700;
+const TICKS_PER_SECOND: u32 = 1_300_000;
+
+/// Loads a loop toggling the buzzer pin with `delay` turns of a delay loop
+/// per half period, 32 * delay + 59 ticks per period.
+fn buzz(pc1500: &mut Pc1500, delay: u8) {
+    #[rustfmt::skip]
+    let program = [
+        0xB5, 0x40,             // LDI A,40
+        0xFD, 0xAE, 0xF0, 0x08, // STA #(F008), OPC
+        0x4A, delay,            // LDI XL,delay
+        0x42,                   // DEC XL
+        0x99, 0x03,             // BZR -3
+        0xB5, 0x00,             // LDI A,00
+        0xFD, 0xAE, 0xF0, 0x08, // STA #(F008)
+        0x4A, delay,            // LDI XL,delay
+        0x42,                   // DEC XL
+        0x99, 0x03,             // BZR -3
+        0x9E, 0x18,             // BCH -18
+    ];
+    for (addr, &byte) in (u32::from(PROGRAM)..).zip(&program) {
+        pc1500.write_byte(addr, byte);
+    }
+    let cpu = pc1500.cpu_mut();
+    cpu.cancel_reset();
+    cpu.set_pc(PROGRAM);
+    cpu.set_s(STACK);
+}
+
+/// Frequency in Hz from the rising zero crossings of `samples`.
+fn frequency(samples: &[i16], sample_rate: u32) -> Option<u32> {
+    let crossings: Vec<usize> = samples
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0 && pair[1] >= 0)
+        .map(|(index, _)| index)
+        .collect();
+    let (first, last) = (crossings.first()?, crossings.last()?);
+    let periods = u32::try_from(crossings.len() - 1).ok()?;
+    let length = u32::try_from(last - first).ok()?;
+    (length > 0).then(|| (periods * sample_rate + length / 2) / length)
+}
+
+#[test]
+fn buzzer_pitch_is_kept_at_any_sample_rate() {
+    let delay = 40;
+    let expected = TICKS_PER_SECOND / (32 * u32::from(delay) + 59);
+
+    for sample_rate in [22_050, 44_100, 48_000] {
+        let mut pc1500 = Pc1500::new();
+        buzz(&mut pc1500, delay);
+        pc1500.enable_audio(sample_rate);
+        for _ in 0..40 {
+            pc1500.step_frame();
+        }
+        let samples = pc1500.take_audio();
+        let measured = frequency(&samples, sample_rate).unwrap_or_default();
+        assert!(
+            measured.abs_diff(expected) <= expected / 100,
+            "{measured} Hz at {sample_rate} Hz, expected {expected} Hz"
+        );
+    }
+}
+
+#[test]
+fn sample_count_follows_emulated_time() {
+    let mut pc1500 = Pc1500::new();
+    buzz(&mut pc1500, 40);
+    pc1500.enable_audio(audio::DEFAULT_SAMPLE_RATE);
+    let start = pc1500.cpu().get_ticks();
+    for _ in 0..87 {
+        pc1500.step_frame();
+    }
+    let ticks = u64::try_from(pc1500.cpu().get_ticks() - start).unwrap_or_default();
+
+    let expected = ticks * u64::from(audio::DEFAULT_SAMPLE_RATE) / u64::from(TICKS_PER_SECOND);
+    let produced = u64::try_from(pc1500.take_audio().len()).unwrap_or_default();
+    assert!(
+        produced.abs_diff(expected) <= 1,
+        "{produced} samples for {ticks} ticks, expected {expected}"
+    );
+    assert!(pc1500.take_audio().is_empty(), "samples are taken once");
+}
+
+#[test]
+fn silent_line_is_silent_and_wav_is_written() {
+    let mut pc1500 = Pc1500::new();
+    assert!(pc1500.take_audio().is_empty(), "audio is off by default");
+    pc1500.enable_audio(8000);
+    for _ in 0..10 {
+        pc1500.step_frame();
+    }
+    let samples = pc1500.take_audio();
+    assert!(
+        samples.iter().all(|&sample| sample == 0),
+        "idle buzzer produces silence"
+    );
+
+    let mut wav = Vec::new();
+    assert!(
+        audio::write_wav(Cursor::new(&mut wav), 8000, &samples).is_ok(),
+        "WAV is written"
+    );
+    assert_eq!(wav.len(), 44 + samples.len() * 2, "16-bit mono PCM");
+}
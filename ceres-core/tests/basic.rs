@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use ceres_core::{
+    Pc1500,
+    basic::{self, KEYWORDS},
+};
+
+#[test]
+fn preloaded_program_is_listed() {
+    let listing = Pc1500::new().list_program().unwrap_or_default();
+    let lines: Vec<&str> = listing.lines().collect();
+
+    assert_eq!(lines.len(), 35, "every line is listed");
+    for expected in [
+        "1 \"BATHYSCAPH\"",
+        "8 \"Z\" CLS:WAIT 0:CLEAR:DIM A$(0)*20:GCURSOR 10",
+        "10 FOR I=0 TO 13",
+        "20 RESTORE 999+RND 16",
+        "30 READ A$(0):GPRINT A$(0);",
+        "40 NEXT I:GCURSOR 154:GPRINT \"141C\":TIME=0,H=3,G=8",
+        "60 Z$=INKEY$:IF Z$=\"\" THEN 85",
+        "70 H=H-SGN(ASC Z$-10.5)",
+        "80 G=INT(2^H+.5)",
+        "85 S=R:R=Q:Q=POINT P",
+        "90 GPRINT S;G OR R;G OR Q",
+        "100 IF(Q AND G)>0 GOSUB \"CRASH\"",
+        "110 BEEP 1,0,1:NEXT P:IF H<>3 THEN 50",
+        "130 END",
+        "210 CLEAR:P=2,G=8,H=3:RETURN",
+        "1000 DATA \"7163470F1F0F47637160\"",
+    ] {
+        assert!(lines.contains(&expected), "{expected} is in\n{listing}");
+    }
+    assert!(!listing.contains('['), "every token is known:\n{listing}");
+}
+
+#[test]
+fn strings_and_remarks_are_not_detokenized() {
+    // PRINT "<F1><97>" : REM <F1><97>
+    let text = [
+        0xF0, 0x97, b'"', 0xF1, 0x97, b'"', b':', 0xF1, 0xAB, b' ', 0xF1, 0x97,
+    ];
+    assert_eq!(
+        basic::detokenize(&text),
+        "PRINT \"[F1][97]\":REM [F1][97]",
+        "bytes in strings and remarks are characters"
+    );
+    assert_eq!(
+        basic::detokenize(&[0xF0, 0x00, b'1']),
+        "[F000]1",
+        "unknown tokens are kept as codes"
+    );
+}
+
+#[test]
+fn program_ends_at_marker_or_fails() {
+    let program = [0x00, 0x0A, 0x02, b'A', 0x0D, 0xFF, 0x00, 0x14];
+    assert_eq!(
+        basic::list(&program).ok().as_deref(),
+        Some("10 A\n"),
+        "listing stops at the end marker"
+    );
+
+    let error = basic::list(&program[..4]).err();
+    assert_eq!(
+        error.map(|error| error.offset()),
+        Some(0),
+        "a line cut short is reported"
+    );
+}
+
+#[test]
+fn preloaded_program_round_trips() {
+    let mut pc1500 = Pc1500::new();
+    let program = pc1500.program_bytes();
+    let listing = basic::list(&program).unwrap_or_default();
+
+    assert_eq!(
+        basic::parse_program(&listing).ok(),
+        Some(program.clone()),
+        "the listing tokenizes back to the same bytes"
+    );
+
+    assert_eq!(pc1500.load_program(&listing), Ok(()));
+    assert_eq!(pc1500.program_bytes(), program, "loading is a no-op");
+}
+
+#[test]
+fn source_is_tokenized_like_the_rom() {
+    let source = "20 P.\"A B\";X:G.10\n10 FORI=1TO 5 STEP 2: REM  Hi P.\n";
+    let program = basic::parse_program(source).unwrap_or_default();
+
+    assert_eq!(
+        program,
+        [
+            0x00, 0x0A, 0x16, 0xF1, 0xA5, b'I', b'=', b'1', 0xF1, 0xB1, b'5', 0xF1, 0xAD, b'2',
+            b':', 0xF1, 0xAB, b' ', b' ', b'H', b'i', b' ', b'P', b'.', 0x0D, //
+            0x00, 0x14, 0x0F, 0xF0, 0x97, b'"', b'A', b' ', b'B', b'"', b';', b'X', b':', 0xF1,
+            0x92, b'1', b'0', 0x0D,
+        ],
+        "lines are sorted, abbreviations expanded and spaces dropped"
+    );
+    assert_eq!(
+        basic::list(&program).ok().as_deref(),
+        Some("10 FOR I=1 TO 5 STEP 2:REM  Hi P.\n20 PRINT \"A B\";X:GOTO 10\n")
+    );
+}
+
+#[test]
+fn abbreviations_expand_to_keywords() {
+    for (letters, name) in [
+        ("P", "PRINT"),
+        ("PR", "PRINT"),
+        ("PO", "POKE"),
+        ("G", "GOTO"),
+        ("GOS", "GOSUB"),
+        ("RE", "RETURN"),
+        ("RES", "RESTORE"),
+        ("GCU", "GCURSOR"),
+    ] {
+        assert_eq!(
+            basic::keyword_by_abbreviation(letters).map(|keyword| keyword.name),
+            Some(name),
+            "{letters}."
+        );
+    }
+    assert!(basic::keyword_by_abbreviation("X").is_none());
+
+    for (_, name) in basic::ABBREVIATIONS {
+        assert!(
+            basic::keyword_by_name(name).is_some(),
+            "{name} is a keyword"
+        );
+    }
+}
+
+#[test]
+fn bad_source_lines_are_reported() {
+    for (source, line) in [
+        ("10 A=1\nPRINT\n", 2),
+        ("10 A=1\n\n65280 END\n", 3),
+        ("10 PRINT \"\u{e9}\"\n", 1),
+    ] {
+        let error = basic::parse_program(source).err();
+        assert_eq!(error.map(|error| error.line()), Some(line), "{source:?}");
+    }
+
+    let long_line = format!("1 REM {}\n", "X".repeat(300));
+    let too_long = basic::parse_program(&long_line).err();
+    assert_eq!(too_long.map(|error| error.line()), Some(1), "line too long");
+
+    let mut huge = String::new();
+    for number in 1..=2000 {
+        huge.push_str(&number.to_string());
+        huge.push_str(" PRINT\n");
+    }
+    let too_large = Pc1500::new().load_program(&huge).err();
+    assert!(too_large.is_some(), "program does not fit in memory");
+}
+
+#[test]
+fn loaded_program_runs() {
+    let mut pc1500 = Pc1500::new();
+    if !pc1500.has_basic_rom() {
+        eprintln!("skipped: the bundled ROM image is a stub that cannot boot");
+        return;
+    }
+    for _ in 0..100 {
+        pc1500.step_frame();
+    }
+
+    let loaded = pc1500.load_program("10 POKE 20480,6*7\n20 END\n");
+    assert_eq!(loaded, Ok(()));
+    assert_eq!(pc1500.type_text("RUN\n"), Ok(()));
+    for _ in 0..100 {
+        pc1500.step_frame();
+    }
+
+    assert_eq!(
+        pc1500.read_byte(0x5000),
+        42,
+        "the program stored its result"
+    );
+}
+
+#[test]
+fn keyword_table_is_unambiguous() {
+    let codes: HashSet<u16> = KEYWORDS.iter().map(|keyword| keyword.code).collect();
+    let names: HashSet<&str> = KEYWORDS.iter().map(|keyword| keyword.name).collect();
+    assert_eq!(codes.len(), KEYWORDS.len(), "codes are unique");
+    assert_eq!(names.len(), KEYWORDS.len(), "names are unique");
+}
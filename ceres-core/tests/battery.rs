@@ -0,0 +1,68 @@
+use ceres_core::{ClockTime, Pc1500, battery::BatteryBackup, clock::ClockSource};
+use chrono::{NaiveDate, NaiveDateTime};
+
+fn datetime(year: i32, month: u32, day: u32, hms: (u32, u32, u32)) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hms.0, hms.1, hms.2))
+        .unwrap_or_default()
+}
+
+#[test]
+fn backup_round_trips_through_bytes() {
+    let mut pc1500 = Pc1500::with_rtc_time(datetime(1984, 3, 14, (12, 0, 0)));
+    pc1500.write_byte(0x4000, 0x12);
+    pc1500.write_byte(0x7FFF, 0x34);
+    let backup = pc1500.battery_backup();
+
+    let restored = BatteryBackup::from_bytes(&backup.to_bytes());
+    assert_eq!(restored.ok(), Some(backup), "nothing is lost");
+}
+
+#[test]
+fn other_files_are_rejected() {
+    assert!(
+        BatteryBackup::from_bytes(b"PC15TAPE\x01").is_err(),
+        "a tape image is not a backup"
+    );
+
+    let mut bytes = Pc1500::new().battery_backup().to_bytes();
+    bytes.pop();
+    assert!(
+        BatteryBackup::from_bytes(&bytes).is_err(),
+        "a truncated backup is rejected"
+    );
+}
+
+#[test]
+fn clock_keeps_its_offset_from_the_host() {
+    let start = datetime(1984, 3, 14, (12, 0, 0));
+    let mut pc1500 = Pc1500::with_clock(ClockSource::Manual(start));
+    // A day and an hour ahead, on the wrong weekday
+    let mut time = ClockTime::from(datetime(1984, 3, 15, (13, 0, 0)));
+    time.weekday = (time.weekday + 2) % 7;
+    pc1500.set_clock_time(time);
+    pc1500.write_byte(0x40C5, 0x56);
+    let backup = pc1500.battery_backup();
+    assert_eq!(backup.clock_offset(), 25 * 3600, "offset in seconds");
+
+    // The next session starts a week later
+    let later = datetime(1984, 3, 21, (8, 30, 0));
+    let mut next = Pc1500::with_clock(ClockSource::Manual(later));
+    next.restore_battery_backup(&backup);
+    let mut expected = ClockTime::from(datetime(1984, 3, 22, (9, 30, 0)));
+    expected.weekday = (expected.weekday + 2) % 7;
+    assert_eq!(next.clock_time(), expected, "the offset is applied");
+    assert_eq!(next.read_byte(0x40C5), 0x56, "RAM is restored");
+}
+
+#[test]
+fn offset_is_measured_across_the_new_year() {
+    let host = datetime(2025, 1, 1, (0, 0, 10));
+    let mut pc1500 = Pc1500::with_clock(ClockSource::Manual(host));
+    pc1500.set_clock_time(ClockTime::from(datetime(2024, 12, 31, (23, 59, 50))));
+    assert_eq!(
+        pc1500.battery_backup().clock_offset(),
+        -20,
+        "the clock is behind, not almost a year ahead"
+    );
+}
@@ -0,0 +1,106 @@
+use ceres_core::{
+    Pc1500,
+    bcd::{self, ZERO},
+};
+
+#[test]
+fn numbers_round_trip_through_f64() {
+    for (value, bytes) in [
+        (0.0, ZERO),
+        (42.0, [0x01, 0x00, 0x42, 0, 0, 0, 0, 0]),
+        (-1.5, [0x00, 0x80, 0x15, 0, 0, 0, 0, 0]),
+        (0.1, [0xFF, 0x00, 0x10, 0, 0, 0, 0, 0]),
+        (
+            123_456_789_012.0,
+            [0x0B, 0x00, 0x12, 0x34, 0x56, 0x78, 0x90, 0x12],
+        ),
+        (1e-99, [0x9D, 0x00, 0x10, 0, 0, 0, 0, 0]),
+        (
+            -9.999_999_999_99e99,
+            [0x63, 0x80, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99],
+        ),
+    ] {
+        assert_eq!(bcd::from_f64(value), Some(bytes), "{value} is encoded");
+        assert_eq!(bcd::to_f64(bytes), Some(value), "{value} is decoded");
+    }
+}
+
+#[test]
+fn special_cases_are_handled() {
+    assert_eq!(bcd::from_f64(-0.0), Some(ZERO), "zero has no sign");
+    assert_eq!(bcd::from_f64(1e-100), Some(ZERO), "underflow gives zero");
+    assert_eq!(bcd::from_f64(1e100), None, "overflow");
+    assert_eq!(bcd::from_f64(f64::NAN), None);
+    assert_eq!(bcd::from_f64(f64::INFINITY), None);
+    assert_eq!(
+        bcd::to_f64([0x00, 0x00, 0x1A, 0, 0, 0, 0, 0]),
+        None,
+        "mantissa digits are decimal"
+    );
+}
+
+#[test]
+fn decimal_strings_are_exact() {
+    for (text, bytes) in [
+        ("0", ZERO),
+        ("42", [0x01, 0x00, 0x42, 0, 0, 0, 0, 0]),
+        ("-0.25", [0xFF, 0x80, 0x25, 0, 0, 0, 0, 0]),
+        ("0.0000000001", [0xF6, 0x00, 0x10, 0, 0, 0, 0, 0]),
+        (
+            "3.14159265359",
+            [0x00, 0x00, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59],
+        ),
+        ("1.5E-20", [0xEC, 0x00, 0x15, 0, 0, 0, 0, 0]),
+        ("-1E99", [0x63, 0x80, 0x10, 0, 0, 0, 0, 0]),
+        ("100000000000", [0x0B, 0x00, 0x10, 0, 0, 0, 0, 0]),
+    ] {
+        assert_eq!(bcd::from_decimal(text), Some(bytes), "{text} is parsed");
+        assert_eq!(
+            bcd::to_decimal(bytes).as_deref(),
+            Some(text),
+            "{text} is written"
+        );
+    }
+}
+
+#[test]
+fn decimal_strings_are_rounded_half_away_from_zero() {
+    for (text, rounded) in [
+        ("1.000000000005", "1.00000000001"),
+        ("-1.0000000000049", "-1"),
+        ("9.999999999995", "10"),
+        ("+.5e1", "5"),
+        ("0012.50", "12.5"),
+    ] {
+        let bytes = bcd::from_decimal(text).unwrap_or_default();
+        assert_eq!(bcd::to_decimal(bytes).as_deref(), Some(rounded), "{text}");
+    }
+
+    for text in ["", ".", "-", "1.2.3", "1E", "12A", "1E100"] {
+        assert_eq!(bcd::from_decimal(text), None, "{text:?} is rejected");
+    }
+}
+
+#[test]
+fn twelve_digits_survive_f64() {
+    for text in [
+        "123456789012",
+        "-9.99999999999E-99",
+        "3.14159265359",
+        "1E99",
+    ] {
+        let bytes = bcd::from_decimal(text).unwrap_or_default();
+        let through_f64 = bcd::to_f64(bytes).and_then(bcd::from_f64);
+        assert_eq!(through_f64, Some(bytes), "{text} keeps every digit");
+    }
+}
+
+#[test]
+fn numbers_are_read_and_written_in_memory() {
+    let mut pc1500 = Pc1500::new();
+    let bytes = bcd::from_decimal("-273.15").unwrap_or_default();
+    pc1500.write_number(bcd::ARX, bytes);
+
+    assert_eq!(pc1500.read_number(bcd::ARX), bytes);
+    assert_eq!(bcd::to_f64(pc1500.read_number(bcd::ARX)), Some(-273.15));
+}
@@ -0,0 +1,251 @@
+use std::error::Error;
+
+use ceres_core::{
+    Pc1500,
+    ce150::{Ce150, PlotMode},
+    paper::{Paper, PenColor, Stroke},
+};
+
+// CE-150 LH5810 registers on ME1
+const DDA: u32 = 0x1B00C;
+const DDB: u32 = 0x1B00D;
+const OPA: u32 = 0x1B00E;
+const OPB: u32 = 0x1B00F;
+const PEN_DOWN: u8 = 0x20;
+// Character cell width at CSIZE 2, in plotter steps
+const CELL: i32 = 12;
+
+// Full steps with one coil energised
+const COILS: [u8; 4] = [0x1, 0x2, 0x4, 0x8];
+
+/// Drives the motors of an attached CE-150 like the plotter ROM does.
+struct Plotter {
+    pc1500: Pc1500,
+    carriage: usize,
+    paper: usize,
+    pen: u8,
+}
+
+impl Plotter {
+    fn new() -> Self {
+        let mut pc1500 = Pc1500::new();
+        pc1500.attach(Ce150::new());
+        pc1500.write_byte(DDA, 0xFF);
+        pc1500.write_byte(DDB, PEN_DOWN);
+        let mut plotter = Self {
+            pc1500,
+            carriage: 0,
+            paper: 0,
+            pen: 0,
+        };
+        plotter.output();
+        plotter
+    }
+
+    fn output(&mut self) {
+        let port_a = COILS[self.carriage % 4] | (COILS[self.paper % 4] << 4);
+        self.pc1500.write_byte(OPA, port_a);
+        self.pc1500.write_byte(OPB, self.pen);
+        self.pc1500.step_frame();
+    }
+
+    fn pen(&mut self, down: bool) {
+        self.pen = if down { PEN_DOWN } else { 0 };
+        self.output();
+    }
+
+    /// Moves by whole steps, one axis after the other.
+    fn move_by(&mut self, dx: isize, dy: isize) {
+        for _ in 0..dx.unsigned_abs() {
+            self.carriage = self.carriage.wrapping_add_signed(dx.signum()) & 3;
+            self.output();
+        }
+        for _ in 0..dy.unsigned_abs() {
+            self.paper = self.paper.wrapping_add_signed(dy.signum()) & 3;
+            self.output();
+        }
+    }
+
+    fn paper(&self) -> Paper {
+        self.pc1500
+            .peripheral::<Ce150>()
+            .map(Ce150::paper)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[test]
+fn motors_draw_strokes() {
+    let mut plotter = Plotter::new();
+    plotter.move_by(5, 5);
+    plotter.pen(true);
+    plotter.move_by(10, 0);
+    plotter.move_by(0, 4);
+    plotter.pen(false);
+    plotter.move_by(-3, 0);
+
+    let paper = plotter.paper();
+    let strokes = paper.strokes();
+    assert_eq!(strokes.len(), 1, "one pen-down stroke");
+    assert_eq!(
+        strokes.first().map(|stroke| stroke.points().to_vec()),
+        Some(vec![(5, 5), (15, 5), (15, 9)]),
+        "stroke follows the pen in plotter steps"
+    );
+    assert_eq!(
+        plotter.pc1500.peripheral::<Ce150>().map(Ce150::position),
+        Some((12, 9)),
+        "pen keeps moving while lifted"
+    );
+}
+
+#[test]
+fn left_stop_turns_pen_drum() {
+    let mut plotter = Plotter::new();
+    let color = |this: &Plotter| this.pc1500.peripheral::<Ce150>().map(Ce150::pen_color);
+    assert_eq!(color(&plotter), Some(PenColor::Black), "starts with black");
+
+    plotter.move_by(-4, 0);
+    assert_eq!(color(&plotter), Some(PenColor::Black), "turns on leaving");
+    plotter.move_by(4, 0);
+    assert_eq!(color(&plotter), Some(PenColor::Blue), "next pen is blue");
+
+    plotter.pen(true);
+    plotter.move_by(0, 3);
+    plotter.pen(false);
+    plotter.move_by(-8, 0);
+    plotter.move_by(1, 0);
+    plotter.pen(true);
+    plotter.move_by(3, 0);
+
+    let colors: Vec<PenColor> = plotter
+        .paper()
+        .strokes()
+        .iter()
+        .map(Stroke::color)
+        .collect();
+    assert_eq!(
+        colors,
+        [PenColor::Blue, PenColor::Green],
+        "strokes keep their pen colour"
+    );
+}
+
+#[test]
+fn exports_svg_and_png() {
+    let mut plotter = Plotter::new();
+    plotter.move_by(2, 2);
+    plotter.pen(true);
+    plotter.move_by(6, 6);
+
+    let paper = plotter.paper();
+    let svg = paper.to_svg();
+    assert!(svg.starts_with("<svg"), "SVG document");
+    assert!(
+        svg.contains("points=\"2,2 8,2 8,8\""),
+        "stroke is a polyline: {svg}"
+    );
+    assert!(svg.contains("stroke=\"#202020\""), "drawn in black");
+
+    let mut png = Vec::new();
+    assert!(paper.write_png(&mut png).is_ok(), "PNG is written");
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"), "PNG signature");
+    let (width, height, rgb) = paper.rasterize();
+    // PNG integers are big endian
+    let header_word = |offset: usize| {
+        png.iter()
+            .skip(offset)
+            .take(4)
+            .fold(0, |word, &byte| (word << 8) | u32::from(byte))
+    };
+    assert_eq!(
+        (header_word(16), header_word(20)),
+        (width, height),
+        "PNG header holds the paper size"
+    );
+    assert!(
+        rgb.chunks_exact(3)
+            .any(|pixel| pixel == PenColor::Black.rgb()),
+        "ink is rasterised"
+    );
+}
+
+fn plotter_commands() -> Pc1500 {
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(Ce150::new());
+    pc1500
+}
+
+fn ce150(pc1500: &mut Pc1500) -> Result<&mut Ce150, &'static str> {
+    pc1500.peripheral_mut::<Ce150>().ok_or("no CE-150 attached")
+}
+
+#[test]
+fn lprint_then_line_draws_in_graph_coordinates() -> Result<(), Box<dyn Error>> {
+    let mut pc1500 = plotter_commands();
+    assert!(pc1500.lprint("AB"), "a CE-150 is attached");
+    assert_eq!(
+        ce150(&mut pc1500)?.position(),
+        (0, 20),
+        "LPRINT ends with a new line"
+    );
+
+    let plotter = ce150(&mut pc1500)?;
+    plotter.graph();
+    assert_eq!(plotter.mode(), PlotMode::Graph, "GRAPH mode");
+    plotter.glcursor((10, -10));
+    plotter.line(&[(50, -10), (50, -30)]);
+    plotter.set_color(PenColor::Red);
+    plotter.rline(&[(-40, 0)]);
+    plotter.text();
+
+    let strokes: Vec<(PenColor, Vec<(i32, i32)>)> = plotter
+        .paper()
+        .strokes()
+        .iter()
+        .map(|stroke| (stroke.color(), stroke.points().to_vec()))
+        .collect();
+    assert_eq!(
+        strokes,
+        [
+            (PenColor::Black, vec![(10, 30), (50, 30), (50, 50)]),
+            (PenColor::Red, vec![(50, 50), (10, 50)]),
+        ],
+        "y grows upwards from the GRAPH origin"
+    );
+    assert_eq!(plotter.position(), (0, 70), "TEXT starts a new line");
+    Ok(())
+}
+
+#[test]
+fn lprint_draws_glyphs_in_character_cells() -> Result<(), Box<dyn Error>> {
+    let mut pc1500 = plotter_commands();
+    let plotter = ce150(&mut pc1500)?;
+    // A vertical bar and a dot below a gap
+    plotter.print_glyph([0x7F, 0, 0, 0, 0]);
+    plotter.print_glyph([0, 0x41, 0, 0, 0]);
+
+    let strokes: Vec<Vec<(i32, i32)>> = plotter
+        .paper()
+        .strokes()
+        .iter()
+        .map(|stroke| stroke.points().to_vec())
+        .collect();
+    assert_eq!(
+        strokes,
+        [vec![(0, 0), (0, 12)], vec![(14, 0)], vec![(14, 12)]],
+        "one stroke per run of dots, 2 steps per dot and 12 per cell"
+    );
+    assert_eq!(plotter.position(), (2 * CELL, 0), "next character cell");
+
+    for _ in 0..17 {
+        plotter.print_glyph([0; 5]);
+    }
+    assert_eq!(
+        plotter.position(),
+        (CELL, 20),
+        "18 characters per line, the 19th starts the next"
+    );
+    Ok(())
+}
@@ -0,0 +1,285 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    process,
+    time::{Duration, Instant},
+};
+
+use ceres_core::{
+    Pc1500,
+    ce158::{self, Ce158},
+    serial::{DeviceLink, FileLink, SerialLink, TcpLink},
+};
+
+// CE-158 LH5810 registers on ME1
+const U: u32 = 0x1D005;
+const L: u32 = 0x1D006;
+const G: u32 = 0x1D009;
+const IF: u32 = 0x1D00B;
+const DDA: u32 = 0x1D00C;
+const DDB: u32 = 0x1D00D;
+const OPA: u32 = 0x1D00E;
+const OPB: u32 = 0x1D00F;
+// IF flags
+const RD: u8 = 0x04;
+const TD: u8 = 0x08;
+// Serial clock running at 4096 ticks per bit, about 317 baud
+const SLOW_CLOCK: u8 = 0x10 | 7;
+const FRAMES: usize = 100;
+
+/// A file in the temporary directory, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        let file = Self(env::temp_dir().join(format!("ceres-ce158-{}-{name}", process::id())));
+        drop(file.remove());
+        file
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        fs::remove_file(&self.0)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        drop(self.remove());
+    }
+}
+
+fn setup(ce158: Ce158) -> Pc1500 {
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(ce158);
+    pc1500.write_byte(G, SLOW_CLOCK);
+    pc1500.write_byte(DDA, 0xFF);
+    pc1500.write_byte(DDB, ce158::STROBE | ce158::RTS | ce158::DTR);
+    pc1500.write_byte(OPB, ce158::RTS | ce158::DTR);
+    pc1500
+}
+
+fn wait_for(pc1500: &mut Pc1500, flag: u8) -> bool {
+    (0..FRAMES).any(|_| {
+        pc1500.step_frame();
+        pc1500.read_byte(IF) & flag != 0
+    })
+}
+
+/// Sends `bytes` like PRINT# does, waiting for each to be shifted out.
+fn send(pc1500: &mut Pc1500, bytes: &[u8]) {
+    for &byte in bytes {
+        pc1500.write_byte(L, byte);
+        assert!(wait_for(pc1500, TD), "character is shifted out");
+    }
+}
+
+/// Receives `count` bytes like INPUT# does.
+fn receive(pc1500: &mut Pc1500, count: usize) -> Vec<u8> {
+    (0..count)
+        .map_while(|_| wait_for(pc1500, RD).then(|| pc1500.bus_read(U)))
+        .collect()
+}
+
+#[test]
+fn g_selects_the_bit_rate() {
+    let mut pc1500 = setup(Ce158::new());
+    pc1500.write_byte(G, 0x10 | 6);
+    assert_eq!(
+        pc1500.peripheral::<Ce158>().map(Ce158::baud_rate),
+        Some(634),
+        "2048 ticks per bit of the 1.3 MHz clock"
+    );
+}
+
+#[test]
+fn serial_file_link_exchanges_data() -> Result<(), Box<dyn Error>> {
+    let output = TempFile::new("serial-out");
+    let input = TempFile::new("serial-in");
+    assert!(
+        fs::write(&input.0, b"10 PRINT\r").is_ok(),
+        "input is written"
+    );
+    let link = FileLink::with_input(&output.0, &input.0)?;
+
+    let mut pc1500 = setup(Ce158::new());
+    if let Some(ce158) = pc1500.peripheral_mut::<Ce158>() {
+        ce158.connect_serial(Box::new(link));
+    }
+    let status = pc1500.read_byte(OPB);
+    assert_eq!(
+        status & (ce158::DSR | ce158::CTS),
+        ce158::DSR | ce158::CTS,
+        "connected link raises DSR and CTS"
+    );
+
+    // RTS stays low while sending, so no input is lost unread
+    pc1500.write_byte(OPB, ce158::DTR);
+    send(&mut pc1500, b"HELLO\r\n");
+    pc1500.write_byte(OPB, ce158::RTS | ce158::DTR);
+    assert_eq!(
+        receive(&mut pc1500, 9),
+        b"10 PRINT\r",
+        "input file is received"
+    );
+    assert!(
+        !wait_for(&mut pc1500, RD),
+        "nothing follows the end of the input"
+    );
+    assert_eq!(
+        fs::read(&output.0).unwrap_or_default(),
+        b"HELLO\r\n",
+        "transmitted bytes are appended"
+    );
+    Ok(())
+}
+
+#[test]
+fn inspecting_data_keeps_the_received_byte() -> Result<(), Box<dyn Error>> {
+    let output = TempFile::new("inspect-out");
+    let input = TempFile::new("inspect-in");
+    fs::write(&input.0, b"X")?;
+    let link = FileLink::with_input(&output.0, &input.0)?;
+    let mut pc1500 = setup(Ce158::new());
+    if let Some(ce158) = pc1500.peripheral_mut::<Ce158>() {
+        ce158.connect_serial(Box::new(link));
+    }
+
+    assert!(wait_for(&mut pc1500, RD), "a byte arrives");
+    assert_eq!(pc1500.read_byte(U), b'X', "inspected");
+    assert!(pc1500.read_byte(IF) & RD != 0, "inspecting U leaves RD set");
+    assert_eq!(pc1500.bus_read(U), b'X', "read by the CPU");
+    assert_eq!(pc1500.read_byte(IF) & RD, 0, "the CPU read clears RD");
+    Ok(())
+}
+
+#[test]
+fn serial_pacing_follows_baud_rate() -> Result<(), Box<dyn Error>> {
+    let output = TempFile::new("pacing");
+    let link = FileLink::open(&output.0)?;
+    let mut pc1500 = setup(Ce158::new());
+    if let Some(ce158) = pc1500.peripheral_mut::<Ce158>() {
+        ce158.connect_serial(Box::new(link));
+    }
+    // Nine bits of 4096 ticks outlast a 15000 tick frame
+    pc1500.write_byte(L, b'A');
+    pc1500.step_frame();
+    assert_eq!(
+        pc1500.read_byte(IF) & TD,
+        0,
+        "character is still being shifted out"
+    );
+    assert!(wait_for(&mut pc1500, TD), "and then done");
+    Ok(())
+}
+
+#[test]
+fn host_waits_for_rts() -> Result<(), Box<dyn Error>> {
+    let output = TempFile::new("rts-out");
+    let input = TempFile::new("rts-in");
+    fs::write(&input.0, b"R")?;
+    let link = FileLink::with_input(&output.0, &input.0)?;
+    let mut pc1500 = setup(Ce158::new());
+    if let Some(ce158) = pc1500.peripheral_mut::<Ce158>() {
+        ce158.connect_serial(Box::new(link));
+    }
+
+    pc1500.write_byte(OPB, ce158::DTR);
+    assert!(!wait_for(&mut pc1500, RD), "nothing arrives with RTS low");
+    pc1500.write_byte(OPB, ce158::RTS | ce158::DTR);
+    assert_eq!(receive(&mut pc1500, 1), b"R", "raising RTS lets it in");
+    Ok(())
+}
+
+#[test]
+fn serial_tcp_link_talks_to_host() -> Result<(), Box<dyn Error>> {
+    let link = TcpLink::listen("127.0.0.1:0")?;
+    let addr = link.local_addr()?;
+    let mut pc1500 = setup(Ce158::new());
+    if let Some(ce158) = pc1500.peripheral_mut::<Ce158>() {
+        ce158.connect_serial(Box::new(link));
+    }
+
+    let mut host = TcpStream::connect(addr)?;
+    host.set_read_timeout(Some(Duration::from_secs(5)))?;
+    host.write_all(b"PING")?;
+    assert_eq!(receive(&mut pc1500, 4), b"PING", "host bytes are received");
+
+    send(&mut pc1500, b"PONG");
+    let mut reply = [0; 4];
+    host.read_exact(&mut reply)?;
+    assert_eq!(&reply, b"PONG", "transmitted bytes reach the host");
+    Ok(())
+}
+
+#[test]
+fn serial_tcp_link_buffers_for_slow_hosts() -> Result<(), Box<dyn Error>> {
+    let mut link = TcpLink::listen("127.0.0.1:0")?;
+    let mut host = TcpStream::connect(link.local_addr()?)?;
+    host.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // More than the socket buffers hold while the host is not reading
+    let sent: Vec<u8> = (0..1 << 22).map(|n: u32| n.to_le_bytes()[1]).collect();
+    for &byte in &sent {
+        link.send(byte)?;
+    }
+
+    let mut received = vec![0; sent.len()];
+    let mut filled = 0;
+    while filled < received.len() {
+        // Polling the link sends what is still buffered
+        link.receive()?;
+        filled += host.read(&mut received[filled..])?;
+    }
+    assert_eq!(link.pending(), 0, "buffer is drained");
+    assert!(received == sent, "bytes arrive complete and in order");
+    Ok(())
+}
+
+#[test]
+fn parallel_strobe_prints_to_file() {
+    let output = TempFile::new("parallel");
+    let mut ce158 = Ce158::new();
+    assert_eq!(
+        setup(Ce158::new()).read_byte(OPB) & ce158::BUSY,
+        ce158::BUSY,
+        "no printer reads as busy"
+    );
+    assert!(ce158.connect_parallel_file(&output.0).is_ok(), "file opens");
+    let mut pc1500 = setup(ce158);
+
+    assert_eq!(
+        pc1500.read_byte(OPB) & ce158::BUSY,
+        0,
+        "attached printer is ready"
+    );
+    let lines = ce158::RTS | ce158::DTR;
+    for &byte in b"LPRINT\r\n" {
+        pc1500.write_byte(OPA, byte);
+        pc1500.write_byte(OPB, lines | ce158::STROBE);
+        pc1500.write_byte(OPB, lines);
+    }
+    // Latching without a strobe prints nothing
+    pc1500.write_byte(OPA, b'X');
+
+    assert_eq!(
+        fs::read(&output.0).unwrap_or_default(),
+        b"LPRINT\r\n",
+        "strobed bytes are appended"
+    );
+}
+
+#[test]
+fn dropping_a_device_link_stops_its_reader() -> Result<(), Box<dyn Error>> {
+    let link = DeviceLink::open("/dev/null")?;
+    let start = Instant::now();
+    drop(link);
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "the reader thread is joined promptly"
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use ceres_core::{
+    Pc1500,
+    display::{self, CELL_WIDTH, CELLS, DISPLAY_WIDTH, UNKNOWN_GLYPH},
+};
+
+const GLYPHS: [(char, [u8; 5]); 3] = [
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('H', [0x7F, 0x08, 0x08, 0x08, 0x7F]),
+    ('I', [0x00, 0x41, 0x7F, 0x41, 0x00]),
+];
+
+fn draw(columns: &mut [u8; DISPLAY_WIDTH], cell: usize, pattern: &[u8]) {
+    let x = cell * CELL_WIDTH;
+    columns[x..x + pattern.len()].copy_from_slice(pattern);
+}
+
+#[test]
+fn cells_are_matched_against_glyphs() {
+    let mut columns = [0; DISPLAY_WIDTH];
+    draw(&mut columns, 0, &GLYPHS[1].1);
+    draw(&mut columns, 1, &GLYPHS[2].1);
+    draw(&mut columns, 25, &GLYPHS[1].1);
+
+    let decoded = display::decode_text(&columns, &GLYPHS);
+    assert_eq!(decoded.text, format!("HI{}H", " ".repeat(23)));
+    assert_eq!(decoded.text.chars().count(), CELLS);
+    assert!(!decoded.has_graphics(), "every cell is a character");
+}
+
+#[test]
+fn graphics_are_kept_apart() {
+    let mut columns = [0; DISPLAY_WIDTH];
+    draw(&mut columns, 0, &GLYPHS[2].1);
+    // A pattern of no glyph, then a glyph running into its spacing column
+    draw(&mut columns, 1, &[0x01, 0x02, 0x04, 0x08, 0x10, 0x20]);
+    draw(&mut columns, 2, &[0x7F, 0x08, 0x08, 0x08, 0x7F, 0x40]);
+
+    let decoded = display::decode_text(&columns, &GLYPHS);
+    let unknown = UNKNOWN_GLYPH.to_string().repeat(2);
+    assert_eq!(decoded.text, format!("I{unknown}{}", " ".repeat(23)));
+
+    let mut graphics = [0; DISPLAY_WIDTH];
+    graphics[CELL_WIDTH..3 * CELL_WIDTH].copy_from_slice(&columns[CELL_WIDTH..3 * CELL_WIDTH]);
+    assert_eq!(decoded.graphics, graphics, "only graphics cells are kept");
+}
+
+#[test]
+fn blank_display_reads_as_spaces() {
+    let decoded = Pc1500::new().display_text();
+    assert_eq!(decoded.text, " ".repeat(CELLS));
+    assert!(!decoded.has_graphics());
+}
+
+#[test]
+fn rom_charset_holds_the_letter_a() {
+    let pc1500 = Pc1500::new();
+    if !pc1500.has_basic_rom() {
+        eprintln!("skipped: the bundled ROM image is a stub that cannot boot");
+        return;
+    }
+
+    assert_eq!(
+        pc1500.glyph(b'A'),
+        Some([0x7E, 0x11, 0x11, 0x11, 0x7E]),
+        "'A' is read from the ROM character generator"
+    );
+    assert_eq!(pc1500.glyph(0x1F), None, "control codes have no glyph");
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+
+use ceres_core::{
+    Pc1500,
+    harness::{BasicHarness, HarnessError},
+    pd1990ac::FREQUENCY,
+    typing::UnsupportedChar,
+    variables::Value,
+};
+
+/// Boots a harness for `source`, or `None` when the ROM cannot boot.
+fn harness(source: &str) -> Result<Option<BasicHarness>, HarnessError> {
+    if !Pc1500::new().has_basic_rom() {
+        eprintln!("skipped: the bundled ROM image is a stub that cannot boot");
+        return Ok(None);
+    }
+    BasicHarness::new(source).map(Some)
+}
+
+#[test]
+fn programs_are_checked_before_booting() {
+    let error = BasicHarness::new("10 PRINT\nPRINT\n").err();
+    assert!(
+        matches!(&error, Some(HarnessError::Program(program)) if program.line() == 2),
+        "the bad line is reported before booting: {error:?}"
+    );
+}
+
+#[test]
+fn input_is_checked_up_front() -> Result<(), Box<dyn Error>> {
+    let Some(mut harness) = harness("10 INPUT A\n")? else {
+        return Ok(());
+    };
+    assert_eq!(harness.set_input("12\n"), Ok(()), "typable input");
+    assert_eq!(
+        harness.set_input("1\u{20ac}\n"),
+        Err(UnsupportedChar('\u{20ac}')),
+        "input that cannot be typed"
+    );
+    Ok(())
+}
+
+#[test]
+fn fixed_variables_are_set_before_the_run() -> Result<(), Box<dyn Error>> {
+    let Some(mut harness) = harness("10 PRINT A;B$\n")? else {
+        return Ok(());
+    };
+    harness.set_variable("A", &Value::Number(2.5))?;
+    harness.set_variable("B$", &Value::String("OK".into()))?;
+    assert!(
+        harness.set_variable("AB", &Value::Number(1.0)).is_err(),
+        "only fixed variables can be set"
+    );
+    assert!(
+        harness
+            .set_variable("C", &Value::String("1".into()))
+            .is_err(),
+        "types must match"
+    );
+
+    let pc1500 = harness.pc1500();
+    assert_eq!(
+        pc1500.variable("A").map(|variable| variable.values),
+        Some(vec![Value::Number(2.5)]),
+        "A is set"
+    );
+    assert_eq!(
+        pc1500.variable("B$").map(|variable| variable.values),
+        Some(vec![Value::String("OK".into())]),
+        "B$ is set"
+    );
+    Ok(())
+}
+
+#[test]
+fn endless_programs_stop_at_the_timeout() -> Result<(), Box<dyn Error>> {
+    let Some(mut harness) = harness("10 GOTO 10\n")? else {
+        return Ok(());
+    };
+    let timeout = 2 * FREQUENCY;
+    harness.set_timeout(timeout);
+
+    let error = harness.run().err();
+    assert!(
+        error.is_some_and(|error| error.output.ticks >= timeout),
+        "the run times out after two emulated seconds"
+    );
+    Ok(())
+}
+
+#[test]
+fn program_output_is_captured() -> Result<(), Box<dyn Error>> {
+    let Some(mut harness) = harness("10 WAIT 0:INPUT \"N?\";N\n20 PRINT N*2\n30 PRINT A$\n")?
+    else {
+        return Ok(());
+    };
+    harness.set_variable("A$", &Value::String("OK".into()))?;
+    harness.set_input("21\n")?;
+
+    let output = harness.run()?;
+    let lines: Vec<&str> = output.display.lines().collect();
+    assert!(lines.ends_with(&["42", "OK"]), "PRINT output: {lines:?}");
+    Ok(())
+}
+
+#[test]
+fn a_rom_that_cannot_boot_is_reported() {
+    if Pc1500::new().has_basic_rom() {
+        return;
+    }
+    assert_eq!(
+        BasicHarness::new("10 END\n").err(),
+        Some(HarnessError::Boot),
+        "the stub ROM never reaches the prompt"
+    );
+}
@@ -0,0 +1,45 @@
+use ceres_core::keyboard::{Key, KeyMatrix, Keyboard};
+
+#[test]
+fn every_key_has_a_unique_position() {
+    let matrix = KeyMatrix::PC1500;
+    for strobe in 0..8 {
+        for bit in 0..8 {
+            let key = matrix.key_at(strobe, bit);
+            assert!(key.is_some(), "matrix position {strobe}/{bit} is wired");
+            let position = key.and_then(|key| matrix.position(key));
+            assert_eq!(position, Some((strobe, bit)), "reverse lookup of {key:?}");
+        }
+    }
+}
+
+#[test]
+fn strobe_selects_row() {
+    let mut keyboard = Keyboard::new();
+    keyboard.press(Key::A);
+
+    keyboard.set_ks(0x40);
+    assert_eq!(!keyboard.input(), 0x08, "A is on KS6, bit 3");
+
+    keyboard.set_ks(0xBF);
+    assert_eq!(!keyboard.input(), 0x00, "A is not visible on other strobes");
+}
+
+#[test]
+fn ghosting_adds_fourth_corner() {
+    let mut keyboard = Keyboard::new();
+    // KS0 bit 0, KS0 bit 1 and KS2 bit 0
+    keyboard.press(Key::Two);
+    keyboard.press(Key::Five);
+    keyboard.press(Key::One);
+
+    keyboard.set_ks(0x04);
+    assert_eq!(!keyboard.input(), 0x01, "without ghosting only 1 is seen");
+
+    keyboard.set_ghosting(true);
+    assert_eq!(
+        !keyboard.input(),
+        0x03,
+        "with ghosting 4 (KS2 bit 1) reads as pressed"
+    );
+}
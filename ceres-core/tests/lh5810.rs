@@ -0,0 +1,297 @@
+use ceres_core::lh5810::{Lh5810, Reg};
+
+// G: internal clock output every 128 ticks
+const CLOCK_128: u8 = 0x10 | 0x02;
+const IF_IRQ: u8 = 0x01;
+const IF_RD: u8 = 0x04;
+const IF_TD: u8 = 0x08;
+const MSK_IRQ: u8 = 0x01;
+const MSK_RD: u8 = 0x04;
+
+/// Steps `chip` tick by tick with SDO wired back to SDI until `done`.
+fn run_loopback(
+    chip: &mut Lh5810,
+    timer_state: &mut usize,
+    ticks: usize,
+    done: impl Fn(&Lh5810) -> bool,
+) -> bool {
+    (0..ticks).any(|_| {
+        chip.set_sdi(chip.get_sdo());
+        chip.step(*timer_state);
+        *timer_state += 1;
+        done(chip)
+    })
+}
+
+#[test]
+fn loopback_receives_transmitted_byte() {
+    let mut chip = Lh5810::new();
+    let mut timer_state = 0;
+    chip.set_reg(Reg::G, CLOCK_128, timer_state);
+    chip.set_reg(Reg::MSK, MSK_RD, timer_state);
+    // Let the line idle high for a few bits
+    run_loopback(&mut chip, &mut timer_state, 1000, |_| false);
+    assert_eq!(
+        chip.get_reg(Reg::IF) & IF_RD,
+        0,
+        "idle line receives nothing"
+    );
+
+    for byte in [0xA5, 0x00, 0xFF, 0x42] {
+        chip.set_reg(Reg::L, byte, timer_state);
+        let received = run_loopback(&mut chip, &mut timer_state, 20 * 128, |chip| {
+            chip.get_reg(Reg::IF) & IF_RD != 0
+        });
+        assert!(received, "RD is flagged for {byte:#04X}");
+        assert!(chip.int(), "unmasked RD interrupts");
+        assert!(
+            chip.get_reg(Reg::IF) & IF_TD != 0,
+            "TD is flagged once the byte is sent"
+        );
+        assert_eq!(chip.get_reg(Reg::U), byte, "U holds the received byte");
+        assert!(
+            chip.get_reg(Reg::IF) & IF_RD != 0,
+            "inspecting U leaves RD alone"
+        );
+        assert_eq!(chip.read_reg(Reg::U), byte, "the CPU reads U");
+        assert_eq!(chip.get_reg(Reg::IF) & IF_RD, 0, "reading U clears RD");
+        // Stop bits
+        run_loopback(&mut chip, &mut timer_state, 2 * 128, |_| false);
+    }
+}
+
+#[test]
+fn masked_rd_does_not_interrupt() {
+    let mut chip = Lh5810::new();
+    let mut timer_state = 0;
+    chip.set_reg(Reg::G, CLOCK_128, timer_state);
+    run_loopback(&mut chip, &mut timer_state, 1000, |_| false);
+    chip.set_reg(Reg::L, 0x5A, timer_state);
+
+    let interrupted = run_loopback(&mut chip, &mut timer_state, 20 * 128, Lh5810::int);
+    assert!(!interrupted, "RD is masked");
+    assert!(chip.get_reg(Reg::IF) & IF_RD != 0, "RD is still flagged");
+}
+
+#[test]
+fn external_clock_shifts_sdi_in() {
+    let mut chip = Lh5810::new();
+    let byte = 0x3C_u8;
+    // Idle, start bit, data bits from bit 0
+    let levels = [true, false]
+        .into_iter()
+        .chain((0..8).map(|bit| byte >> bit & 1 != 0));
+    for (timer_state, level) in levels.enumerate() {
+        chip.set_sdi(level);
+        chip.set_cli(true);
+        chip.step(timer_state);
+        chip.set_cli(false);
+        chip.step(timer_state);
+    }
+    assert!(chip.get_reg(Reg::IF) & IF_RD != 0, "RD after 8 data bits");
+    assert_eq!(chip.get_reg(Reg::U), byte, "bits arrive LSB first");
+}
+
+#[test]
+fn irq_input_is_flagged_and_masked() {
+    let mut chip = Lh5810::new();
+    chip.set_irq(true);
+    chip.step(0);
+    assert!(chip.get_reg(Reg::IF) & IF_IRQ != 0, "IRQ is flagged in IF");
+    assert!(!chip.int(), "masked IRQ does not interrupt");
+
+    chip.set_reg(Reg::MSK, MSK_IRQ, 0);
+    chip.step(1);
+    assert!(chip.int(), "unmasked IRQ interrupts");
+
+    chip.set_irq(false);
+    chip.step(2);
+    assert!(!chip.int(), "released IRQ stops interrupting");
+}
+
+#[test]
+fn msk_reads_input_levels() {
+    let mut chip = Lh5810::new();
+    assert_eq!(chip.get_reg(Reg::MSK) & 0xD0, 0, "inputs are low");
+    chip.set_irq(true);
+    chip.set_sdi(true);
+    chip.set_cli(true);
+    assert_eq!(
+        chip.get_reg(Reg::MSK) & 0xD0,
+        0xD0,
+        "IRQ, SDI and CLI levels read back in bits 4, 6 and 7"
+    );
+}
+
+#[test]
+fn plain_registers_read_back() {
+    let mut chip = Lh5810::new();
+    for reg in [
+        Reg::RESET,
+        Reg::U,
+        Reg::L,
+        Reg::G,
+        Reg::F,
+        Reg::OPC,
+        Reg::DDA,
+        Reg::DDB,
+    ] {
+        for value in [0x00, 0x5A, 0xA5, 0xFF] {
+            chip.set_reg(reg, value, 0);
+            assert_eq!(chip.get_reg(reg), value, "{reg:?} reads back {value:#04X}");
+        }
+    }
+}
+
+#[test]
+fn msk_and_if_keep_writable_bits() {
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::MSK, 0xFF, 0);
+    assert_eq!(
+        chip.get_reg(Reg::MSK),
+        0x0F,
+        "only the mask nibble is stored"
+    );
+
+    chip.set_reg(Reg::IF, 0xFF, 0);
+    assert_eq!(chip.get_reg(Reg::IF), 0x03, "RD and TD cannot be written");
+    chip.set_reg(Reg::IF, 0x00, 0);
+    assert_eq!(chip.get_reg(Reg::IF), 0x00, "IRQ and PB7 flags are cleared");
+}
+
+#[test]
+fn port_direction_masks_writes() {
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::DDA, 0x0F, 0);
+    chip.set_reg(Reg::OPA, 0xFF, 0);
+    assert_eq!(chip.output_a(), 0x0F, "only output pins are driven");
+    assert_eq!(chip.get_reg(Reg::OPA), 0x0F, "output latch reads back");
+
+    // A peripheral drives pins of both directions
+    chip.set_input_a(0x81);
+    assert_eq!(
+        chip.get_reg(Reg::OPA),
+        0x8F,
+        "output pins read the latch, input pins their level"
+    );
+    chip.set_reg(Reg::OPA, 0x00, 0);
+    assert_eq!(chip.get_reg(Reg::OPA), 0x80, "writes do not reach inputs");
+    assert_eq!(chip.output_a(), 0x00, "inputs are not driven");
+
+    // The latch keeps bits written while a pin was an input
+    chip.set_reg(Reg::OPA, 0x50, 0);
+    chip.set_reg(Reg::DDA, 0xFF, 0);
+    assert_eq!(
+        chip.get_reg(Reg::OPA),
+        0x50,
+        "turning a pin to output drives the latch"
+    );
+    assert_eq!(chip.output_a(), 0x50, "and drives it on the pin");
+
+    chip.set_reg(Reg::DDB, 0xF0, 0);
+    chip.set_reg(Reg::OPB, 0xAA, 0);
+    assert_eq!(chip.output_b(), 0xA0, "port B is masked by DDB");
+}
+
+#[test]
+fn opc_changes_are_flagged() {
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::OPC, 0x01, 0);
+    assert!(chip.new_opc(), "writes flag OPC");
+    chip.set_new_opc(false);
+
+    chip.set_reg_bit(Reg::OPC, 2, true);
+    assert!(chip.new_opc(), "setting a bit flags OPC");
+    chip.set_new_opc(false);
+
+    chip.set_reg_bit(Reg::OPC, 0, false);
+    assert!(chip.new_opc(), "clearing a bit flags OPC");
+    assert_eq!(chip.get_reg(Reg::OPC), 0x04, "bits are updated");
+}
+
+/// Ticks between the first rising edges of CLO.
+fn clock_period(g: u8) -> Option<usize> {
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::G, g, 0);
+    let mut rising = Vec::new();
+    let mut clo = false;
+    for timer_state in 0..20_000 {
+        chip.step(timer_state);
+        if chip.get_clo() && !clo {
+            rising.push(timer_state);
+        }
+        clo = chip.get_clo();
+    }
+    match rising.as_slice() {
+        [.., previous, last] => Some(last - previous),
+        _ => None,
+    }
+}
+
+#[test]
+fn g_selects_clock_output_rate() {
+    for (g, period) in [
+        (0x12, 128),
+        (0x13, 256),
+        (0x14, 512),
+        (0x15, 1024),
+        (0x17, 4096),
+    ] {
+        assert_eq!(
+            clock_period(g),
+            Some(period),
+            "G {g:#04X} clocks every {period} ticks"
+        );
+    }
+    assert_eq!(
+        clock_period(0x07),
+        None,
+        "clock output is off without bit 4"
+    );
+}
+
+#[test]
+fn td_flags_and_interrupts_after_transmit() {
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::G, CLOCK_128, 0);
+    chip.set_reg(Reg::MSK, 0x08, 0);
+    chip.step(0);
+    chip.set_reg(Reg::L, 0x12, 0);
+
+    let sent = (1..20 * 128).find(|&timer_state| {
+        chip.step(timer_state);
+        chip.get_reg(Reg::IF) & IF_TD != 0
+    });
+    assert_eq!(
+        sent.map(|ticks| ticks / 128),
+        Some(9),
+        "TD after the start and 8 data bits"
+    );
+    assert!(chip.int(), "unmasked TD interrupts");
+
+    chip.set_reg(Reg::L, 0x34, 20 * 128);
+    chip.step(20 * 128);
+    assert_eq!(chip.get_reg(Reg::IF) & IF_TD, 0, "writing L clears TD");
+}
+
+#[test]
+fn f_modulates_sdo_per_bit() {
+    // Bit 1 toggles every 0x40 << 2 / 2 ticks, bit 0 every 0x40 << 3 / 2
+    let mut chip = Lh5810::new();
+    chip.set_reg(Reg::F, 0x40 | (3 << 3) | 2, 0);
+
+    let mut toggles = Vec::new();
+    let mut sdo = chip.get_sdo();
+    for timer_state in 1..2000 {
+        chip.step(timer_state);
+        if chip.get_sdo() != sdo {
+            toggles.push(timer_state);
+        }
+        sdo = chip.get_sdo();
+    }
+    let periods: Vec<usize> = toggles.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    assert!(
+        !periods.is_empty() && periods.iter().all(|&period| period == 128),
+        "idle line sends the bit 1 tone: {periods:?}"
+    );
+}
@@ -0,0 +1,180 @@
+use ceres_core::{
+    Key, Pc1500,
+    macros::{Delay, KeyMacro, MacroPlayer, MacroStatus, Step},
+    replay::{InputEvent, Recording},
+};
+
+// Longest instruction, the most a step can start late by
+const SLACK: usize = 100;
+
+fn frame_ticks() -> usize {
+    Delay::Frames(1).ticks()
+}
+
+/// Plays `steps` to the end, returning the status and the key events.
+fn play(steps: Vec<Step>) -> (MacroStatus, Vec<InputEvent>) {
+    let mut pc1500 = Pc1500::new();
+    pc1500.start_recording();
+    let status = MacroPlayer::new(KeyMacro::new(steps)).run(&mut pc1500);
+    let events = pc1500
+        .stop_recording()
+        .map(|recording| recording.events().to_vec())
+        .unwrap_or_default();
+    (status, events)
+}
+
+fn ticks_between(events: &[InputEvent], from: usize, to: usize) -> Option<usize> {
+    Some(events.get(to)?.tick - events.get(from)?.tick)
+}
+
+#[test]
+fn parse_and_format_round_trip() {
+    let source = "
+        # Enter RUN mode and call DEF A
+        tap MODE
+        wait 10frames
+        press DEF
+        hold A 150ms
+        release DEF
+        type \"X=\\\"1\\\"\\n\"
+        until \"DONE\" 5000ms
+    ";
+
+    let parsed = KeyMacro::from_text(source);
+    let expected = KeyMacro::new(vec![
+        Step::Hold(Key::Mode, Delay::Millis(100)),
+        Step::Wait(Delay::Frames(10)),
+        Step::Press(Key::Control),
+        Step::Hold(Key::A, Delay::Millis(150)),
+        Step::Release(Key::Control),
+        Step::Type("X=\"1\"\n".to_owned()),
+        Step::Until("DONE".to_owned(), Delay::Millis(5000)),
+    ]);
+    assert_eq!(parsed.as_ref().ok(), Some(&expected), "macro parses");
+
+    let reparsed = KeyMacro::from_text(&expected.to_text());
+    assert_eq!(reparsed.ok(), Some(expected), "formatted macro parses back");
+}
+
+#[test]
+fn reports_invalid_line() {
+    let parsed = KeyMacro::from_text("tap MODE\nhold A\n");
+    assert_eq!(
+        parsed.err().map(|err| err.line()),
+        Some(2),
+        "hold needs a delay"
+    );
+}
+
+#[test]
+fn old_key_names_still_parse() {
+    assert_eq!(
+        "CONTROL".parse(),
+        Ok(Key::Control),
+        "DEF keeps its old name"
+    );
+    assert_eq!("def".parse(), Ok(Key::Control), "names ignore case");
+
+    let recording = Recording::from_text(
+        "PC1500 RECORDING 1\nRTC 2024-01-01T12:00:00\nCLOCK RUNNING\nSTART 0\nEND 100\n10 PRESS CONTROL\n",
+    );
+    assert_eq!(
+        recording
+            .ok()
+            .and_then(|recording| recording.events().first().map(|event| event.key)),
+        Some(Key::Control),
+        "recordings made before the rename load"
+    );
+}
+
+#[test]
+fn hold_keeps_the_key_down_for_its_delay() {
+    let hold = Delay::Millis(150);
+    let (status, events) = play(vec![Step::Hold(Key::A, hold), Step::Press(Key::B)]);
+
+    assert_eq!(status, MacroStatus::Finished, "macro finishes");
+    let keys: Vec<(Key, bool)> = events
+        .iter()
+        .map(|event| (event.key, event.pressed))
+        .collect();
+    assert_eq!(
+        keys,
+        [(Key::A, true), (Key::A, false), (Key::B, true)],
+        "A is held, then B pressed"
+    );
+    let held = ticks_between(&events, 0, 1).unwrap_or_default();
+    assert!(
+        (hold.ticks()..hold.ticks() + SLACK).contains(&held),
+        "A is held for 150 ms, not {held} ticks"
+    );
+    assert_eq!(
+        ticks_between(&events, 1, 2),
+        Some(0),
+        "next step follows at once"
+    );
+}
+
+#[test]
+fn wait_delays_the_next_step() {
+    let wait = Delay::Frames(10);
+    let (status, events) = play(vec![
+        Step::Press(Key::A),
+        Step::Wait(wait),
+        Step::Release(Key::A),
+    ]);
+
+    assert_eq!(status, MacroStatus::Finished, "macro finishes");
+    let waited = ticks_between(&events, 0, 1).unwrap_or_default();
+    assert_eq!(wait.ticks(), 10 * frame_ticks(), "frames are whole frames");
+    assert!(
+        (wait.ticks()..wait.ticks() + SLACK).contains(&waited),
+        "release waits 10 frames, not {waited} ticks"
+    );
+}
+
+#[test]
+fn until_fails_after_its_timeout() {
+    let timeout = Delay::Millis(100);
+    let mut pc1500 = Pc1500::new();
+    let start = pc1500.cpu().get_ticks();
+    let mut player = MacroPlayer::new(KeyMacro::new(vec![
+        Step::Press(Key::A),
+        // No glyph exists for this code, so it is never displayed
+        Step::Until("\u{1}".to_owned(), timeout),
+        Step::Release(Key::A),
+    ]));
+
+    assert_eq!(
+        player.run(&mut pc1500),
+        MacroStatus::TimedOut { step: 1 },
+        "the until step times out"
+    );
+    // The display is only checked once per frame
+    let elapsed = pc1500.cpu().get_ticks() - start;
+    assert!(
+        (timeout.ticks()..timeout.ticks() + 2 * frame_ticks()).contains(&elapsed),
+        "gives up after 100 ms, not {elapsed} ticks"
+    );
+}
+
+#[test]
+fn until_continues_once_the_text_shows() {
+    let mut pc1500 = Pc1500::new();
+    let start = pc1500.cpu().get_ticks();
+    let mut player = MacroPlayer::new(KeyMacro::new(vec![
+        // A blank cell shows a space with any character set
+        Step::Until(" ".to_owned(), Delay::Millis(5000)),
+        Step::Press(Key::A),
+    ]));
+
+    assert_eq!(
+        player.run(&mut pc1500),
+        MacroStatus::Finished,
+        "macro finishes"
+    );
+    let elapsed = pc1500.cpu().get_ticks() - start;
+    assert!(
+        elapsed <= 2 * frame_ticks() + SLACK,
+        "text is seen on the next frame, not after {elapsed} ticks"
+    );
+}
@@ -0,0 +1,53 @@
+use ceres_core::{Pc1500, output};
+
+const CALLER: u16 = 0x4000;
+const STACK: u16 = 0x7700;
+
+/// Calls the ROM `routine` with `a` in A, stopping at its first instruction.
+fn call(pc1500: &mut Pc1500, routine: u16, a: u8) {
+    let [routine_low, routine_high] = routine.to_le_bytes();
+    // SJP routine
+    let program = [0xBE, routine_high, routine_low];
+    for (addr, &byte) in (u32::from(CALLER)..).zip(&program) {
+        pc1500.write_byte(addr, byte);
+    }
+
+    let cpu = pc1500.cpu_mut();
+    cpu.cancel_reset();
+    cpu.set_pc(CALLER);
+    cpu.set_s(STACK);
+    cpu.set_a(a);
+    pc1500.step_cpu();
+}
+
+fn print(pc1500: &mut Pc1500, text: &[u8]) {
+    call(pc1500, output::BCMD_PRINT, 0);
+    for &byte in text {
+        call(pc1500, output::CHAR_OUT, byte);
+    }
+}
+
+#[test]
+fn nothing_is_captured_by_default() {
+    let mut pc1500 = Pc1500::new();
+    print(&mut pc1500, b"HI");
+
+    assert!(!pc1500.is_capturing_output());
+    assert_eq!(pc1500.take_display_output(), "");
+}
+
+#[test]
+fn display_output_is_taken_once() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.enable_output_capture();
+
+    print(&mut pc1500, b"HELLO");
+    print(&mut pc1500, b"A=\x01");
+
+    assert_eq!(pc1500.take_display_output(), "HELLO\nA=[01]");
+    assert_eq!(pc1500.take_display_output(), "", "text is taken once");
+
+    print(&mut pc1500, b"BYE");
+    pc1500.disable_output_capture();
+    assert_eq!(pc1500.take_display_output(), "", "capture is dropped");
+}
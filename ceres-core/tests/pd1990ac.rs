@@ -0,0 +1,271 @@
+use ceres_core::{
+    ClockTime,
+    pd1990ac::{
+        FREQUENCY, INTERVAL_RESET, INTERVAL_STOP, Pd1990ac, REGISTER_HOLD, REGISTER_SHIFT, SERIAL,
+        SERIAL_TEST_MODE, TEST_MODE, TIME_READ, TIME_SET, TP_1S_INTERVAL, TP_64HZ, TP_256HZ,
+        TP_2048HZ, TP_4096HZ, Variant,
+    },
+};
+use chrono::NaiveDate;
+
+// Wednesday 14 March, 12:34:56 in the shift register order of the
+// datasheet: seconds, minutes, hours, day, weekday, month
+const REGISTER: u64 = 0x0033_1412_3456;
+
+/// A selected chip with its output enabled, driven one pin change at a time.
+struct Chip {
+    pd1990ac: Pd1990ac,
+    timer_state: usize,
+}
+
+impl Chip {
+    fn new() -> Self {
+        Self::with_variant(Variant::Upd1990ac)
+    }
+
+    fn with_variant(variant: Variant) -> Self {
+        let datetime = NaiveDate::from_ymd_opt(1984, 3, 14)
+            .and_then(|date| date.and_hms_opt(12, 34, 56))
+            .unwrap_or_default();
+        let mut pd1990ac = Pd1990ac::with_datetime(datetime);
+        pd1990ac.set_variant(variant);
+        pd1990ac.set_cs(true);
+        pd1990ac.set_out_enable(true);
+        let mut chip = Self {
+            pd1990ac,
+            timer_state: 1,
+        };
+        chip.step(0);
+        chip
+    }
+
+    fn step(&mut self, ticks: usize) {
+        self.timer_state += ticks;
+        self.pd1990ac.step(self.timer_state);
+    }
+
+    /// Strobes `command` in on C2 C1 C0.
+    fn command(&mut self, command: u8) {
+        self.pd1990ac.set_c0(command & 1 != 0);
+        self.pd1990ac.set_c1(command & 2 != 0);
+        self.pd1990ac.set_c2(command & 4 != 0);
+        self.step(10);
+        self.pd1990ac.set_stb(true);
+        self.step(10);
+        self.pd1990ac.set_stb(false);
+        self.step(10);
+    }
+
+    /// Shifts the 4 bits of `command` into the command register of a
+    /// uPD4990A, LSB first, and executes it.
+    fn serial_command(&mut self, command: u8) {
+        self.command(SERIAL);
+        for bit in 0..4 {
+            self.pd1990ac.set_data(command >> bit & 1 != 0);
+            self.clock();
+        }
+        self.command(SERIAL);
+    }
+
+    fn clock(&mut self) {
+        self.pd1990ac.set_clk(true);
+        self.step(10);
+        self.pd1990ac.set_clk(false);
+        self.step(10);
+    }
+
+    /// Reads 40 bits, sampling DATA OUT before each rising edge of CLK.
+    fn read_register(&mut self) -> u64 {
+        (0..40).fold(0, |register, bit| {
+            let data = u64::from(self.pd1990ac.get_data());
+            self.clock();
+            register | data << bit
+        })
+    }
+
+    /// Rising edges of TP over `ticks`.
+    fn tp_rising_edges(&mut self, ticks: usize) -> usize {
+        let mut previous = self.pd1990ac.get_tp(self.timer_state);
+        (0..ticks / 10)
+            .filter(|_| {
+                self.timer_state += 10;
+                let tp = self.pd1990ac.get_tp(self.timer_state);
+                let rising = tp && !previous;
+                previous = tp;
+                rising
+            })
+            .count()
+    }
+}
+
+#[test]
+fn time_is_read_out_lsb_first() {
+    let mut chip = Chip::new();
+    chip.command(TIME_READ);
+    chip.command(REGISTER_SHIFT);
+    assert_eq!(
+        chip.read_register(),
+        REGISTER,
+        "seconds units come out first and the month last"
+    );
+}
+
+#[test]
+fn time_is_set_from_the_shift_register() {
+    let mut chip = Chip::new();
+    // 31 December, Monday, 23:59:58
+    let register: u64 = 0x00C1_3123_5958;
+    chip.command(REGISTER_SHIFT);
+    for bit in 0..40 {
+        chip.pd1990ac.set_data(register >> bit & 1 != 0);
+        chip.clock();
+    }
+    chip.command(TIME_SET);
+    chip.command(REGISTER_HOLD);
+
+    let time = ClockTime {
+        month: 12,
+        day: 31,
+        weekday: 1,
+        hour: 23,
+        minute: 59,
+        second: 58,
+    };
+    assert_eq!(chip.pd1990ac.time(), time, "the counters are loaded");
+    chip.step(FREQUENCY - 100);
+    assert_eq!(chip.pd1990ac.time(), time, "time set starts a new second");
+    chip.step(200);
+    assert_eq!(chip.pd1990ac.time().second, 59, "the second has passed");
+}
+
+#[test]
+fn hold_and_read_output_1_hz() {
+    let mut chip = Chip::new();
+    for command in [REGISTER_HOLD, TIME_READ] {
+        chip.command(TIME_SET);
+        chip.command(command);
+        assert!(chip.pd1990ac.get_data(), "high for the first half second");
+        chip.step(FREQUENCY / 2);
+        assert!(!chip.pd1990ac.get_data(), "low for the second half");
+        chip.step(FREQUENCY / 2);
+        assert!(chip.pd1990ac.get_data(), "high again");
+    }
+}
+
+#[test]
+fn tp_follows_the_frequency_commands() {
+    let mut chip = Chip::with_variant(Variant::Upd4990a);
+    for (command, frequency) in [(TP_64HZ, 64), (TP_256HZ, 256), (TP_2048HZ, 2048)] {
+        chip.command(command);
+        let edges = chip.tp_rising_edges(FREQUENCY);
+        // Periods are whole ticks
+        assert!(
+            edges.abs_diff(frequency) <= frequency / 500 + 1,
+            "{edges} rising edges in a second, expected {frequency}"
+        );
+    }
+
+    chip.serial_command(TP_4096HZ);
+    let edges = chip.tp_rising_edges(FREQUENCY / 4);
+    assert!(
+        edges.abs_diff(1024) <= 3,
+        "{edges} rising edges in a quarter second at 4096 Hz"
+    );
+}
+
+#[test]
+fn interval_timer_can_be_stopped_and_reset() {
+    let mut chip = Chip::with_variant(Variant::Upd4990a);
+    chip.serial_command(TP_1S_INTERVAL);
+    assert!(chip.pd1990ac.get_tp(chip.timer_state), "TP starts high");
+    assert_eq!(chip.tp_rising_edges(3 * FREQUENCY), 3, "one pulse a second");
+
+    chip.step(FREQUENCY * 3 / 4);
+    assert!(
+        !chip.pd1990ac.get_tp(chip.timer_state),
+        "low in the second half"
+    );
+    chip.serial_command(INTERVAL_STOP);
+    assert_eq!(chip.tp_rising_edges(2 * FREQUENCY), 0, "stopped timer");
+    chip.serial_command(INTERVAL_RESET);
+    assert!(
+        chip.pd1990ac.get_tp(chip.timer_state),
+        "reset starts a new interval"
+    );
+}
+
+#[test]
+fn test_mode_counts_fast() {
+    let mut chip = Chip::new();
+    chip.command(TEST_MODE);
+    chip.step(FREQUENCY);
+    let time = chip.pd1990ac.time();
+    assert_eq!(
+        (time.hour, time.minute, time.second),
+        (12, 52, 0),
+        "1024 seconds pass in a second"
+    );
+
+    chip.command(REGISTER_HOLD);
+    chip.step(FREQUENCY);
+    assert_eq!(chip.pd1990ac.time().second, 1, "normal counting resumes");
+}
+
+#[test]
+fn test_mode_is_a_serial_command_on_the_4990a() {
+    let mut chip = Chip::with_variant(Variant::Upd4990a);
+    // 111 selects the command register, which holds REGISTER_HOLD
+    chip.command(SERIAL);
+    chip.step(FREQUENCY);
+    assert_eq!(chip.pd1990ac.time().second, 57, "111 alone is not TEST");
+
+    chip.serial_command(SERIAL_TEST_MODE);
+    chip.step(FREQUENCY);
+    let time = chip.pd1990ac.time();
+    assert_eq!(
+        (time.hour, time.minute, time.second),
+        (12, 52, 1),
+        "1024 seconds pass in a second"
+    );
+}
+
+#[test]
+fn the_1990ac_has_no_command_register() {
+    let mut chip = Chip::new();
+    chip.command(TIME_READ);
+    chip.command(REGISTER_SHIFT);
+    // Clocking with 111 on the pins shifts the time register out
+    chip.pd1990ac.set_c0(true);
+    chip.pd1990ac.set_c1(true);
+    chip.pd1990ac.set_c2(true);
+    chip.clock();
+    chip.clock();
+    chip.clock();
+    chip.clock();
+    assert_eq!(
+        chip.read_register(),
+        REGISTER >> 4,
+        "bits are shifted out of the time register"
+    );
+}
+
+#[test]
+fn deselected_chip_ignores_commands() {
+    let mut chip = Chip::new();
+    chip.command(TIME_READ);
+    chip.command(REGISTER_SHIFT);
+    chip.pd1990ac.set_cs(false);
+    chip.command(REGISTER_HOLD);
+    chip.clock();
+    chip.pd1990ac.set_cs(true);
+    assert_eq!(
+        chip.read_register(),
+        REGISTER,
+        "neither the command nor the clock were taken"
+    );
+
+    chip.command(TIME_READ);
+    chip.command(REGISTER_SHIFT);
+    chip.pd1990ac.set_out_enable(false);
+    assert!(!chip.pd1990ac.get_data(), "disabled DATA OUT reads low");
+}
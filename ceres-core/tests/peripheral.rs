@@ -0,0 +1,135 @@
+use ceres_core::{
+    Pc1500,
+    peripheral::{Bank, BusCycle, Peripheral},
+};
+
+const ME1: u32 = 0x1_0000;
+
+/// A RAM card on ME0 with a control latch on ME1.
+struct RamCard {
+    ram: Vec<u8>,
+    latch: Option<BusCycle>,
+    steps: usize,
+    interrupt: bool,
+}
+
+impl RamCard {
+    const BEGIN: u16 = 0x2000;
+    const END: u16 = 0x27FF;
+    const LATCH: u16 = 0x8000;
+
+    fn new() -> Self {
+        Self {
+            ram: vec![0; usize::from(Self::END - Self::BEGIN) + 1],
+            latch: None,
+            steps: 0,
+            interrupt: false,
+        }
+    }
+}
+
+impl Peripheral for RamCard {
+    fn read(&self, cycle: &BusCycle) -> Option<u8> {
+        cycle
+            .selects(Bank::Me0, Self::BEGIN, Self::END)
+            .then(|| self.ram.get(usize::from(cycle.addr - Self::BEGIN)))
+            .flatten()
+            .copied()
+    }
+
+    fn write(&mut self, cycle: &BusCycle, value: u8) -> bool {
+        if cycle.selects(Bank::Me1, Self::LATCH, Self::LATCH) {
+            self.latch = Some(*cycle);
+            self.interrupt = value != 0;
+            return true;
+        }
+        let Some(byte) = cycle
+            .selects(Bank::Me0, Self::BEGIN, Self::END)
+            .then(|| self.ram.get_mut(usize::from(cycle.addr - Self::BEGIN)))
+            .flatten()
+        else {
+            return false;
+        };
+        *byte = value;
+        true
+    }
+
+    fn step(&mut self, _timer_state: usize) {
+        self.steps += 1;
+    }
+
+    fn interrupt(&self) -> bool {
+        self.interrupt
+    }
+}
+
+#[test]
+fn bus_cycles_reach_attached_peripheral() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.write_byte(0x2010, 0x42);
+    assert_eq!(
+        pc1500.read_byte(0x2010),
+        0xFF,
+        "nothing answers before attaching"
+    );
+
+    pc1500.attach(RamCard::new());
+    pc1500.write_byte(0x2010, 0x42);
+    assert_eq!(pc1500.read_byte(0x2010), 0x42, "ME0 reaches the card");
+    assert_eq!(
+        pc1500.read_byte(ME1 | 0x2010),
+        0xFF,
+        "the same address on ME1 does not"
+    );
+
+    pc1500.write_byte(ME1 | u32::from(RamCard::LATCH), 0);
+    let latch = pc1500.peripheral::<RamCard>().and_then(|card| card.latch);
+    assert!(
+        latch.is_some_and(|cycle| cycle.bank == Bank::Me1 && cycle.addr == RamCard::LATCH),
+        "ME1 cycles carry the bank and address: {latch:?}"
+    );
+    assert!(
+        latch.is_some_and(|cycle| !cycle.pu && !cycle.pv),
+        "PU and PV are low after reset"
+    );
+}
+
+#[test]
+fn peripherals_step_and_interrupt() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(RamCard::new());
+    pc1500.step_frame();
+    assert!(
+        pc1500
+            .peripheral::<RamCard>()
+            .is_some_and(|card| card.steps > 0),
+        "peripheral is stepped"
+    );
+    assert!(!pc1500.cpu().ir2(), "no interrupt requested");
+
+    pc1500.write_byte(ME1 | u32::from(RamCard::LATCH), 1);
+    pc1500.step_frame();
+    assert!(pc1500.cpu().ir2(), "interrupt output raises IR2");
+}
+
+#[test]
+fn detach_returns_peripheral_state() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(RamCard::new());
+    if let Some(card) = pc1500.peripheral_mut::<RamCard>() {
+        card.ram[0] = 0x99;
+    }
+    assert_eq!(pc1500.read_byte(0x2000), 0x99, "mutable access");
+
+    let card = pc1500.detach::<RamCard>();
+    assert_eq!(
+        card.and_then(|card| card.ram.first().copied()),
+        Some(0x99),
+        "detached card keeps its contents"
+    );
+    assert!(
+        pc1500.peripheral::<RamCard>().is_none(),
+        "card is unplugged"
+    );
+    assert_eq!(pc1500.read_byte(0x2000), 0xFF, "bus is open again");
+}
@@ -0,0 +1,145 @@
+use ceres_core::{
+    ClockTime, Key, Pc1500,
+    clock::ClockSource,
+    replay::{Recording, Replay},
+};
+
+fn ram(pc1500: &Pc1500) -> Vec<u8> {
+    (0x4000..=0x57FF)
+        .chain(0x7600..=0x7FFF)
+        .map(|addr| pc1500.read_byte(addr))
+        .collect()
+}
+
+fn rtc_start() -> chrono::NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(1984, 3, 14)
+        .and_then(|date| date.and_hms_opt(9, 30, 0))
+        .unwrap_or_default()
+}
+
+#[test]
+fn replay_reproduces_recorded_session() {
+    let mut pc1500 = Pc1500::with_rtc_time(rtc_start());
+    pc1500.start_recording();
+
+    for _ in 0..60 {
+        pc1500.step_frame();
+    }
+
+    for key in [Key::Mode, Key::Two, Key::Plus, Key::Three, Key::Enter] {
+        pc1500.press(key);
+        for _ in 0..5 {
+            pc1500.step_frame();
+        }
+        pc1500.release(key);
+        for _ in 0..5 {
+            pc1500.step_frame();
+        }
+    }
+
+    let recording = pc1500.stop_recording();
+    assert!(recording.is_some(), "recording was started");
+    let Some(recording) = recording else { return };
+
+    let parsed = Recording::from_text(&recording.to_text());
+    assert_eq!(
+        parsed.as_ref().ok(),
+        Some(&recording),
+        "text format round-trips"
+    );
+    let Ok(parsed) = parsed else { return };
+
+    let replay = Replay::new(parsed);
+    assert!(replay.is_ok(), "recording started at power-on");
+    let Ok(mut replay) = replay else { return };
+    let mut replayed = replay.machine();
+    replay.run_to_end(&mut replayed);
+
+    assert!(replay.is_finished(&replayed), "all events were applied");
+    assert_eq!(
+        replayed.cpu().get_ticks(),
+        pc1500.cpu().get_ticks(),
+        "replay stops at the recorded tick"
+    );
+    assert_eq!(ram(&replayed), ram(&pc1500), "final RAM matches");
+}
+
+#[test]
+fn replay_applies_host_clock_changes() {
+    let mut pc1500 = Pc1500::with_clock(ClockSource::Manual(rtc_start()));
+    pc1500.start_recording();
+
+    for _ in 0..30 {
+        pc1500.step_frame();
+    }
+    pc1500.set_clock_time(ClockTime {
+        month: 12,
+        day: 31,
+        weekday: 2,
+        hour: 23,
+        minute: 59,
+        second: 58,
+    });
+    for _ in 0..30 {
+        pc1500.step_frame();
+    }
+
+    let recording = pc1500.stop_recording();
+    assert!(recording.is_some(), "recording was started");
+    let Some(recording) = recording else { return };
+    assert_eq!(
+        recording.clock_source(),
+        ClockSource::Manual(rtc_start()),
+        "the manual clock is recorded"
+    );
+    assert_eq!(
+        recording.clock_events().len(),
+        1,
+        "the clock change is recorded"
+    );
+
+    let parsed = Recording::from_text(&recording.to_text());
+    assert_eq!(
+        parsed.as_ref().ok(),
+        Some(&recording),
+        "text format round-trips"
+    );
+    let Ok(parsed) = parsed else { return };
+
+    let replay = Replay::new(parsed);
+    assert!(replay.is_ok(), "recording started at power-on");
+    let Ok(mut replay) = replay else { return };
+    let mut replayed = replay.machine();
+    replay.run_to_end(&mut replayed);
+
+    assert!(replay.is_finished(&replayed), "all events were applied");
+    assert_eq!(
+        replayed.clock_time(),
+        pc1500.clock_time(),
+        "the clock matches"
+    );
+    assert_eq!(ram(&replayed), ram(&pc1500), "final RAM matches");
+}
+
+#[test]
+fn mid_session_recordings_are_not_replayed() {
+    let mut pc1500 = Pc1500::with_rtc_time(rtc_start());
+    pc1500.step_frame();
+    pc1500.start_recording();
+    pc1500.press(Key::Mode);
+    pc1500.step_frame();
+
+    let recording = pc1500.stop_recording();
+    let start_tick = recording.as_ref().map(Recording::start_tick);
+    assert!(
+        start_tick.is_some_and(|tick| tick > 0),
+        "the start tick is kept"
+    );
+    assert_eq!(
+        recording
+            .and_then(|recording| Replay::new(recording).err())
+            .map(|err| err.start_tick()),
+        start_tick,
+        "replay is refused"
+    );
+}
@@ -0,0 +1,130 @@
+use ceres_core::{ClockTime, Pc1500, clock::ClockSource};
+use chrono::{Local, NaiveDate, NaiveDateTime, Utc};
+
+const PROGRAM: u16 = 0x4000;
+const STACK: u16 = 0x7700;
+const TICKS_PER_SECOND: usize = 1_300_000;
+
+/// A machine with its clock at `datetime`, spinning in RAM so the ROM leaves
+/// the clock alone.
+fn machine(year: i32, month: u32, day: u32, hms: (u32, u32, u32)) -> Pc1500 {
+    spinning(ClockSource::Fixed(datetime(year, month, day, hms)))
+}
+
+fn datetime(year: i32, month: u32, day: u32, hms: (u32, u32, u32)) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hms.0, hms.1, hms.2))
+        .unwrap_or_default()
+}
+
+fn spinning(source: ClockSource) -> Pc1500 {
+    let mut pc1500 = Pc1500::with_clock(source);
+    // BCH -2
+    pc1500.write_byte(u32::from(PROGRAM), 0x9E);
+    pc1500.write_byte(u32::from(PROGRAM) + 1, 0x02);
+    let cpu = pc1500.cpu_mut();
+    cpu.cancel_reset();
+    cpu.set_pc(PROGRAM);
+    cpu.set_s(STACK);
+    pc1500
+}
+
+fn run_seconds(pc1500: &mut Pc1500, seconds: usize) {
+    let start = pc1500.cpu().get_ticks();
+    while pc1500.cpu().get_ticks() - start < seconds * TICKS_PER_SECOND {
+        pc1500.step_frame();
+    }
+}
+
+#[test]
+fn clock_advances_with_emulated_time() {
+    let mut pc1500 = machine(2024, 6, 15, (10, 20, 30));
+    let start = pc1500.clock_time();
+    run_seconds(&mut pc1500, 2);
+    assert_eq!(
+        pc1500.clock_time(),
+        ClockTime {
+            second: 32,
+            ..start
+        },
+        "two seconds have passed"
+    );
+}
+
+#[test]
+fn year_rolls_over_at_midnight() {
+    // Tuesday
+    let mut pc1500 = machine(2024, 12, 31, (23, 59, 59));
+    run_seconds(&mut pc1500, 1);
+    assert_eq!(
+        pc1500.clock_time(),
+        ClockTime {
+            month: 1,
+            day: 1,
+            weekday: 3,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        },
+        "every counter carries into the next"
+    );
+}
+
+#[test]
+fn months_have_their_length() {
+    for (month, day) in [(2, 28), (4, 30), (7, 31)] {
+        let mut pc1500 = machine(2023, month, day, (23, 59, 59));
+        run_seconds(&mut pc1500, 1);
+        let time = pc1500.clock_time();
+        assert_eq!(
+            (time.month, time.day),
+            (u8::try_from(month).unwrap_or_default() + 1, 1),
+            "{month}/{day} is the last day of the month"
+        );
+    }
+}
+
+#[test]
+fn weekday_wraps_after_saturday() {
+    // Saturday
+    let mut pc1500 = machine(2024, 6, 15, (23, 59, 59));
+    assert_eq!(pc1500.clock_time().weekday, 6, "starts on Saturday");
+    run_seconds(&mut pc1500, 1);
+    assert_eq!(pc1500.clock_time().weekday, 0, "Sunday follows");
+}
+
+/// Whether a machine created with `source` starts at the host time `now`.
+fn starts_at(source: ClockSource, now: fn() -> NaiveDateTime) -> bool {
+    let before = ClockTime::from(now());
+    let time = Pc1500::with_clock(source).clock_time();
+    time == before || time == ClockTime::from(now())
+}
+
+#[test]
+fn host_sources_start_at_host_time() {
+    assert!(
+        starts_at(ClockSource::HostLocal, || Local::now().naive_local()),
+        "local time is used"
+    );
+    assert!(
+        starts_at(ClockSource::HostUtc, || Utc::now().naive_utc()),
+        "UTC is used"
+    );
+}
+
+#[test]
+fn manual_clock_only_changes_when_set() {
+    let start = datetime(1985, 3, 14, (9, 26, 53));
+    let mut pc1500 = spinning(ClockSource::Manual(start));
+    run_seconds(&mut pc1500, 2);
+    assert_eq!(
+        pc1500.clock_time(),
+        ClockTime::from(start),
+        "the clock stands still"
+    );
+
+    let set = ClockTime::from(datetime(1985, 7, 1, (12, 0, 0)));
+    pc1500.set_clock_time(set);
+    run_seconds(&mut pc1500, 1);
+    assert_eq!(pc1500.clock_time(), set, "the set time is kept");
+}
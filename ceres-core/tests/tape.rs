@@ -0,0 +1,263 @@
+use std::error::Error;
+
+use ceres_core::{
+    Pc1500,
+    ce150::Ce150,
+    harness::BasicHarness,
+    pd1990ac::FREQUENCY,
+    tape::{DEFAULT_SAMPLE_RATE, TapeDeck, TapeState, demodulate},
+    tape_image::{FileKind, NO_ENTRY, TapeFile, TapeImage},
+};
+
+// CE-150 LH5810 MSK on ME1, bit 6 reads SDI
+const CE150_MSK: u32 = 0x1B00A;
+const SDI: u8 = 0x40;
+// PC-1500 LH5810 port B, bit 5 reads TP of the PD1990AC
+const LH5810_OPB: u32 = 0x1F00F;
+const TP: u8 = 0x20;
+const FRAMES_PER_SECOND: usize = FREQUENCY / 15_000;
+
+fn image(bytes: &[u8]) -> TapeImage {
+    TapeImage::new(vec![TapeFile::new(
+        FileKind::Machine,
+        "TAPE",
+        0x7C01,
+        NO_ENTRY,
+        bytes.to_vec(),
+    )])
+}
+
+/// The tape audio of `image` and the same audio as a WAV file.
+fn modulated(image: &TapeImage) -> (Vec<i16>, Vec<u8>) {
+    let mut wav = Vec::new();
+    let written = image.write_wav(&mut wav, DEFAULT_SAMPLE_RATE);
+    assert!(written.is_ok(), "WAV is written to memory");
+    (image.to_samples(DEFAULT_SAMPLE_RATE), wav)
+}
+
+fn wav_from_samples(samples: &[i16]) -> Vec<u8> {
+    let data_size = u32::try_from(samples.len() * 2).unwrap_or_default();
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(data_size + 36).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes());
+    wav.extend_from_slice(&44_100_u32.to_le_bytes());
+    wav.extend_from_slice(&88_200_u32.to_le_bytes());
+    wav.extend_from_slice(&2_u16.to_le_bytes());
+    wav.extend_from_slice(&16_u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Classifies each half period of a square wave as a short (false) or long
+/// (true) tone. The silence at both ends is dropped with the half periods
+/// next to it, which a demodulator may stretch into it.
+fn tones(samples: &[i16]) -> Vec<bool> {
+    let first = samples.iter().position(|&sample| sample != 0);
+    let last = samples.iter().rposition(|&sample| sample != 0);
+    let (Some(first), Some(last)) = (first, last) else {
+        return Vec::new();
+    };
+
+    let mut runs = Vec::new();
+    let mut length = 0_usize;
+    let mut level = samples[first] > 0;
+    for &sample in &samples[first..=last] {
+        if (sample > 0) != level {
+            runs.push(length);
+            length = 0;
+            level = !level;
+        }
+        length += 1;
+    }
+
+    let inner = runs.get(1..).unwrap_or_default();
+    // Most half periods belong to the short tone
+    let mut sorted = inner.to_vec();
+    sorted.sort_unstable();
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or_default();
+    inner.iter().map(|&run| run > median * 3 / 2).collect()
+}
+
+fn step_seconds(pc1500: &mut Pc1500, seconds: usize) {
+    for _ in 0..seconds * FRAMES_PER_SECOND {
+        pc1500.step_frame();
+    }
+}
+
+#[test]
+fn records_silence_without_a_ce150() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.tape_mut().record();
+    pc1500.step_frame();
+    pc1500.tape_mut().stop();
+
+    let samples = pc1500.tape().samples();
+    assert!(!samples.is_empty(), "tape runs");
+    assert!(
+        samples.iter().all(|&sample| sample == 0),
+        "nothing drives the tape output"
+    );
+}
+
+#[test]
+fn recorded_files_load_back() {
+    let image = image(&[0x00, 0xFF, 0x55, 0xAA, 0x01, 0x80, 0x3C]);
+    let (_, wav) = modulated(&image);
+
+    let mut tape = TapeDeck::default();
+    assert!(tape.load_wav(wav.as_slice()).is_ok(), "WAV loads");
+    assert_eq!(tape.to_image(), image, "files survive the WAV round trip");
+}
+
+#[test]
+fn loads_wavs_with_oversized_chunks() {
+    let (original, wav) = modulated(&image(&[0x42]));
+
+    // Streaming recorders leave the sizes unset
+    let mut streamed = wav.clone();
+    streamed[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    streamed[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut tape = TapeDeck::default();
+    assert!(tape.load_wav(streamed.as_slice()).is_ok(), "WAV loads");
+    assert_eq!(
+        tones(tape.samples()),
+        tones(&original),
+        "all samples are read"
+    );
+    let full_length = tape.samples().len();
+
+    // A recording cut off in the silence after its end
+    let cut = wav.get(..wav.len() - 1001).unwrap_or_default();
+    assert!(tape.load_wav(cut).is_ok(), "cut WAV loads");
+    // Demodulation may round the last half period by a sample
+    assert!(
+        tape.samples().len().abs_diff(full_length - 501) <= 1,
+        "samples up to the cut are read"
+    );
+}
+
+#[test]
+fn demodulates_clean_recording() {
+    let (original, wav) = modulated(&image(&[0x55, 0x00, 0xFF, 0xA7]));
+
+    let mut tape = TapeDeck::default();
+    assert!(tape.load_wav(wav.as_slice()).is_ok(), "WAV loads");
+    assert_eq!(
+        tones(tape.samples()),
+        tones(&original),
+        "clean recording is unchanged"
+    );
+}
+
+#[test]
+fn tolerates_amplitude_polarity_and_drift() {
+    let image = image(&[0x12, 0x34, 0x56, 0x78]);
+    let (modulated, _) = modulated(&image);
+    // Without the silence around the file, which an off-center signal
+    // would turn into a stray half period
+    let original: Vec<i16> = modulated
+        .into_iter()
+        .skip_while(|&sample| sample == 0)
+        .collect();
+    let expected = tones(&original);
+
+    // Quiet, inverted, off-center and speeding up by 4% along the tape
+    let len = original.len();
+    let mut distorted = Vec::new();
+    let mut position = 0;
+    while position < len * 1000 {
+        let index = position / 1000;
+        let sample = i32::from(original[index]);
+        distorted.push(i16::try_from(-sample / 5 + 1000).unwrap_or_default());
+        position += 1000 + 40 * index / len;
+    }
+
+    let distorted_wav = wav_from_samples(&distorted);
+    let mut tape = TapeDeck::default();
+    assert!(tape.load_wav(distorted_wav.as_slice()).is_ok(), "WAV loads");
+    assert_eq!(tones(tape.samples()), expected, "tones survive distortion");
+    assert_eq!(
+        tones(&demodulate(&distorted)),
+        expected,
+        "demodulate matches load_wav"
+    );
+    assert_eq!(tape.to_image(), image, "files survive distortion");
+}
+
+#[test]
+fn plays_into_sdi_beside_tp() {
+    let (_, wav) = modulated(&image(&[0xC3]));
+
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(Ce150::new());
+    assert!(
+        pc1500.tape_mut().load_wav(wav.as_slice()).is_ok(),
+        "WAV loads"
+    );
+    pc1500.tape_mut().play();
+
+    let mut edges = 0;
+    let mut level = false;
+    let mut tp_levels = [false; 2];
+    while pc1500.tape().state() == TapeState::Playing {
+        pc1500.step_frame();
+        let input = pc1500.read_byte(CE150_MSK) & SDI != 0;
+        assert_eq!(input, pc1500.tape().input(), "CE-150 sees the tape");
+        if input != level {
+            level = input;
+            edges += 1;
+        }
+        let tp = pc1500.read_byte(LH5810_OPB) & TP != 0;
+        tp_levels[usize::from(tp)] = true;
+    }
+
+    assert!(edges > 0, "tape input toggles while playing");
+    assert_eq!(tp_levels, [true; 2], "TP runs while the tape plays");
+    assert_eq!(
+        pc1500.tape().position(),
+        pc1500.tape().samples().len(),
+        "tape stops at its end"
+    );
+}
+
+#[test]
+fn csave_wav_cload_round_trip() -> Result<(), Box<dyn Error>> {
+    if !Pc1500::new().has_basic_rom() {
+        eprintln!("skipped: the bundled ROM image is a stub that cannot boot");
+        return Ok(());
+    }
+    let source = "10 PRINT \"TAPE\"\n20 END\n";
+
+    let mut saving = BasicHarness::new(source)?;
+    let saver = saving.pc1500_mut();
+    saver.attach(Ce150::new());
+    saver.tape_mut().record();
+    saver.type_text("CSAVE \"T\"\n")?;
+    step_seconds(saver, 20);
+    saver.tape_mut().stop();
+    let mut wav = Vec::new();
+    saver.tape().write_wav(&mut wav)?;
+
+    let mut loading = BasicHarness::new("")?;
+    let loader = loading.pc1500_mut();
+    loader.attach(Ce150::new());
+    loader.tape_mut().load_wav(wav.as_slice())?;
+    loader.tape_mut().play();
+    loader.type_text("CLOAD \"T\"\n")?;
+    step_seconds(loader, 20);
+
+    assert_eq!(
+        loading.pc1500().list_program()?,
+        saving.pc1500().list_program()?,
+        "the program is loaded from the recording"
+    );
+    Ok(())
+}
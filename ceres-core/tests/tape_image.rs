@@ -0,0 +1,166 @@
+use ceres_core::{
+    Pc1500,
+    ce150::Ce150,
+    tape::TapeDeck,
+    tape_image::{FileKind, NO_ENTRY, TapeFile, TapeImage},
+};
+
+// CE-150 LH5810 registers on ME1
+const LH5810_L: u32 = 0x1B006;
+const LH5810_F: u32 = 0x1B007;
+const LH5810_G: u32 = 0x1B009;
+
+// Header and its checksum
+const HEADER_BYTES: usize = 34;
+// The CE-150 tape setup: modulated output with a one every 256 ticks and a
+// zero every 512, one bit every 2048 ticks with the clock running
+const TAPE_F: u8 = 0x40 | (4 << 3) | 3;
+const TAPE_G: u8 = 0x10 | 6;
+
+fn sample_image() -> TapeImage {
+    let program = (0..200_u8).map(|n| n.wrapping_mul(7)).collect();
+    TapeImage::new(vec![
+        TapeFile::new(FileKind::Basic, "HELLO", 0x40C5, NO_ENTRY, program),
+        TapeFile::new(
+            FileKind::Machine,
+            "ROUTINE",
+            0x7C01,
+            0x7C10,
+            vec![0xA5, 0x9A],
+        ),
+        TapeFile::new(FileKind::Data, "", 0, 0, Vec::new()),
+    ])
+}
+
+#[test]
+fn image_bytes_round_trip() {
+    let image = sample_image();
+    let bytes = image.to_bytes();
+
+    assert_eq!(
+        bytes.get(..6),
+        Some([0xA1, b'H', b'E', b'L', b'L', b'O'].as_slice()),
+        "image holds the bytes recorded on tape"
+    );
+    assert_eq!(
+        bytes.get(26..34),
+        Some([0x40, 0xC5, 0x00, 200, 0xFF, 0xFF, 0x05, 0xE0].as_slice()),
+        "addresses and length follow the reserved bytes, then the checksum"
+    );
+    assert_eq!(
+        TapeImage::from_bytes(&bytes).ok(),
+        Some(image),
+        "image survives serialization"
+    );
+}
+
+#[test]
+fn long_names_are_truncated() {
+    let file = TapeFile::new(
+        FileKind::Basic,
+        "A VERY LONG PROGRAM NAME",
+        0,
+        0,
+        Vec::new(),
+    );
+    assert_eq!(file.name(), "A VERY LONG PROG", "name keeps 16 characters");
+}
+
+#[test]
+fn names_read_back_as_created() {
+    let file = TapeFile::new(FileKind::Basic, "CAF\u{E9} \u{2603}", 0, 0, Vec::new());
+    assert_eq!(file.name(), "CAF? ?", "non-ASCII characters become ?");
+
+    let image = TapeImage::new(vec![file.clone()]);
+    let read = TapeImage::from_bytes(&image.to_bytes()).unwrap_or_default();
+    assert_eq!(
+        read.files().first(),
+        Some(&file),
+        "name survives a round trip"
+    );
+    assert!(file.header_ok(), "header checksum covers the stored name");
+}
+
+#[test]
+fn detects_damaged_blocks() {
+    let image = sample_image();
+    let mut bytes = image.to_bytes();
+    bytes[HEADER_BYTES + 100] ^= 0x10;
+
+    let damaged = TapeImage::from_bytes(&bytes).unwrap_or_default();
+    let checksums: Vec<bool> = damaged.files().iter().map(TapeFile::checksums_ok).collect();
+    assert_eq!(
+        checksums,
+        [false, true, true],
+        "only the damaged file fails"
+    );
+}
+
+#[test]
+fn wav_round_trip() {
+    let image = sample_image();
+
+    for sample_rate in [22_050, 44_100, 48_000] {
+        let mut wav = Vec::new();
+        assert!(
+            image.write_wav(&mut wav, sample_rate).is_ok(),
+            "WAV is written to memory"
+        );
+        assert_eq!(
+            TapeImage::read(wav.as_slice()).ok().as_ref(),
+            Some(&image),
+            "files survive modulation at {sample_rate} Hz"
+        );
+    }
+}
+
+#[test]
+fn decodes_lh5810_modulation() {
+    let file = TapeFile::new(
+        FileKind::Machine,
+        "BYTES",
+        0x7C01,
+        0x7C01,
+        vec![0x00, 0xFF, 0x3C],
+    );
+    let bytes = TapeImage::new(vec![file.clone()]).to_bytes();
+    let bursts = bytes.chunks(HEADER_BYTES).take(2);
+
+    let mut pc1500 = Pc1500::new();
+    pc1500.attach(Ce150::new());
+    pc1500.tape_mut().record();
+    pc1500.write_byte(LH5810_G, TAPE_G);
+    pc1500.write_byte(LH5810_F, TAPE_F);
+    for burst in bursts {
+        // Leader
+        for _ in 0..20 {
+            pc1500.step_frame();
+        }
+        for &byte in burst {
+            pc1500.write_byte(LH5810_L, byte);
+            for _ in 0..2 {
+                pc1500.step_frame();
+            }
+        }
+    }
+    pc1500.tape_mut().stop();
+
+    assert_eq!(
+        pc1500.tape().to_image().files(),
+        [file],
+        "bytes sent by the LH5810 are decoded"
+    );
+}
+
+#[test]
+fn deck_loads_images_and_wavs() {
+    let image = sample_image();
+    let mut wav = Vec::new();
+    assert!(image.write_wav(&mut wav, 44_100).is_ok(), "WAV is written");
+
+    for source in [image.to_bytes(), wav] {
+        let mut tape = TapeDeck::default();
+        assert!(tape.load(source.as_slice()).is_ok(), "tape loads");
+        assert_eq!(tape.to_image(), image, "tape holds the image files");
+    }
+}
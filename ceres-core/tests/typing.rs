@@ -0,0 +1,150 @@
+use ceres_core::{
+    Key, Pc1500,
+    replay::InputEvent,
+    typing::{self, UnsupportedChar, keys_for_char},
+};
+
+const SCAN_CALLER: u16 = 0x4000;
+
+/// Jumps into the ROM keyboard routine at `addr`, as the ROM does while
+/// waiting for input.
+fn scan(pc1500: &mut Pc1500, addr: u16) {
+    let [low, high] = addr.to_le_bytes();
+    // JMP addr
+    for (cell, byte) in (u32::from(SCAN_CALLER)..).zip([0xBA, high, low]) {
+        pc1500.write_byte(cell, byte);
+    }
+    let cpu = pc1500.cpu_mut();
+    cpu.cancel_reset();
+    cpu.set_pc(SCAN_CALLER);
+    pc1500.step_cpu();
+}
+
+/// Key changes made while the ROM runs `scans`, as (key, pressed) pairs.
+fn strokes(pc1500: &mut Pc1500, scans: &[u16]) -> Vec<(Key, bool)> {
+    pc1500.start_recording();
+    for &addr in scans {
+        scan(pc1500, addr);
+    }
+    pc1500
+        .stop_recording()
+        .map(|recording| {
+            recording
+                .events()
+                .iter()
+                .map(|&InputEvent { key, pressed, .. }| (key, pressed))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn characters_map_to_key_strokes() {
+    assert_eq!(keys_for_char('A'), Some([Key::A].as_slice()), "letter");
+    assert_eq!(
+        keys_for_char('a'),
+        Some([Key::A].as_slice()),
+        "lowercase is typed as uppercase"
+    );
+    assert_eq!(
+        keys_for_char('\n'),
+        Some([Key::Enter].as_slice()),
+        "newline"
+    );
+    assert_eq!(
+        keys_for_char('"'),
+        Some([Key::Shift, Key::F2].as_slice()),
+        "SHIFT is a separate stroke"
+    );
+    assert_eq!(
+        keys_for_char('$'),
+        Some([Key::Shift, Key::F4].as_slice()),
+        "dollar is shifted"
+    );
+    assert_eq!(
+        keys_for_char('+'),
+        Some([Key::Plus].as_slice()),
+        "plus has its own key"
+    );
+    assert_eq!(keys_for_char('~'), None, "tilde has no key");
+}
+
+#[test]
+fn untypable_text_is_rejected_whole() {
+    let mut pc1500 = Pc1500::new();
+    assert_eq!(
+        pc1500.type_text("PRINT ~1"),
+        Err(UnsupportedChar('~')),
+        "the offending character is reported"
+    );
+    assert!(!pc1500.is_typing(), "nothing before it is queued");
+    assert_eq!(pc1500.type_text("10 A=1\r\n"), Ok(()), "CR is ignored");
+}
+
+#[test]
+fn keys_are_held_until_the_rom_scans_again() {
+    let mut pc1500 = Pc1500::new();
+    assert_eq!(pc1500.type_text("A\""), Ok(()), "text is queued");
+
+    let scans = [
+        typing::WAIT_4_KB,
+        // Polling while the key is down does not release it
+        typing::ISKEY,
+        typing::WAIT_4_KB,
+        // The next key waits for the released matrix to be scanned
+        typing::WAIT_4_KB,
+        typing::ISKEY,
+        typing::ISKEY,
+        typing::WAIT_4_KB,
+        typing::ISKEY,
+        typing::WAIT_4_KB,
+    ];
+    assert_eq!(
+        strokes(&mut pc1500, &scans),
+        [
+            (Key::A, true),
+            (Key::A, false),
+            (Key::Shift, true),
+            (Key::Shift, false),
+            (Key::F2, true),
+        ],
+        "one key at a time, each released before the next"
+    );
+    assert!(pc1500.is_typing(), "F2 is still held");
+
+    assert_eq!(
+        strokes(&mut pc1500, &[typing::WAIT_4_KB, typing::ISKEY]),
+        [(Key::F2, false)],
+        "last key is released"
+    );
+    assert!(!pc1500.is_typing(), "queue is drained");
+}
+
+#[test]
+fn special_keys_are_typed_and_cancelled() {
+    let mut pc1500 = Pc1500::new();
+    pc1500.type_keys(&[Key::Mode, Key::Cl]);
+
+    assert_eq!(
+        strokes(&mut pc1500, &[typing::WAIT_4_KB]),
+        [(Key::Mode, true)],
+        "MODE is pressed"
+    );
+    pc1500.start_recording();
+    pc1500.cancel_typing();
+    let released = pc1500
+        .stop_recording()
+        .map(|recording| recording.events().len());
+    assert_eq!(released, Some(1), "cancelling releases the held key");
+    assert!(!pc1500.is_typing(), "CL is dropped");
+}
+
+#[test]
+fn only_keys_of_characters_are_text_keys() {
+    for key in [Key::A, Key::Shift, Key::F2, Key::Enter] {
+        assert!(typing::is_text_key(key), "{key} types text");
+    }
+    for key in [Key::On, Key::Off, Key::Mode, Key::Up] {
+        assert!(!typing::is_text_key(key), "{key} is pressed directly");
+    }
+}
@@ -0,0 +1,87 @@
+use ceres_core::{
+    Pc1500,
+    variables::{Value, Variable},
+};
+
+fn poke(pc1500: &mut Pc1500, addr: u32, bytes: &[u8]) {
+    for (byte_addr, &byte) in (addr..).zip(bytes) {
+        pc1500.write_byte(byte_addr, byte);
+    }
+}
+
+#[test]
+fn fixed_variables_are_decoded() {
+    let mut pc1500 = Pc1500::new();
+    // C = 42, Z = -1.5, A$ = "HI", Z$ = "PC-1500"
+    poke(&mut pc1500, 0x7910, &[0x01, 0x00, 0x42, 0, 0, 0, 0, 0]);
+    poke(&mut pc1500, 0x79C8, &[0x00, 0x80, 0x15, 0, 0, 0, 0, 0]);
+    poke(&mut pc1500, 0x78C0, b"HI\0");
+    poke(&mut pc1500, 0x77F0, b"PC-1500\0");
+
+    let variables = pc1500.fixed_variables();
+    assert_eq!(variables.len(), 52, "A to Z and A$ to Z$");
+    for (name, value) in [
+        ("C", Value::Number(42.0)),
+        ("Z", Value::Number(-1.5)),
+        ("A$", Value::String("HI".into())),
+        ("Z$", Value::String("PC-1500".into())),
+    ] {
+        assert_eq!(
+            pc1500.variable(name).map(|variable| variable.values),
+            Some(vec![value]),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn dynamic_variables_and_arrays_are_decoded() {
+    let mut pc1500 = Pc1500::new();
+    let entries = [
+        // AB = 0.25
+        &[b'A', b'B', 0x00, 0x00, 0x08][..],
+        &[0xFF, 0x00, 0x25, 0, 0, 0, 0, 0],
+        // DIM B$(1)*4, B$(0) = "X", B$(1) = "YZ"
+        &[b'B', 0x00, 0x81, 0x00, 0x0A, 0x01, 0x04],
+        b"X\0\0\0YZ\0\0",
+        // DIM M(1, 0), M(1, 0) = 7
+        &[b'M', 0x00, 0x02, 0x00, 0x12, 0x01, 0x00],
+        &[0; 8],
+        &[0x00, 0x00, 0x70, 0, 0, 0, 0, 0],
+        // Cut short, not decoded
+        &[b'Q', 0x00, 0x00, 0x00, 0x08, 0x00],
+    ]
+    .concat();
+    poke(&mut pc1500, 0x5000, &entries);
+    poke(&mut pc1500, 0x7899, &[0x50, 0x00, 0x50, 0x39]);
+
+    assert_eq!(
+        pc1500.dynamic_variables(),
+        [
+            Variable {
+                name: "AB".into(),
+                dimensions: Vec::new(),
+                values: vec![Value::Number(0.25)],
+            },
+            Variable {
+                name: "B$".into(),
+                dimensions: vec![2],
+                values: vec![Value::String("X".into()), Value::String("YZ".into())],
+            },
+            Variable {
+                name: "M".into(),
+                dimensions: vec![2, 1],
+                values: vec![Value::Number(0.0), Value::Number(7.0)],
+            },
+        ]
+    );
+}
+
+#[test]
+fn pointers_outside_ram_give_no_variables() {
+    let pc1500 = Pc1500::new();
+    assert!(
+        pc1500.dynamic_variables().is_empty(),
+        "pointers start unset"
+    );
+}
@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Players tried in order, for messages telling what to install
+pub const PLAYERS: &str = "aplay or pacat";
+
+/// Plays emulator audio through a host command-line PCM player, so no
+/// audio library is needed. The first player found on the host is used.
+pub struct AudioPlayer {
+    child: Child,
+    samples: Sender<Vec<i16>>,
+}
+
+impl AudioPlayer {
+    pub fn start(sample_rate: u32) -> Option<Self> {
+        let rate = sample_rate.to_string();
+        let players: [(&str, Vec<String>); 2] = [
+            (
+                "aplay",
+                ["-q", "-t", "raw", "-f", "S16_LE", "-c", "1", "-r", &rate]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            (
+                "pacat",
+                vec![
+                    "--raw".to_owned(),
+                    "--format=s16le".to_owned(),
+                    "--channels=1".to_owned(),
+                    format!("--rate={rate}"),
+                ],
+            ),
+        ];
+
+        let mut child = players.iter().find_map(|(program, args)| {
+            Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()
+        })?;
+        let mut stdin = child.stdin.take()?;
+
+        // Writes block once the player's buffer is full, keep them off the UI thread
+        let (samples, receiver) = mpsc::channel::<Vec<i16>>();
+        thread::spawn(move || {
+            for chunk in receiver {
+                let bytes: Vec<u8> = chunk
+                    .iter()
+                    .flat_map(|sample| sample.to_le_bytes())
+                    .collect();
+                if stdin.write_all(&bytes).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Some(Self { child, samples })
+    }
+
+    /// Whether the player is still running, it exits when the host audio
+    /// server goes away.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    pub fn play(&self, samples: Vec<i16>) {
+        if !samples.is_empty() {
+            // The player thread only stops when the player exits
+            drop(self.samples.send(samples));
+        }
+    }
+}
+
+impl Drop for AudioPlayer {
+    fn drop(&mut self) {
+        drop(self.child.kill());
+    }
+}
@@ -1,4 +1,6 @@
+mod audio;
 mod pc1500_app;
+mod settings;
 
 use eframe::egui;
 use pc1500_app::Pc1500App;
@@ -1,15 +1,27 @@
+use crate::audio::{self, AudioPlayer};
+use crate::settings::Settings;
 use ceres_core::Pc1500;
+use ceres_core::battery::BatteryBackup;
+use ceres_core::bcd;
 use ceres_core::keyboard::Key as Pc1500Key;
+use ceres_core::macros::{KeyMacro, MacroPlayer, MacroStatus};
+use ceres_core::typing;
+use ceres_core::variables::{Value, Variable};
 use eframe::egui;
 use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 pub struct Pc1500App {
     // CORE EMULATOR - The real PC-1500 system
     emulator: Pc1500,
 
-    // KEYBOARD STATE - Full PC-1500 keyboard with timing
+    // KEYBOARD STATE - Keys held on the PC keyboard, on-screen text keys
+    // typed in step with the ROM keyboard scan, and other on-screen keys
+    // held for a few frames
     pressed_keys: HashSet<Pc1500Key>,
-    key_press_timers: std::collections::HashMap<Pc1500Key, std::time::Instant>,
+    typed_keys: HashSet<Pc1500Key>,
+    held_clicks: Vec<(Pc1500Key, usize)>,
 
     // DISPLAY - Full display system
     display_buffer: Vec<u8>,
@@ -22,22 +34,122 @@ pub struct Pc1500App {
 
     // PHYSICAL KEYBOARD MAPPING - Map PC keyboard to PC-1500 keys
     pc_to_pc1500_mapping: std::collections::HashMap<egui::Key, Pc1500Key>,
+
+    // MACROS - F9 records live input, the next key pressed plays it back.
+    // Bindings are kept in the settings, None when the host has no config
+    // directory
+    unbound_macro: Option<KeyMacro>,
+    macro_player: Option<MacroPlayer>,
+    settings: Settings,
+    settings_path: Option<PathBuf>,
+
+    // AUDIO - Buzzer output, None when no host player is available
+    audio: Option<AudioPlayer>,
+
+    // WATCH PANEL - BASIC variables, toggled with F8
+    show_variables: bool,
+
+    // BATTERY BACKUP - Saved every BATTERY_SAVE_INTERVAL and on exit, None
+    // when the host has no data directory
+    battery_path: Option<PathBuf>,
+    last_battery_save: Instant,
+
+    // STATUS - Errors worth telling the user about, one line each, shown
+    // under the display until dismissed
+    status: Vec<String>,
+}
+
+const RECORD_MACRO_KEY: egui::Key = egui::Key::F9;
+const DISCARD_MACRO_KEY: egui::Key = egui::Key::Escape;
+const WATCH_PANEL_KEY: egui::Key = egui::Key::F8;
+// Emulated frames an on-screen key other than a text key stays down
+const CLICK_HOLD_FRAMES: usize = 6;
+
+// RAM and clock offset kept between sessions, like the PC-1500 batteries
+// do, in the per-user data directory
+const BATTERY_BACKUP_DIR: &str = "ceres";
+const BATTERY_BACKUP_FILE: &str = "pc1500.battery";
+// A crash loses at most this much of a session
+const BATTERY_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+fn battery_path() -> Option<PathBuf> {
+    Some(
+        dirs::data_dir()?
+            .join(BATTERY_BACKUP_DIR)
+            .join(BATTERY_BACKUP_FILE),
+    )
+}
+
+fn restore_battery(emulator: &mut Pc1500, path: &Path) -> Result<(), String> {
+    match BatteryBackup::open(path) {
+        Ok(backup) => {
+            emulator.restore_battery_backup(&backup);
+            Ok(())
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("Could not restore {}: {err}", path.display())),
+    }
+}
+
+/// Writes next to `path` first, so a crash while saving leaves the previous
+/// backup in place.
+fn save_battery(emulator: &Pc1500, path: &Path) -> Result<(), String> {
+    let temp = path.with_extension("tmp");
+    path.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| emulator.battery_backup().save(&temp))
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(|err| format!("Could not save {}: {err}", path.display()))
 }
 
 impl Pc1500App {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let emulator = Pc1500::new();
+        let mut emulator = Pc1500::new();
+
+        let battery_path = battery_path();
+        let battery = battery_path.as_ref().map_or_else(
+            || Err("No data directory, memory is not kept between sessions".to_owned()),
+            |path| restore_battery(&mut emulator, path),
+        );
+
+        let settings_path = Settings::path();
+        let settings = settings_path.as_ref().map_or_else(
+            || Err("No config directory, macros are not kept between sessions".to_owned()),
+            |path| Settings::load(path),
+        );
+
+        let audio = AudioPlayer::start(audio::SAMPLE_RATE);
+        let mut status: Vec<String> = battery.err().into_iter().collect();
+        status.extend(settings.as_ref().err().cloned());
+        if audio.is_some() {
+            emulator.enable_audio(audio::SAMPLE_RATE);
+        } else {
+            status.push(format!(
+                "No audio player found ({}), sound is off",
+                audio::PLAYERS
+            ));
+        }
 
         Self {
             emulator,
             pressed_keys: HashSet::new(),
-            key_press_timers: std::collections::HashMap::new(),
+            typed_keys: HashSet::new(),
+            held_clicks: Vec::new(),
             display_buffer: vec![0; 156 * 7 * 4], // RGBA buffer
             display_width: 156,
             display_height: 7,
             display_scale: 6.0,
             symbol_states: [false; 14],
             pc_to_pc1500_mapping: Self::create_keyboard_mapping(),
+            unbound_macro: None,
+            macro_player: None,
+            settings: settings.unwrap_or_default(),
+            settings_path,
+            audio,
+            show_variables: false,
+            battery_path,
+            last_battery_save: Instant::now(),
+            status,
         }
     }
 
@@ -122,8 +234,29 @@ impl Pc1500App {
     }
 
     fn update_emulator(&mut self) {
-        // Step the emulator
-        self.emulator.step_frame();
+        // Step the emulator, driven by the macro player while one is running
+        if let Some(player) = &mut self.macro_player {
+            let status = player.step_frame(&mut self.emulator);
+            if status != MacroStatus::Running {
+                if let MacroStatus::TimedOut { step } | MacroStatus::InvalidText { step } = status {
+                    self.report(format!("Macro failed at step {}", step + 1));
+                }
+                self.macro_player = None;
+            }
+        } else {
+            self.emulator.step_frame();
+        }
+        self.release_clicked_keys();
+
+        if let Some(audio) = &mut self.audio {
+            if audio.is_running() {
+                audio.play(self.emulator.take_audio());
+            } else {
+                self.audio = None;
+                self.emulator.disable_audio();
+                self.report("The audio player stopped, sound is off".to_owned());
+            }
+        }
 
         // Update display buffer
         let display = self.emulator.display();
@@ -182,6 +315,45 @@ impl Pc1500App {
         });
     }
 
+    // Watch panel with every BASIC variable, read again each frame
+    fn render_variables(&self, ui: &mut egui::Ui) {
+        ui.heading("Variables");
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("variables").striped(true).show(ui, |ui| {
+                for variable in self.emulator.variables() {
+                    ui.monospace(Self::variable_label(&variable));
+                    let values: Vec<String> =
+                        variable.values.iter().map(Self::value_text).collect();
+                    ui.monospace(values.join(", "));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    // Arrays are labelled as DIM declares them, with the largest index
+    fn variable_label(variable: &Variable) -> String {
+        if !variable.is_array() {
+            return variable.name.clone();
+        }
+        let largest: Vec<String> = variable
+            .dimensions
+            .iter()
+            .map(|count| count.saturating_sub(1).to_string())
+            .collect();
+        format!("{}({})", variable.name, largest.join(","))
+    }
+
+    // Twelve digits survive the f64, so the codec gives back the stored value
+    fn value_text(value: &Value) -> String {
+        match value {
+            Value::Number(number) => bcd::from_f64(*number)
+                .and_then(bcd::to_decimal)
+                .unwrap_or_else(|| "?".to_owned()),
+            Value::String(text) => format!("{text:?}"),
+        }
+    }
+
     fn render_symbols(&mut self, ui: &mut egui::Ui) {
         use ceres_core::display::Symbol;
 
@@ -206,6 +378,16 @@ impl Pc1500App {
                 ("●", Symbol::Battery),
             ];
 
+            match (self.emulator.is_recording(), self.macro_player.is_some()) {
+                (true, _) => {
+                    ui.colored_label(egui::Color32::RED, "REC");
+                }
+                (false, true) => {
+                    ui.colored_label(egui::Color32::DARK_GREEN, "MACRO");
+                }
+                (false, false) => {}
+            }
+
             for (label, symbol) in symbols.iter() {
                 let is_on = self.symbol_states[*symbol as usize];
                 let color = if is_on {
@@ -226,7 +408,9 @@ impl Pc1500App {
 
             // Helper function to create keyboard button
             let mut create_key = |ui: &mut egui::Ui, key: Pc1500Key, label: &str, width: f32| {
-                let is_pressed = self.pressed_keys.contains(&key);
+                let is_pressed = self.pressed_keys.contains(&key)
+                    || self.typed_keys.contains(&key)
+                    || self.held_clicks.iter().any(|&(held, _)| held == key);
                 let button_color = if is_pressed {
                     egui::Color32::YELLOW
                 } else {
@@ -340,30 +524,41 @@ impl Pc1500App {
                 create_key(ui, Pc1500Key::Right, "Right", 25.0);
             });
 
-            // Process clicked keys from virtual keyboard
-            for key in clicked_keys {
-                self.send_key_press(key);
-            }
+            self.click_keys(clicked_keys);
         });
     }
 
-    fn send_key_press(&mut self, key: Pc1500Key) {
-        // Add key to pressed set for visual feedback
-        self.pressed_keys.insert(key);
+    // Text keys are held until the ROM has read them. The others, like
+    // BREAK, are polled while programs run, so they go down right away.
+    fn click_keys(&mut self, keys: Vec<Pc1500Key>) {
+        let (text_keys, other_keys): (Vec<_>, Vec<_>) =
+            keys.into_iter().partition(|&key| typing::is_text_key(key));
+        self.emulator.type_keys(&text_keys);
+        self.typed_keys.extend(text_keys);
+        for key in other_keys {
+            self.emulator.press(key);
+            self.held_clicks.push((key, CLICK_HOLD_FRAMES));
+        }
+    }
 
-        // Record the press time for auto-release
-        self.key_press_timers.insert(key, std::time::Instant::now());
+    fn release_clicked_keys(&mut self) {
+        for (key, frames) in &mut self.held_clicks {
+            *frames = frames.saturating_sub(1);
+            if *frames == 0 {
+                self.emulator.release(*key);
+            }
+        }
+        self.held_clicks.retain(|&(_, frames)| frames > 0);
+    }
 
-        // Send key press to emulator
+    // PC keys stay down on the PC-1500 for as long as they are held
+    fn send_key_press(&mut self, key: Pc1500Key) {
+        self.pressed_keys.insert(key);
         self.emulator.press(key);
     }
 
     fn send_key_release(&mut self, key: Pc1500Key) {
-        // Remove key from pressed set
         self.pressed_keys.remove(&key);
-        self.key_press_timers.remove(&key);
-
-        // Send key release to emulator
         self.emulator.release(key);
     }
 
@@ -371,6 +566,21 @@ impl Pc1500App {
         // Check for physical keyboard input
         ctx.input(|i| {
             for event in &i.events {
+                if let egui::Event::Key {
+                    key, pressed: true, ..
+                } = event
+                {
+                    if let Some(key_macro) = self.unbound_macro.take() {
+                        self.bind_macro(*key, key_macro);
+                        continue;
+                    }
+                    match *key {
+                        RECORD_MACRO_KEY => self.toggle_macro_recording(),
+                        WATCH_PANEL_KEY => self.show_variables = !self.show_variables,
+                        key => self.play_macro(key),
+                    }
+                }
+
                 if let egui::Event::Key { key, pressed, .. } = event {
                     if let Some(&pc1500_key) = self.pc_to_pc1500_mapping.get(key) {
                         if *pressed {
@@ -380,44 +590,154 @@ impl Pc1500App {
                         }
                     }
                 }
+
+                // Pasted text is typed in step with the ROM keyboard scan
+                if let egui::Event::Paste(text) = event
+                    && let Err(err) = self.emulator.type_text(text)
+                {
+                    self.report(format!("Cannot paste text: {err}"));
+                }
             }
         });
     }
 
-    fn update_key_timers(&mut self) {
-        // Auto-release keys after 150ms for visual feedback
-        let now = std::time::Instant::now();
-        let keys_to_release: Vec<Pc1500Key> = self
-            .key_press_timers
-            .iter()
-            .filter(|(_, press_time)| now.duration_since(**press_time).as_millis() > 150)
-            .map(|(key, _)| *key)
-            .collect();
+    fn toggle_macro_recording(&mut self) {
+        if self.emulator.is_recording() {
+            self.unbound_macro = self
+                .emulator
+                .stop_recording()
+                .map(|recording| KeyMacro::from_recording(&recording));
+        } else {
+            self.emulator.start_recording();
+        }
+    }
+
+    // Keys of the PC-1500 keyboard and the app's own keys cannot play macros
+    fn bind_macro(&mut self, key: egui::Key, key_macro: KeyMacro) {
+        if key == DISCARD_MACRO_KEY {
+            return;
+        }
+        if self.pc_to_pc1500_mapping.contains_key(&key)
+            || [RECORD_MACRO_KEY, WATCH_PANEL_KEY].contains(&key)
+        {
+            self.report(format!(
+                "{} is already in use, press another key",
+                key.name()
+            ));
+            self.unbound_macro = Some(key_macro);
+            return;
+        }
+        self.settings.bind_macro(key, key_macro);
+        self.save_settings();
+    }
+
+    fn play_macro(&mut self, key: egui::Key) {
+        if self.macro_player.is_none() && !self.emulator.is_recording() {
+            self.macro_player = self.settings.macro_for(key).cloned().map(MacroPlayer::new);
+        }
+    }
+
+    fn save_settings(&mut self) {
+        if let Some(path) = &self.settings_path
+            && let Err(err) = self.settings.save(path)
+        {
+            self.report(err);
+        }
+    }
 
-        for key in keys_to_release {
-            self.send_key_release(key);
+    fn render_macros(&mut self, ui: &mut egui::Ui) {
+        if self.unbound_macro.is_some() {
+            ui.label(format!(
+                "Press a key to play the recorded macro with, {} discards it",
+                DISCARD_MACRO_KEY.name()
+            ));
+        }
+        let mut unbound = None;
+        ui.horizontal(|ui| {
+            for binding in self.settings.macros() {
+                ui.monospace(binding.key.name());
+                if ui.small_button("Remove").clicked() {
+                    unbound = Some(binding.key);
+                }
+            }
+        });
+        if let Some(key) = unbound {
+            self.settings.unbind_macro(key);
+            self.save_settings();
+        }
+    }
+
+    fn save_battery_periodically(&mut self) {
+        if self.last_battery_save.elapsed() < BATTERY_SAVE_INTERVAL {
+            return;
+        }
+        self.last_battery_save = Instant::now();
+
+        if let Some(path) = &self.battery_path
+            && let Err(err) = save_battery(&self.emulator, path)
+        {
+            self.report(err);
+        }
+    }
+
+    /// Adds a status line, unless the same message is already shown.
+    fn report(&mut self, message: String) {
+        if !self.status.contains(&message) {
+            self.status.push(message);
+        }
+    }
+
+    fn render_status(&mut self, ui: &mut egui::Ui) {
+        let mut dismissed = None;
+        for (index, message) in self.status.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, message);
+                if ui.small_button("OK").clicked() {
+                    dismissed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = dismissed {
+            self.status.remove(index);
         }
     }
 }
 
 impl eframe::App for Pc1500App {
+    fn on_exit(&mut self) {
+        if let Some(path) = &self.battery_path
+            && let Err(err) = save_battery(&self.emulator, path)
+        {
+            eprintln!("{err}");
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Handle physical keyboard input FIRST
         self.handle_physical_keyboard(ctx);
 
-        // Update key timers for visual feedback
-        self.update_key_timers();
-
         // Update emulator
         self.update_emulator();
+        self.save_battery_periodically();
+
+        // On-screen keys light up until the ROM has read them all
+        if !self.emulator.is_typing() {
+            self.typed_keys.clear();
+        }
 
         // Request continuous repaints for smooth animation
         ctx.request_repaint();
 
+        if self.show_variables {
+            egui::SidePanel::right("variables").show(ctx, |ui| self.render_variables(ui));
+        }
+
         // Main UI
         egui::CentralPanel::default().show(ctx, |ui| {
             // Main display
             self.render_main_display(ui);
+            self.render_status(ui);
+            self.render_macros(ui);
 
             ui.separator();
 
@@ -0,0 +1,123 @@
+// App settings kept between sessions in the per-user config directory
+
+use ceres_core::macros::KeyMacro;
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+const SETTINGS_DIR: &str = "ceres";
+const SETTINGS_FILE: &str = "settings.txt";
+// Starts the steps of the macro bound to a key, e.g. `[macro F10]`
+const MACRO_HEADER: &str = "macro ";
+
+pub struct MacroBinding {
+    pub key: egui::Key,
+    pub key_macro: KeyMacro,
+}
+
+/// Settings file contents. Each macro is a section holding its steps in the
+/// [`KeyMacro`] text format:
+///
+/// ```text
+/// [macro F10]
+/// tap MODE
+/// type "RUN\n"
+/// ```
+#[derive(Default)]
+pub struct Settings {
+    macros: Vec<MacroBinding>,
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join(SETTINGS_DIR).join(SETTINGS_FILE))
+    }
+
+    /// Missing settings are not an error, the defaults are used.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_text(&text)
+                .map_err(|err| format!("Could not read {}: {err}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("Could not read {}: {err}", path.display())),
+        }
+    }
+
+    /// Writes next to `path` first, so a crash while saving leaves the
+    /// previous settings in place.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let temp = path.with_extension("tmp");
+        path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&temp, self.to_text()))
+            .and_then(|()| std::fs::rename(&temp, path))
+            .map_err(|err| format!("Could not save {}: {err}", path.display()))
+    }
+
+    pub fn macros(&self) -> &[MacroBinding] {
+        &self.macros
+    }
+
+    pub fn macro_for(&self, key: egui::Key) -> Option<&KeyMacro> {
+        self.macros
+            .iter()
+            .find(|binding| binding.key == key)
+            .map(|binding| &binding.key_macro)
+    }
+
+    /// Replaces whatever macro `key` played before.
+    pub fn bind_macro(&mut self, key: egui::Key, key_macro: KeyMacro) {
+        self.unbind_macro(key);
+        self.macros.push(MacroBinding { key, key_macro });
+    }
+
+    pub fn unbind_macro(&mut self, key: egui::Key) {
+        self.macros.retain(|binding| binding.key != key);
+    }
+
+    fn from_text(text: &str) -> Result<Self, String> {
+        let mut sections: Vec<(egui::Key, String)> = Vec::new();
+
+        for line in text.lines() {
+            if let Some(header) = line
+                .trim()
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+            {
+                let name = header
+                    .strip_prefix(MACRO_HEADER)
+                    .ok_or_else(|| format!("unknown section [{header}]"))?;
+                let key = egui::Key::from_name(name.trim())
+                    .ok_or_else(|| format!("unknown key {name} in [{header}]"))?;
+                sections.push((key, String::new()));
+            } else if let Some((_, steps)) = sections.last_mut() {
+                steps.push_str(line);
+                steps.push('\n');
+            } else {
+                let line = line.trim();
+                if !line.is_empty() && !line.starts_with('#') {
+                    return Err(format!("{line:?} is outside any section"));
+                }
+            }
+        }
+
+        let mut settings = Self::default();
+        for (key, steps) in sections {
+            let key_macro = KeyMacro::from_text(&steps)
+                .map_err(|err| format!("macro {}: {err}", key.name()))?;
+            settings.bind_macro(key, key_macro);
+        }
+        Ok(settings)
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for binding in &self.macros {
+            text.push('[');
+            text.push_str(MACRO_HEADER);
+            text.push_str(binding.key.name());
+            text.push_str("]\n");
+            text.push_str(&binding.key_macro.to_text());
+        }
+        text
+    }
+}
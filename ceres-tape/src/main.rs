@@ -0,0 +1,93 @@
+use std::{env, fs, path::Path};
+
+use anyhow::{Context, bail};
+use ceres_core::{
+    basic,
+    tape::DEFAULT_SAMPLE_RATE,
+    tape_image::{FileKind, TapeFile, TapeImage},
+};
+
+const USAGE: &str = "usage:
+  ceres-tape list <tape>
+  ceres-tape extract <tape> [directory]
+  ceres-tape convert <input> <output>
+
+Tapes are tape images or WAV recordings, converting to a .wav file
+modulates the image and anything else writes a tape image. BASIC
+programs are extracted as listings, other files as raw bytes.";
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["list", tape] => {
+            list(&open(tape)?);
+            Ok(())
+        }
+        ["extract", tape] => extract(&open(tape)?, Path::new(".")),
+        ["extract", tape, directory] => extract(&open(tape)?, Path::new(directory)),
+        ["convert", input, output] => convert(&open(input)?, Path::new(output)),
+        _ => bail!(USAGE),
+    }
+}
+
+fn open(path: &str) -> anyhow::Result<TapeImage> {
+    TapeImage::open(path).with_context(|| format!("cannot read tape {path}"))
+}
+
+fn list(image: &TapeImage) {
+    println!("#   KIND   NAME              START  ENTRY  LENGTH  CHECKSUM");
+    for (index, file) in image.files().iter().enumerate() {
+        println!(
+            "{index:<3} {:<6} {:<16}  {:04X}   {:04X}   {:>6}  {}",
+            file.kind().name(),
+            file.name(),
+            file.start(),
+            file.entry(),
+            file.data().len(),
+            if file.checksums_ok() { "ok" } else { "BAD" },
+        );
+    }
+}
+
+fn file_name(index: usize, file: &TapeFile, extension: &str) -> String {
+    let name: String = file
+        .name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{index:02}-{name}.{extension}")
+}
+
+fn extract(image: &TapeImage, directory: &Path) -> anyhow::Result<()> {
+    for (index, file) in image.files().iter().enumerate() {
+        // Programs that do not detokenize are kept as they are on tape
+        let listing = match file.kind() {
+            FileKind::Basic => basic::list(file.data()).ok(),
+            FileKind::Machine | FileKind::Data => None,
+        };
+        let (extension, contents) = match (&listing, file.kind()) {
+            (Some(listing), _) => ("bas", listing.as_bytes()),
+            (None, FileKind::Data) => ("dat", file.data()),
+            (None, FileKind::Basic | FileKind::Machine) => ("bin", file.data()),
+        };
+
+        let path = directory.join(file_name(index, file, extension));
+        fs::write(&path, contents).with_context(|| format!("cannot write {}", path.display()))?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+fn convert(image: &TapeImage, output: &Path) -> anyhow::Result<()> {
+    let result = if output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+    {
+        image.save_wav(output, DEFAULT_SAMPLE_RATE)
+    } else {
+        image.save(output)
+    };
+    result.with_context(|| format!("cannot write {}", output.display()))
+}